@@ -22,6 +22,8 @@ from serial.tools import hexlify_codec
 from pprint import pprint
 import urllib.request
 
+__version__ = '1.0.0'
+
 dev = False
 spiffs = True
 sota = False
@@ -126,6 +128,7 @@ def parse_args():
         metavar='ID',
         help='Firmware ID (1-30). If omitted, firmware is selected interactively. See API.md for IDs.'
     )
+    parser.add_argument('--version', action='version', version=__version__)
     parser.add_argument('-dev', '--dev', action='store_true', help='Use development firmware builds')
     parser.add_argument('-ns', '--no-spiffs', action='store_true', dest='no_spiffs', help='Do not flash SPIFFS')
     parser.add_argument('-sota', '--sota', action='store_true', help='Flash SOTA Relay firmware')
@@ -135,6 +138,18 @@ def parse_args():
         metavar='PATH',
         help='Build SPIFFS from this PlatformIO project (data/) and flash it instead of S3 image. Run: pio run -t buildfs in PATH.'
     )
+    parser.add_argument(
+        '--before',
+        default='default_reset',
+        choices=['default_reset', 'usb_reset', 'no_reset', 'no_reset_no_sync'],
+        help='esptool --before reset mode (default: default_reset)'
+    )
+    parser.add_argument(
+        '--after',
+        default='hard_reset',
+        choices=['hard_reset', 'soft_reset', 'no_reset', 'no_reset_stub'],
+        help='esptool --after reset mode (default: hard_reset)'
+    )
     return parser.parse_args()
 
 
@@ -444,7 +459,7 @@ try:
         firmware_file = urllib.request.urlretrieve('https://ncd-esp32.s3.amazonaws.com/SOTA_Relay/firmware.bin', firmware_path)
         partitions_file = urllib.request.urlretrieve('https://ncd-esp32.s3.amazonaws.com/SOTA_Relay/partitions.bin', partitions_path)
         bootloader_file = urllib.request.urlretrieve('https://ncd-esp32.s3.amazonaws.com/SOTA_Relay/bootloader.bin', bootloader_path)
-        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', 'default_reset', '--after', 'hard_reset', 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_path, '0x8000', partitions_path, '0x10000', firmware_path])
+        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', _args.before, '--after', _args.after, 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_path, '0x8000', partitions_path, '0x10000', firmware_path])
         status_code = 0
         raise SystemExit(0)
 
@@ -557,19 +572,19 @@ try:
     spiffs_bin = os.path.join(temp_dir, 'spiffs.bin')
 
     if firmware_choice == '1':
-        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', '--before', 'default_reset', '--after', 'hard_reset', 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '80m', '--flash_size', '4MB', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0xe000', boot_app0_bin, '0x10000', firmware_bin])
-        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', 'write_flash', '0x290000', spiffs_bin])
+        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', '--before', _args.before, '--after', _args.after, 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '80m', '--flash_size', '4MB', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0xe000', boot_app0_bin, '0x10000', firmware_bin])
+        espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', '--before', _args.before, '--after', _args.after, 'write_flash', '0x290000', spiffs_bin])
     else:
         if spiffs:
             # Only 5 and 14 use custom layout (spiffs at 0x383000). Others use default: spiffs at 0x290000.
             if firmware_choice in ('5', '14'):
-                espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', 'default_reset', '--after', 'hard_reset', 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0x00383000', spiffs_bin, '0x10000', firmware_bin])
+                espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', _args.before, '--after', _args.after, 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0x00383000', spiffs_bin, '0x10000', firmware_bin])
             else:
                 # Flash all required files: bootloader, partitions, boot_app0, firmware, and spiffs
-                espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', '--before', 'default_reset', '--after', 'hard_reset', 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '80m', '--flash_size', '4MB', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0xe000', boot_app0_bin, '0x10000', firmware_bin, '0x290000', spiffs_bin])
+                espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '460800', '--before', _args.before, '--after', _args.after, 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '80m', '--flash_size', '4MB', '0x1000', bootloader_bin, '0x8000', partitions_bin, '0xe000', boot_app0_bin, '0x10000', firmware_bin, '0x290000', spiffs_bin])
         else:
             print('no spiffs')
-            espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', 'default_reset', '--after', 'hard_reset', 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_bin, '0x10000', firmware_bin])
+            espmodule = esptool.main(['--chip', 'esp32', '--port', target_port, '--baud', '921600', '--before', _args.before, '--after', _args.after, 'write_flash', '-z', '--flash_mode', 'dio', '--flash_freq', '40m', '--flash_size', 'detect', '0x1000', bootloader_bin, '0x10000', firmware_bin])
 except SystemExit as e:
     status_code = e.code if e.code is not None else 1
 except Exception as e:
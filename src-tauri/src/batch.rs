@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{run_device_test, TestOptions, TestResult};
+use crate::operations;
+
+/// Outcome for one port in a batch; `error` is set when the test couldn't run at all
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchUnitResult {
+    pub port: String,
+    pub result: Option<TestResult>,
+    pub error: Option<String>,
+}
+
+impl BatchUnitResult {
+    fn passed(&self) -> bool {
+        self.result.as_ref().is_some_and(|r| r.success)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub results: Vec<BatchUnitResult>,
+    /// True when stop_on_failure or cancellation ended the batch before every port was tested
+    pub halted: bool,
+}
+
+/// Payload of the "batch-halted" event
+#[derive(Debug, Clone, Serialize)]
+struct BatchHalted {
+    port: String,
+    message: String,
+    remaining: usize,
+}
+
+/// Run the production test on each port in turn
+/// With `stop_on_failure` the batch halts at the first failed unit and emits "batch-halted";
+/// otherwise every port is tested regardless of earlier failures.
+#[tauri::command]
+pub async fn run_batch_test(
+    app_handle: AppHandle,
+    ports: Vec<String>,
+    options: Option<TestOptions>,
+    stop_on_failure: Option<bool>,
+) -> Result<BatchResult, String> {
+    let options = options.unwrap_or_default();
+    let stop_on_failure = stop_on_failure.unwrap_or(false);
+    let mut results = Vec::new();
+    let mut halted = false;
+    let mut operation = operations::begin(&app_handle, "batch", &ports.join(","));
+    let control = operation.control();
+
+    for (index, port) in ports.iter().enumerate() {
+        if control.is_cancelled() {
+            halted = true;
+            break;
+        }
+        let unit = match run_device_test(app_handle.clone(), port.clone(), Some(options.clone())).await {
+            Ok(result) => BatchUnitResult {
+                port: port.clone(),
+                result: Some(result),
+                error: None,
+            },
+            Err(e) => BatchUnitResult {
+                port: port.clone(),
+                result: None,
+                error: Some(e),
+            },
+        };
+        let passed = unit.passed();
+        let message = unit
+            .error
+            .clone()
+            .or_else(|| unit.result.as_ref().map(|r| r.message.clone()))
+            .unwrap_or_default();
+        results.push(unit);
+
+        if !passed && stop_on_failure {
+            let remaining = ports.len() - index - 1;
+            if remaining > 0 {
+                halted = true;
+                let _ = app_handle.emit(
+                    "batch-halted",
+                    BatchHalted {
+                        port: port.clone(),
+                        message,
+                        remaining,
+                    },
+                );
+            }
+            break;
+        }
+    }
+
+    let result = Ok(BatchResult { results, halted });
+    operation.finish(&result, |r| !r.halted && r.results.iter().all(BatchUnitResult::passed));
+    result
+}
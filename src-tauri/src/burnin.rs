@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{run_device_test, TestOptions, TestResult};
+use crate::operations;
+
+/// Outcome of a burn-in run on one unit
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BurninResult {
+    pub iterations: u32,
+    pub passed: u32,
+    pub failed: u32,
+    /// One result per iteration that ran, in order
+    pub results: Vec<TestResult>,
+    /// Set when an iteration couldn't run at all (e.g. the port disappeared); the run stops there
+    pub error: Option<String>,
+}
+
+/// Payload of the "burnin-progress" event, sent after each iteration
+#[derive(Debug, Clone, Serialize)]
+struct BurninProgress<'a> {
+    port: &'a str,
+    /// 1-based iteration just finished
+    iteration: u32,
+    iterations: u32,
+    passed: u32,
+    failed: u32,
+    success: bool,
+}
+
+/// Reset and re-test the same unit `iterations` times for reliability sampling.
+/// Each iteration hardware-resets the unit and runs the full test with `timeout_secs`.
+/// By default the run stops at the first failure; pass `stop_on_failure: false` to run them all.
+/// Iterations are stored as "burnin" results, outside yield and the session counters.
+#[tauri::command]
+pub async fn run_burnin_test(
+    app_handle: AppHandle,
+    port: String,
+    iterations: u32,
+    timeout_secs: Option<u64>,
+    options: Option<TestOptions>,
+    stop_on_failure: Option<bool>,
+) -> Result<BurninResult, String> {
+    if iterations == 0 {
+        return Err("Burn-in needs at least one iteration".to_string());
+    }
+    let stop_on_failure = stop_on_failure.unwrap_or(true);
+    let options = TestOptions {
+        timeout_secs: timeout_secs.or(options.as_ref().and_then(|o| o.timeout_secs)),
+        reset_before_test: true,
+        // The same unit passes every iteration by design
+        allow_duplicates: true,
+        record_kind: Some("burnin"),
+        ..options.unwrap_or_default()
+    };
+
+    let mut operation = operations::begin(&app_handle, "burnin", &port);
+    let control = operation.control();
+    let mut summary = BurninResult {
+        iterations,
+        passed: 0,
+        failed: 0,
+        results: Vec::new(),
+        error: None,
+    };
+
+    for iteration in 1..=iterations {
+        if control.is_cancelled() {
+            summary.error = Some("Burn-in cancelled".to_string());
+            break;
+        }
+        let result = match run_device_test(app_handle.clone(), port.clone(), Some(options.clone())).await {
+            Ok(result) => result,
+            Err(e) => {
+                summary.failed += 1;
+                summary.error = Some(format!("Iteration {}: {}", iteration, e));
+                break;
+            }
+        };
+
+        let success = result.success;
+        if success {
+            summary.passed += 1;
+        } else {
+            summary.failed += 1;
+        }
+        summary.results.push(result);
+        let _ = app_handle.emit(
+            "burnin-progress",
+            BurninProgress {
+                port: &port,
+                iteration,
+                iterations,
+                passed: summary.passed,
+                failed: summary.failed,
+                success,
+            },
+        );
+
+        if !success && stop_on_failure {
+            break;
+        }
+    }
+
+    let result = Ok(summary);
+    operation.finish(&result, |s| s.failed == 0 && s.passed == s.iterations);
+    result
+}
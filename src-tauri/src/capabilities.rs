@@ -0,0 +1,43 @@
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::commands::{get_flasher_path, python_available, python_command};
+use crate::esptool::resolve_esptool;
+
+/// Which features this station can run, so the UI can gray out the rest.
+/// Serial test, reset and config commands need neither Python nor esptool.
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub python: bool,
+    /// flash_firmware: ncd_flasher.py run through Python
+    pub catalog_flash: bool,
+    /// erase_device, read_flash and backup restore: esptool.py with Python, or a standalone esptool
+    pub esptool: bool,
+    /// run_device_test, factory_reset, read_device_config and other serial-only commands
+    pub serial: bool,
+    /// Why each unavailable capability is missing
+    pub reasons: Vec<String>,
+}
+
+/// Report which features are usable on this station
+#[tauri::command]
+pub async fn get_capabilities(app_handle: AppHandle) -> Result<Capabilities, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut reasons = Vec::new();
+        let python = python_available();
+        if !python {
+            reasons.push(format!("{} not found", python_command()));
+        }
+
+        let flasher = get_flasher_path(&app_handle).map_err(|e| reasons.push(e)).is_ok();
+        let esptool = resolve_esptool(&app_handle).map_err(|e| reasons.push(e)).is_ok();
+
+        Capabilities {
+            python,
+            catalog_flash: python && flasher,
+            esptool,
+            serial: true,
+            reasons,
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e))
+}
@@ -0,0 +1,282 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Returns true if the line carries the given production event, either bare
+/// (`[EVENT:NAME]`) or with inline parameters (`[EVENT:NAME key=value ...]`)
+pub fn line_has_event(line: &str, event: &str) -> bool {
+    let tag = format!("[EVENT:{}", event);
+    line.match_indices(&tag).any(|(start, _)| {
+        matches!(line[start + tag.len()..].chars().next(), Some(']') | Some(' '))
+    })
+}
+
+/// Names of every production event carried by the line, in order
+pub fn event_names(line: &str) -> Vec<String> {
+    line.match_indices("[EVENT:")
+        .filter_map(|(start, tag)| {
+            let rest = &line[start + tag.len()..];
+            let end = rest.find([']', ' '])?;
+            Some(rest[..end].to_string()).filter(|name| !name.is_empty())
+        })
+        .collect()
+}
+
+/// Human-readable label for the [CHECK] line emitted when an event is observed
+pub fn check_label(event: &str) -> String {
+    match event {
+        "BOOT_COMPLETE" => "Boot complete".to_string(),
+        "WIFI_CONNECTED" => "WiFi connected".to_string(),
+        "MQTT_CONNECTED" => "MQTT connected".to_string(),
+        "SENSOR_OK" => "Sensor OK".to_string(),
+        "MQTT_PUBLISH_OK" => "MQTT publish OK".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Event definitions starting with this are regular expressions matched against the raw line
+pub const REGEX_PREFIX: &str = "re:";
+
+/// A required, fail-fast or warn event definition: an event name matched as `[EVENT:NAME...]`,
+/// or a "re:"-prefixed regex for firmware whose event formatting drifts
+#[derive(Debug, Clone)]
+pub enum EventMatcher {
+    Name(String),
+    Pattern { definition: String, regex: Regex },
+}
+
+impl EventMatcher {
+    pub fn compile(definition: &str) -> Result<Self, String> {
+        match definition.strip_prefix(REGEX_PREFIX) {
+            Some(pattern) => {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| format!("Invalid event pattern '{}': {}", pattern, e))?;
+                Ok(EventMatcher::Pattern {
+                    definition: definition.to_string(),
+                    regex,
+                })
+            }
+            None => Ok(EventMatcher::Name(definition.to_string())),
+        }
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        match self {
+            EventMatcher::Name(name) => line_has_event(line, name),
+            EventMatcher::Pattern { regex, .. } => regex.is_match(line),
+        }
+    }
+
+    /// The definition as written, used in check lines and missing-event lists
+    pub fn name(&self) -> &str {
+        match self {
+            EventMatcher::Name(name) => name,
+            EventMatcher::Pattern { definition, .. } => definition,
+        }
+    }
+}
+
+/// Compile a list of event definitions, failing on the first invalid pattern
+pub fn compile_events(definitions: &[String]) -> Result<Vec<EventMatcher>, String> {
+    definitions.iter().map(|d| EventMatcher::compile(d)).collect()
+}
+
+/// One entry of a profile's required_events: a bare event definition, or an object that also
+/// gives the operator-facing name and the entry's position in the UI checklist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequiredEvent {
+    Definition(String),
+    Detailed {
+        event: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        order: Option<u32>,
+    },
+}
+
+impl RequiredEvent {
+    /// The event name or "re:" pattern
+    pub fn definition(&self) -> &str {
+        match self {
+            RequiredEvent::Definition(definition) => definition,
+            RequiredEvent::Detailed { event, .. } => event,
+        }
+    }
+}
+
+/// How an observed event appears in the operator's checklist
+#[derive(Debug, Clone, Serialize)]
+pub struct ChecklistItem {
+    pub event: String,
+    pub display_name: String,
+    /// Position in the checklist. None for events the profile doesn't require, which go last.
+    pub order: Option<u32>,
+    pub required: bool,
+}
+
+impl ChecklistItem {
+    /// An event seen on the wire that the profile doesn't list, shown under its raw name
+    pub fn unlisted(event: String) -> Self {
+        ChecklistItem {
+            display_name: event.clone(),
+            event,
+            order: None,
+            required: false,
+        }
+    }
+}
+
+/// Tracks which of a profile's required events have been seen during a test
+#[derive(Debug, Clone)]
+pub struct EventChecklist {
+    required: Vec<EventMatcher>,
+    items: Vec<ChecklistItem>,
+    seen: Vec<bool>,
+}
+
+impl EventChecklist {
+    /// Fails on the first invalid "re:" pattern. Entries without a display_name use the
+    /// check label, and entries without an order keep their position in the list.
+    pub fn compile(required: &[RequiredEvent]) -> Result<Self, String> {
+        let mut matchers = Vec::with_capacity(required.len());
+        let mut items = Vec::with_capacity(required.len());
+        for (index, entry) in required.iter().enumerate() {
+            let matcher = EventMatcher::compile(entry.definition())?;
+            let (display_name, order) = match entry {
+                RequiredEvent::Definition(_) => (None, None),
+                RequiredEvent::Detailed { display_name, order, .. } => (display_name.clone(), *order),
+            };
+            items.push(ChecklistItem {
+                event: matcher.name().to_string(),
+                display_name: display_name.unwrap_or_else(|| check_label(matcher.name())),
+                order: Some(order.unwrap_or(index as u32)),
+                required: true,
+            });
+            matchers.push(matcher);
+        }
+        Ok(EventChecklist {
+            seen: vec![false; matchers.len()],
+            required: matchers,
+            items,
+        })
+    }
+
+    /// Every required entry, in the order the UI checklist shows them
+    pub fn items(&self) -> Vec<&ChecklistItem> {
+        let mut items: Vec<&ChecklistItem> = self.items.iter().collect();
+        items.sort_by_key(|item| item.order);
+        items
+    }
+
+    /// Marks any not-yet-seen required event carried by `line`.
+    /// Returns the newly satisfied entry, if any, so the caller can report it once.
+    pub fn observe(&mut self, line: &str) -> Option<ChecklistItem> {
+        for (index, (event, seen)) in self.required.iter().zip(self.seen.iter_mut()).enumerate() {
+            if !*seen && event.matches(line) {
+                *seen = true;
+                return Some(self.items[index].clone());
+            }
+        }
+        None
+    }
+
+    /// True if the named event satisfies one of the required entries
+    pub fn requires(&self, event: &str) -> bool {
+        let line = format!("[EVENT:{}]", event);
+        self.required.iter().any(|matcher| matcher.matches(&line))
+    }
+
+    /// True once every required event has been observed.
+    /// An empty checklist is never complete, so a misconfigured profile can't pass a unit.
+    pub fn is_complete(&self) -> bool {
+        !self.required.is_empty() && self.seen.iter().all(|s| *s)
+    }
+
+    /// Entries for the required events that have not been observed yet, in profile order
+    pub fn missing_items(&self) -> Vec<&ChecklistItem> {
+        self.items
+            .iter()
+            .zip(&self.seen)
+            .filter(|(_, seen)| !**seen)
+            .map(|(item, _)| item)
+            .collect()
+    }
+
+    /// Required events that have not been observed yet, in profile order
+    pub fn missing(&self) -> Vec<&str> {
+        self.required
+            .iter()
+            .zip(&self.seen)
+            .filter(|(_, seen)| !**seen)
+            .map(|(event, _)| event.name())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::builtin_profiles;
+
+    fn local_sensor() -> EventChecklist {
+        let profile = builtin_profiles().into_iter().find(|p| p.name == "local-sensor").unwrap();
+        EventChecklist::compile(&profile.required_events).unwrap()
+    }
+
+    fn observe_all(checklist: &mut EventChecklist, lines: &[&str]) {
+        for line in lines {
+            checklist.observe(line);
+        }
+    }
+
+    #[test]
+    fn local_sensor_completes_on_boot_and_sensor() {
+        let mut checklist = local_sensor();
+        observe_all(&mut checklist, &["[EVENT:BOOT_COMPLETE FIRMWARE=1.2 MAC=AA:BB:CC:DD:EE:FF]", "[EVENT:SENSOR_OK TEMP=21.5]"]);
+        assert!(checklist.is_complete());
+        assert!(checklist.missing().is_empty());
+    }
+
+    #[test]
+    fn local_sensor_incomplete_without_sensor() {
+        let mut checklist = local_sensor();
+        observe_all(&mut checklist, &["[EVENT:BOOT_COMPLETE FIRMWARE=1.2]"]);
+        assert!(!checklist.is_complete());
+        assert_eq!(checklist.missing(), vec!["SENSOR_OK"]);
+    }
+
+    #[test]
+    fn local_sensor_ignores_network_events() {
+        let mut checklist = local_sensor();
+        observe_all(&mut checklist, &["[EVENT:BOOT_COMPLETE]", "[EVENT:WIFI_CONNECTED]", "[EVENT:MQTT_CONNECTED]"]);
+        assert!(!checklist.is_complete());
+        observe_all(&mut checklist, &["[EVENT:SENSOR_OK]", "[EVENT:MQTT_PUBLISH_OK]"]);
+        assert!(checklist.is_complete());
+    }
+
+    #[test]
+    fn event_names_match_whole_names_only() {
+        let matcher = EventMatcher::compile("SENSOR_OK").unwrap();
+        assert!(matcher.matches("[EVENT:SENSOR_OK]"));
+        assert!(matcher.matches("[EVENT:SENSOR_OK TEMP=21.5]"));
+        assert!(!matcher.matches("[EVENT:SENSOR_OK_2]"));
+        assert_eq!(event_names("[EVENT:BOOT_COMPLETE MAC=AA] [EVENT:SENSOR_OK]"), vec!["BOOT_COMPLETE", "SENSOR_OK"]);
+    }
+
+    #[test]
+    fn regex_definitions_match_the_raw_line() {
+        let matcher = EventMatcher::compile(r"re:^\[?EVENT:SENSOR[_ ]OK").unwrap();
+        assert!(matcher.matches("[EVENT:SENSOR_OK]"));
+        assert!(matcher.matches("EVENT:SENSOR OK temp=21"));
+        assert!(!matcher.matches("[EVENT:SENSOR_FAIL]"));
+        assert_eq!(matcher.name(), r"re:^\[?EVENT:SENSOR[_ ]OK");
+    }
+
+    #[test]
+    fn invalid_regex_definition_is_rejected() {
+        let error = EventMatcher::compile("re:SENSOR_(OK").unwrap_err();
+        assert!(error.starts_with("Invalid event pattern 'SENSOR_(OK'"));
+        assert!(compile_events(&["BOOT_COMPLETE".to_string(), "re:(".to_string()]).is_err());
+    }
+}
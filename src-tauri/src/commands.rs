@@ -1,23 +1,129 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::io::{BufRead, BufReader, Write};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::ops::{Deref, DerefMut};
 use std::time::Duration;
+use serialport::{ClearBuffer, SerialPort};
 use tauri::{AppHandle, Emitter, Manager};
 
-/// Returns the appropriate Python command for the current platform
-fn python_command() -> &'static str {
-    if cfg!(target_os = "windows") {
-        "python"
-    } else {
-        "python3"
+use crate::checklist::{ChecklistItem, EventChecklist};
+use crate::config;
+use crate::esptool::{detect_chip, forget_chip, parse_chip_line, parse_mac_line, resolve_esptool, Esptool, ResetConfig};
+use crate::integrity::{ensure_esptool_intact, ensure_flasher_intact};
+use crate::mirror::{emit_output, emit_stderr, OutputSink};
+use crate::operations::{self, acquire_process_slot, OperationControl};
+use crate::outbox;
+use crate::profiles::{self, TestProfile};
+use crate::protocol::{LineEnding, Protocol};
+use crate::results;
+use crate::session::TestSession;
+use crate::session_stats;
+use crate::stages::FlashStageTracker;
+use crate::signal::{notify_complete, signal_result};
+use crate::state::AppState;
+use crate::throughput::{parse_flash_size, report_throughput, Throughput, ThroughputMeter, DEFAULT_FLASH_SIZE_BYTES};
+
+/// Interpreter chosen with set_python_path, applied from the config at startup
+static PYTHON_PATH: RwLock<Option<String>> = RwLock::new(None);
+
+/// Returns the Python command to run: the interpreter set with set_python_path,
+/// otherwise the platform's default on PATH
+pub fn python_command() -> String {
+    let configured = PYTHON_PATH.read().ok().and_then(|p| p.clone());
+    configured.unwrap_or_else(|| {
+        if cfg!(target_os = "windows") { "python" } else { "python3" }.to_string()
+    })
+}
+
+/// Make `path` the interpreter python_command returns; None restores the default
+pub fn set_python_override(path: Option<String>) {
+    if let Ok(mut configured) = PYTHON_PATH.write() {
+        *configured = path;
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Run `<python> --version`; returns its version line
+pub fn probe_python(python: &str) -> Result<String, String> {
+    let output = Command::new(python)
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("Python interpreter '{}' did not run: {}", python, e))?;
+    if !output.status.success() {
+        return Err(format!("Python interpreter '{}' exited with {}", python, output.status));
+    }
+    // Python 2 printed its version on stderr
+    let text = [output.stdout, output.stderr].concat();
+    Ok(String::from_utf8_lossy(&text).trim().to_string())
+}
+
+/// Whether the Python interpreter can be run. Checked once per interpreter; without it the
+/// catalog flasher and esptool.py are unavailable, but serial commands and a standalone
+/// esptool still work.
+pub fn python_available() -> bool {
+    static AVAILABLE: Mutex<Option<(String, bool)>> = Mutex::new(None);
+    let python = python_command();
+    let Ok(mut cached) = AVAILABLE.lock() else {
+        return probe_python(&python).is_ok();
+    };
+    match &*cached {
+        Some((checked, available)) if *checked == python => *available,
+        _ => {
+            let available = probe_python(&python).is_ok();
+            if !available {
+                log::warn!("{} not found; catalog flashing is disabled", python);
+            }
+            *cached = Some((python, available));
+            available
+        }
+    }
+}
+
+/// Use a specific Python interpreter for the flasher and esptool.py; None restores the default.
+/// The interpreter must run `--version` successfully; returns the version it reports.
+#[tauri::command]
+pub async fn set_python_path(app_handle: AppHandle, path: Option<String>) -> Result<Option<String>, String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    let version = match path.clone() {
+        Some(path) => Some(
+            tokio::task::spawn_blocking(move || probe_python(&path))
+                .await
+                .map_err(|e| format!("Task join error: {}", e))??,
+        ),
+        None => None,
+    };
+
+    config::update(&app_handle, |c| c.python_path = path.clone())?;
+    set_python_override(path);
+    // Tool versions were probed with the old interpreter
+    let state = app_handle.state::<AppState>();
+    *state.versions.lock().map_err(|e| e.to_string())? = None;
+    Ok(version)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SerialPortInfo {
     pub name: String,
     pub port_type: String,
+    /// USB serial number, vendor id and product id; None for non-USB ports or when the
+    /// adapter doesn't report them. Lets identical adapters on a fixture be told apart.
+    pub serial_number: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+}
+
+/// How many times list_serial_ports tries to enumerate before falling back to the cache
+const PORT_LIST_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortList {
+    pub ports: Vec<SerialPortInfo>,
+    /// True when enumeration failed and `ports` is the last successful result
+    pub stale: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +131,18 @@ pub struct FlashResult {
     pub success: bool,
     pub message: String,
     pub output: String,
+    pub throughput: Option<Throughput>,
+    /// Chip family and eFuse MAC esptool reported while flashing (e.g. "esp32").
+    /// Populated even when the flash fails, so a failed unit can still be identified.
+    pub detected_chip: Option<String>,
+    pub detected_mac: Option<String>,
+    /// Why a failed flash failed, when recognisable: "disconnected" if the USB link dropped
+    pub error_category: Option<String>,
+    /// Phase a failed flash stopped in: "erase" (with erase_first), "flash" or "verify"
+    pub failed_phase: Option<String>,
+    /// For flash_if_needed: "verified" when the image already matched and nothing was
+    /// written, "flashed" when it was written and verified again
+    pub path_taken: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,38 +150,160 @@ pub struct TestResult {
     pub success: bool,
     pub message: String,
     pub events: Vec<String>,
+    /// Firmware and MAC from BOOT_COMPLETE, kept on failures so a retest is tied to the same unit
     pub firmware_version: Option<String>,
     pub mac_address: Option<String>,
+    /// Every numeric reading reported on the SENSOR_OK event, by sensor name
+    pub sensor_readings: HashMap<String, f64>,
+    /// Every key=value parameter reported on any event, last value wins
+    pub device_attributes: HashMap<String, String>,
+    /// Soft-check findings that were reported but didn't affect `success`
+    pub warnings: Vec<String>,
+    /// Values of the SENSOR_SAMPLE events seen before SENSOR_OK, in order
+    pub sensor_samples: Vec<f64>,
+    /// Unexpected reset the ROM reported during the run (e.g. "BROWNOUT", "RTCWDT_RTC_RESET")
+    pub reset_reason: Option<String>,
+    /// Coefficients reported on the CAL_DONE event, e.g. {"offset": 0.12, "gain": 1.003}
+    pub calibration: Option<HashMap<String, f64>>,
+    /// Line acknowledging the post_pass_command, when one was sent
+    pub post_pass_ack: Option<String>,
+    /// Summary per numeric field of the TELEMETRY events captured after SENSOR_OK
+    pub telemetry: HashMap<String, TelemetryStats>,
+    /// Firmware build time from BOOT_COMPLETE's BUILD parameter, as RFC 3339 UTC
+    pub build_time: Option<String>,
+    /// Times the test command was sent (more than 1 when the run had to be retried)
+    pub attempts: u32,
+    pub quality: TestQuality,
+    /// When each event was first seen, in order
+    #[serde(default)]
+    pub event_times: Vec<EventTime>,
+    /// Boot output kept apart from events when capture_boot_log is set
+    #[serde(default)]
+    pub boot_log: Vec<String>,
+    /// Each required event as a timed step, in the order they passed, then those that didn't
+    #[serde(default)]
+    pub steps: Vec<TestStep>,
+    /// Internal temperature from BOOT_COMPLETE's temp_c parameter
+    #[serde(default)]
+    pub boot_temp_c: Option<f64>,
+    /// Where mac_address came from: "boot_event" (BOOT_COMPLETE) or "efuse" (read by esptool
+    /// while flashing, used when the test reported none)
+    #[serde(default)]
+    pub mac_source: Option<String>,
+    /// Outcome of the allowed_firmware check, when one was made
+    #[serde(default)]
+    pub firmware_check: Option<FirmwareCheck>,
+}
+
+/// The booted firmware version checked against allowed_firmware
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirmwareCheck {
+    /// Version from BOOT_COMPLETE's FIRMWARE parameter; None when it reported none
+    pub version: Option<String>,
+    pub approved: bool,
+    /// The allowed_firmware entry the version matched
+    pub matched: Option<String>,
+}
+
+/// One required check of a test run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestStep {
+    /// Display name of the required event
+    pub name: String,
+    pub passed: bool,
+    /// Milliseconds since the test command was sent: when the previous step finished
+    pub started_ms: u64,
+    /// When the event arrived, or when the run ended for a step that didn't pass
+    pub finished_ms: u64,
+    /// The line that satisfied the step, or why it didn't pass
+    pub detail: Option<String>,
+}
+
+/// First sighting of an event during a test run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventTime {
+    pub event: String,
+    /// Milliseconds since the test command was sent
+    pub t_ms: u64,
+}
+
+/// How cleanly a unit passed, so marginal units can be told apart in yield tracking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestQuality {
+    CleanPass,
+    /// Passed, but only after the test command was resent
+    PassWithRetry,
+    /// Passed first time with soft-check warnings
+    PassWithWarnings,
+    Fail,
+}
+
+impl TestQuality {
+    pub fn assess(result: &TestResult) -> Self {
+        if !result.success {
+            TestQuality::Fail
+        } else if result.attempts > 1 {
+            TestQuality::PassWithRetry
+        } else if !result.warnings.is_empty() {
+            TestQuality::PassWithWarnings
+        } else {
+            TestQuality::CleanPass
+        }
+    }
+}
+
+/// Summary of one telemetry field over the capture window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl TelemetryStats {
+    /// None for an empty series
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(TelemetryStats {
+            count: values.len(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ResetResult {
     pub success: bool,
     pub message: String,
+    pub throughput: Option<Throughput>,
+    /// Chip family esptool reported, for operations that go through esptool
+    pub detected_chip: Option<String>,
 }
 
-/// List all available serial ports
-#[tauri::command]
-pub fn list_serial_ports() -> Result<Vec<SerialPortInfo>, String> {
+/// Enumerate serial ports once, without retries
+pub(crate) fn enumerate_ports() -> Result<Vec<SerialPortInfo>, String> {
     let ports = serialport::available_ports()
         .map_err(|e| format!("Failed to list serial ports: {}", e))?;
 
     let port_infos: Vec<SerialPortInfo> = ports
         .into_iter()
         // On macOS, filter out /dev/tty.* ports - we only want /dev/cu.* (call-out devices)
-        .filter(|p| {
-            #[cfg(target_os = "macos")]
-            {
-                !p.port_name.starts_with("/dev/tty.")
-            }
-            #[cfg(not(target_os = "macos"))]
-            {
-                true
-            }
-        })
+        .filter(|p| !cfg!(target_os = "macos") || !p.port_name.starts_with("/dev/tty."))
         .map(|p| {
+            let mut serial_number = None;
+            let mut vid = None;
+            let mut pid = None;
             let port_type = match p.port_type {
                 serialport::SerialPortType::UsbPort(info) => {
+                    serial_number = info.serial_number;
+                    vid = Some(info.vid);
+                    pid = Some(info.pid);
                     format!(
                         "USB - {}",
                         info.product.unwrap_or_else(|| "Unknown".to_string())
@@ -76,6 +316,9 @@ pub fn list_serial_ports() -> Result<Vec<SerialPortInfo>, String> {
             SerialPortInfo {
                 name: p.port_name,
                 port_type,
+                serial_number,
+                vid,
+                pid,
             }
         })
         .collect();
@@ -83,13 +326,117 @@ pub fn list_serial_ports() -> Result<Vec<SerialPortInfo>, String> {
     Ok(port_infos)
 }
 
+/// List all available serial ports
+/// Enumeration is retried with a short backoff because some USB stacks briefly fail
+/// right after a plug/unplug; if every attempt fails, the last good list is returned
+/// flagged as stale.
+#[tauri::command]
+pub async fn list_serial_ports(app_handle: AppHandle) -> Result<PortList, String> {
+    tokio::task::spawn_blocking(move || {
+        let mut last_error = String::new();
+        for attempt in 0..PORT_LIST_ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(Duration::from_millis(100 * attempt as u64));
+            }
+            match enumerate_ports() {
+                Ok(ports) => {
+                    let state = app_handle.state::<AppState>();
+                    if let Ok(mut cached) = state.last_ports.lock() {
+                        *cached = Some(ports.clone());
+                    }
+                    return Ok(PortList { ports, stale: false });
+                }
+                Err(e) => last_error = e,
+            }
+        }
+
+        let state = app_handle.state::<AppState>();
+        let cached = state.last_ports.lock().ok().and_then(|c| c.clone());
+        match cached {
+            Some(ports) => {
+                log::warn!("{}; returning cached port list", last_error);
+                Ok(PortList { ports, stale: true })
+            }
+            None => Err(last_error),
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Pick the device port automatically when exactly one USB serial port matches the
+/// configured vid/pid allowlist. Zero or several matches are an error naming the count,
+/// so the UI can fall back to manual selection.
+#[tauri::command]
+pub async fn auto_select_port(app_handle: AppHandle) -> Result<SerialPortInfo, String> {
+    let allowlist = config::current(&app_handle)
+        .port_allowlist
+        .unwrap_or_else(|| config::DEFAULT_PORT_ALLOWLIST.to_vec());
+    tokio::task::spawn_blocking(move || {
+        let mut candidates: Vec<SerialPortInfo> = enumerate_ports()?
+            .into_iter()
+            .filter(|p| match (p.vid, p.pid) {
+                (Some(vid), Some(pid)) => allowlist.iter().any(|id| id.matches(vid, pid)),
+                _ => false,
+            })
+            .collect();
+        match candidates.len() {
+            1 => Ok(candidates.remove(0)),
+            0 => Err("No matching device found (0 candidates) - select a port manually".to_string()),
+            count => Err(format!("{} matching devices found - select a port manually", count)),
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e))?
+}
+
+/// Set (or reset to the built-in list, with None) the USB ids auto_select_port accepts
+#[tauri::command]
+pub fn set_port_allowlist(app_handle: AppHandle, allowlist: Option<Vec<config::UsbId>>) -> Result<(), String> {
+    config::update(&app_handle, |c| c.port_allowlist = allowlist)?;
+    Ok(())
+}
+
+/// Reject empty port names and ports that aren't currently enumerated,
+/// so the operator gets actionable feedback instead of a raw OS error
+pub(crate) fn validate_port(port: &str) -> Result<(), String> {
+    if port.trim().is_empty() {
+        return Err("No serial port selected".to_string());
+    }
+
+    // If enumeration itself fails, let the open attempt report the real error
+    if let Ok(ports) = serialport::available_ports() {
+        if !ports.iter().any(|p| p.port_name == port) {
+            return Err(format!(
+                "Port '{}' is not available — refresh the port list",
+                port
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 /// Get the path to the bundled flasher script
-fn get_flasher_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+pub(crate) fn get_flasher_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let mut searched = Vec::new();
+
+    // A path set with set_flasher_path wins over everything else
+    if let Some(custom_path) = config::current(app_handle).flasher_path {
+        if custom_path.exists() {
+            return Ok(custom_path);
+        }
+        searched.push(format!("custom: {}", custom_path.display()));
+    }
+
     // Try to get the resource path for bundled app
-    if let Ok(resource_path) = app_handle.path().resource_dir() {
-        let script_path = resource_path.join("flasher").join("ncd_flasher.py");
-        if script_path.exists() {
-            return Ok(script_path);
+    match app_handle.path().resource_dir() {
+        Ok(resource_path) => {
+            let script_path = resource_path.join("flasher").join("ncd_flasher.py");
+            if script_path.exists() {
+                return Ok(script_path);
+            }
+            searched.push(format!("resource: {}", script_path.display()));
+        }
+        Err(e) => {
+            log::warn!("Could not resolve resource directory: {}", e);
+            searched.push(format!("resource: unavailable ({})", e));
         }
     }
     
@@ -102,235 +449,1224 @@ fn get_flasher_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
     if dev_path.exists() {
         return Ok(dev_path);
     }
-    
-    Err("Could not find ncd_flasher.py in bundled resources".to_string())
+    searched.push(format!("dev: {}", dev_path.display()));
+
+    let searched = searched.join(", ");
+    log::error!("Could not find ncd_flasher.py. Searched: {}", searched);
+    Err(format!("Could not find ncd_flasher.py. Searched: {}", searched))
+}
+
+/// Use a specific ncd_flasher.py instead of the bundled one (None restores the default search)
+#[tauri::command]
+pub fn set_flasher_path(app_handle: AppHandle, path: Option<String>) -> Result<(), String> {
+    let path = path.map(PathBuf::from);
+    if let Some(path) = &path {
+        if !path.is_file() {
+            return Err(format!("Flasher script not found: {}", path.display()));
+        }
+    }
+    config::update(&app_handle, |c| c.flasher_path = path)?;
+    Ok(())
+}
+
+/// esptool/pyserial output seen when the USB link drops mid-operation
+const DISCONNECT_PATTERNS: [&str; 4] = [
+    "serial data stream stopped",
+    "device disconnected",
+    "clearcommerror failed",
+    "device reports readiness to read but returned no data",
+];
+
+fn is_disconnect_line(line: &str) -> bool {
+    let line = line.to_lowercase();
+    DISCONNECT_PATTERNS.iter().any(|pattern| line.contains(pattern))
+}
+
+/// Lines of tool output, split on '\r' as well as '\n'. esptool redraws its progress in place
+/// with a bare '\r', which a plain `lines()` would run together into one line. Blank lines are
+/// skipped, so no returned line holds a line break or carriage return.
+pub(crate) struct OutputLines<R> {
+    reader: BufReader<R>,
+}
+
+pub(crate) fn output_lines<R: Read>(reader: R) -> OutputLines<R> {
+    OutputLines {
+        reader: BufReader::new(reader),
+    }
+}
+
+impl<R: Read> Iterator for OutputLines<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => &[],
+            };
+            if buffer.is_empty() {
+                let line = String::from_utf8_lossy(&line).into_owned();
+                return (!line.trim().is_empty()).then_some(line);
+            }
+            match buffer.iter().position(|&b| b == b'\r' || b == b'\n') {
+                Some(end) => {
+                    line.extend_from_slice(&buffer[..end]);
+                    self.reader.consume(end + 1);
+                    let text = String::from_utf8_lossy(&line).into_owned();
+                    if !text.trim().is_empty() {
+                        return Some(text);
+                    }
+                    line.clear();
+                }
+                None => {
+                    let read = buffer.len();
+                    line.extend_from_slice(buffer);
+                    self.reader.consume(read);
+                }
+            }
+        }
+    }
+}
+
+/// Lines of a child's stdout and stderr as they arrive, each flagged true when it came from
+/// stderr. Both pipes are drained at once, so a chatty stderr can't fill up and stall the child.
+pub(crate) fn merged_output_lines<O, E>(stdout: Option<O>, stderr: Option<E>) -> impl Iterator<Item = (String, bool)>
+where
+    O: Read + Send + 'static,
+    E: Read + Send + 'static,
+{
+    let (sender, lines) = mpsc::channel();
+    if let Some(stderr) = stderr {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            for line in output_lines(stderr) {
+                if sender.send((line, true)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    if let Some(stdout) = stdout {
+        std::thread::spawn(move || {
+            for line in output_lines(stdout) {
+                if sender.send((line, false)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    lines.into_iter()
+}
+
+/// Shown while an operation waits for a free process slot
+pub(crate) const QUEUED_MESSAGE: &str = "Queued — waiting for a flash slot";
+
+/// Window in which a repeated flash_firmware on the same port is rejected (e.g. a double click)
+pub(crate) const DEFAULT_FLASH_DEBOUNCE_MS: u64 = 2000;
+
+/// Reject a flash of `port` within the debounce window of the last one starting or
+/// finishing; otherwise note this one as started
+fn debounce_flash(app_handle: &AppHandle, port: &str) -> Result<(), String> {
+    let window = Duration::from_millis(config::current(app_handle).flash_debounce_ms.unwrap_or(DEFAULT_FLASH_DEBOUNCE_MS));
+    let state = app_handle.state::<AppState>();
+    let mut last_flash = state.last_flash.lock().map_err(|e| e.to_string())?;
+    if last_flash.get(port).is_some_and(|last| last.elapsed() < window) {
+        return Err("Flash already in progress or just completed for this port".to_string());
+    }
+    last_flash.insert(port.to_string(), std::time::Instant::now());
+    Ok(())
+}
+
+/// Restart the debounce window of `port` when its flash finishes
+fn mark_flash_finished(app_handle: &AppHandle, port: &str) {
+    if let Ok(mut last_flash) = app_handle.state::<AppState>().last_flash.lock() {
+        last_flash.insert(port.to_string(), std::time::Instant::now());
+    }
+}
+
+/// Set how long after a flash starts or finishes a repeat on the same port is rejected
+/// (None restores the default of 2 seconds, 0 turns the check off)
+#[tauri::command]
+pub fn set_flash_debounce(app_handle: AppHandle, window_ms: Option<u64>) -> Result<(), String> {
+    config::update(&app_handle, |c| c.flash_debounce_ms = window_ms)?;
+    Ok(())
 }
 
 /// Flash firmware to the device using the Python ncd_flasher script
-/// Emits "flash-output" events for real-time progress
+/// With `erase_first`, the whole flash is erased with esptool beforehand; a failed erase
+/// stops before anything is written. Queues while every process slot is in use, and rejects
+/// a repeat request for the same port within the flash_debounce_ms window.
+/// `connect_timeout_secs` overrides the configured time esptool spends syncing with the bootloader.
+/// Emits "flash-output" events for real-time progress, with stderr lines also on "flash-output-err"
 #[tauri::command]
-pub async fn flash_firmware(app_handle: AppHandle, port: String, firmware_id: u32) -> Result<FlashResult, String> {
+pub async fn flash_firmware(
+    app_handle: AppHandle,
+    port: String,
+    firmware_id: u32,
+    erase_first: Option<bool>,
+    connect_timeout_secs: Option<u32>,
+) -> Result<FlashResult, String> {
+    validate_port(&port)?;
+    if !python_available() {
+        return Err(format!("{} was not found; firmware catalog flashing needs Python", python_command()));
+    }
+    let erase_esptool = match erase_first {
+        Some(true) => Some(resolve_esptool(&app_handle)?),
+        _ => None,
+    };
+    if let Some(esptool) = &erase_esptool {
+        ensure_esptool_intact(&app_handle, esptool)?;
+    }
     let script_path = get_flasher_path(&app_handle)?;
+    ensure_flasher_intact(&app_handle, &script_path)?;
     let script_dir = script_path.parent()
         .ok_or("Could not get script directory")?
         .to_path_buf();
+    // Only once nothing else can reject the request, so a corrected retry isn't debounced
+    debounce_flash(&app_handle, &port)?;
+    let mut operation = operations::begin(&app_handle, "flash", &port);
     
     // Run the blocking operation in a separate thread
     let handle = app_handle.clone();
+    let control = operation.control();
+    let mut reset = config::current(&app_handle).flash_reset;
+    reset.connect_timeout_secs = connect_timeout_secs.or(reset.connect_timeout_secs);
     let result = tokio::task::spawn_blocking(move || {
-        let mut child = Command::new(python_command())
+        let _slot = acquire_process_slot(&handle, &control, || {
+            emit_output(&handle, "flash-output", &port, QUEUED_MESSAGE)
+        })?;
+        // The erase log leads the flash output, so a result explains both phases
+        let mut all_output = Vec::new();
+        if let Some(esptool) = &erase_esptool {
+            emit_output(&handle, "flash-output", &port, "[INFO] Phase 1/2: erasing flash");
+            let (erased, erase_output) = erase_flash(&handle, esptool, &port, None, &reset, &control, "flash-output")
+                .map_err(|e| format!("Erase phase failed: {}", e))?;
+            if !erased.success {
+                emit_output(&handle, "flash-output", &port, "[CHECK] ✗ Erase failed - firmware was not flashed");
+                let result = FlashResult {
+                    success: false,
+                    message: "Erase phase failed - firmware was not flashed".to_string(),
+                    output: erase_output.join("\n"),
+                    throughput: None,
+                    detected_chip: erased.detected_chip,
+                    detected_mac: None,
+                    error_category: None,
+                    failed_phase: Some("erase".to_string()),
+                    path_taken: None,
+                };
+                results::record(&handle, "flash", &port, None, false, &result.message, &result);
+                return Ok(result);
+            }
+            all_output = erase_output;
+            emit_output(&handle, "flash-output", &port, "[INFO] Phase 2/2: flashing firmware");
+        }
+
+        let mut command = Command::new(python_command());
+        command
             .arg(&script_path)
             .arg("--port")
             .arg(&port)
             .arg("--firmware")
-            .arg(firmware_id.to_string())
+            .arg(firmware_id.to_string());
+        reset.apply(&mut command, &port)?;
+        let mut child = command
             .current_dir(&script_dir)
+            // Unbuffered so lines arrive as they're printed and phase timing is meaningful
+            .env("PYTHONUNBUFFERED", "1")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
             .map_err(|e| format!("Failed to execute flash script: {}", e))?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        control.attach_child(child);
 
-        let mut all_output = Vec::new();
+        let mut meter = ThroughputMeter::default();
+        let mut stages = FlashStageTracker::default();
+        let mut detected_chip = None;
+        let mut detected_mac = None;
         
         // Read stdout in real-time
-        if let Some(stdout) = child.stdout.take() {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    // Emit event to frontend
-                    let _ = handle.emit("flash-output", &line);
-                    all_output.push(line);
+        if let Some(stdout) = stdout {
+            for line in output_lines(stdout) {
+                // Structured progress lines become events rather than output
+                if stages.observe(&handle, &port, &line) {
+                    continue;
+                }
+                // Emit event to frontend
+                emit_output(&handle, "flash-output", &port, &line);
+                meter.observe_write(&line);
+                if let Some(chip) = parse_chip_line(&line) {
+                    detected_chip = Some(chip.to_string());
+                }
+                if let Some(mac) = parse_mac_line(&line) {
+                    detected_mac = Some(mac);
                 }
+                all_output.push(line);
             }
         }
         
-        // Read any remaining stderr
-        if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    let _ = handle.emit("flash-output", &line);
-                    all_output.push(line);
-                }
+        // Read any remaining stderr; on failure its last line is usually the actual error
+        let mut last_error = None;
+        if let Some(stderr) = stderr {
+            for line in output_lines(stderr) {
+                emit_stderr(&handle, "flash-output", &port, &line);
+                last_error = Some(line.trim().to_string());
+                all_output.push(line);
             }
         }
         
-        let status = child.wait().map_err(|e| format!("Failed to wait for process: {}", e))?;
+        let status = control.wait_child()?;
+        if control.is_cancelled() {
+            return Err("Firmware flash cancelled".to_string());
+        }
         let combined_output = all_output.join("\n");
         let success = status.success() && combined_output.contains("Status: Success");
 
-        Ok::<FlashResult, String>(FlashResult {
+        let throughput = meter.finish();
+        if let Some(throughput) = &throughput {
+            report_throughput(&handle, "flash-output", &port, throughput);
+        }
+
+        let disconnected = !success && all_output.iter().any(|line| is_disconnect_line(line));
+        let mut message = match (success, &detected_mac) {
+            (true, _) => "Firmware flashed successfully".to_string(),
+            (false, _) if disconnected => "USB disconnected during flash — reseat the board and retry".to_string(),
+            (false, Some(mac)) => format!("Firmware flash failed (MAC {})", mac),
+            (false, None) => "Firmware flash failed".to_string(),
+        };
+        if let (false, false, Some(error)) = (success, disconnected, &last_error) {
+            message = format!("{}: {}", message, error);
+        }
+        let result = FlashResult {
             success,
-            message: if success {
-                "Firmware flashed successfully".to_string()
-            } else {
-                "Firmware flash failed".to_string()
-            },
+            message,
+            error_category: disconnected.then(|| "disconnected".to_string()),
+            failed_phase: (!success).then(|| "flash".to_string()),
+            path_taken: None,
             output: combined_output,
-        })
-    }).await.map_err(|e| format!("Task join error: {}", e))??;
+            throughput,
+            detected_chip,
+            detected_mac,
+        };
+        results::record(&handle, "flash", &port, result.detected_mac.as_deref(), result.success, &result.message, &result);
 
-    Ok(result)
+        Ok::<FlashResult, String>(result)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    mark_flash_finished(&app_handle, operation.port());
+    operation.finish(&result, |r| r.success);
+    notify_complete(&app_handle, "Flash", operation.port(), &result, |r| (r.success, &r.message));
+    result
+}
+
+/// Inclusive range a reading must fall within
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Bounds {
+    pub fn contains(&self, value: f64) -> bool {
+        value >= self.min && value <= self.max
+    }
+}
+
+const DEFAULT_TEST_TIMEOUT_SECS: u64 = 60;
+const QUICK_TEST_TIMEOUT_SECS: u64 = 20;
+
+/// A prompt printed by interactive test firmware and the answer to send back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestPrompt {
+    /// Text to look for in a line, or "re:<regex>"
+    pub pattern: String,
+    /// Sent followed by the test's line ending; empty just presses Enter
+    #[serde(default)]
+    pub response: String,
+}
+
+/// Optional tunables for run_device_test; every field has a sensible default
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestOptions {
+    /// Name of the test profile selecting the required events (defaults to the active profile,
+    /// "full" until switch_profile picks another)
+    pub profile: Option<String>,
+    /// Separate UART carrying debug/telemetry output, read alongside the command port
+    pub log_port: Option<String>,
+    /// Allowed range per sensor reading on SENSOR_OK, e.g. {"temp": {"min": 10, "max": 40}}
+    pub sensor_bounds: HashMap<String, Bounds>,
+    /// Allowed range per calibration coefficient on CAL_DONE, e.g. {"gain": {"min": 0.9, "max": 1.1}}
+    pub calibration_bounds: HashMap<String, Bounds>,
+    /// Minimum SENSOR_SAMPLE events required before SENSOR_OK counts
+    pub min_sensor_samples: Option<u32>,
+    /// Highest allowed sample variance of the SENSOR_SAMPLE values
+    pub max_sample_variance: Option<f64>,
+    /// Events that flag a marginal unit with a warning without failing it
+    pub warn_events: Vec<String>,
+    /// Soft limits on any numeric event parameter, e.g. {"rssi": {"min": -80, "max": 0}};
+    /// a value outside its range adds a warning but doesn't fail the unit
+    pub warn_bounds: HashMap<String, Bounds>,
+    /// Fail with "Power fault detected: brownout" as soon as the brownout detector fires,
+    /// instead of only warning
+    pub fail_on_power_fault: bool,
+    /// How long to wait for the required events (defaults to 60 seconds, 20 in quick mode)
+    pub timeout_secs: Option<u64>,
+    /// Hardware-reset the unit through RTS before sending TEST, so the run covers a fresh boot
+    pub reset_before_test: bool,
+    /// Let a unit that already passed this session pass again (RMA/returns re-test)
+    pub allow_duplicates: bool,
+    /// Prompt the firmware prints when it's ready for a command (e.g. "> ");
+    /// when set, output is discarded until it appears before TEST is sent
+    pub wait_for_ready: Option<String>,
+    /// Send raw device lines as "test-output-batch" arrays (up to ~50ms or 32 lines)
+    /// instead of one "test-output" event per line
+    pub batch_output: bool,
+    /// Wire format the firmware speaks ("text" or "json")
+    pub protocol: Protocol,
+    /// JSON key holding the event name in json mode (defaults to "event")
+    pub json_event_field: Option<String>,
+    /// Spot check that only requires BOOT_COMPLETE and SENSOR_OK (overrides `profile`)
+    pub quick: bool,
+    /// Fail early with "Device appears stuck in a loop" once any single line has been
+    /// seen more than this many times during the test (e.g. a crash loop)
+    pub max_line_repeats: Option<u32>,
+    /// Command sent only after a full pass (e.g. "LOCK" or "SEAL"); the unit fails if it isn't acknowledged
+    pub post_pass_command: Option<String>,
+    /// Marker acknowledging post_pass_command (defaults to "[EVENT:<command>_OK")
+    pub post_pass_ack: Option<String>,
+    /// Fail once the device has been silent this long
+    pub idle_timeout_secs: Option<u64>,
+    /// Command port baud rate (defaults to 115200)
+    pub baud: Option<u32>,
+    /// Command that starts the test, without line ending (defaults to the protocol's TEST command)
+    pub command: Option<String>,
+    /// Terminator sent after `command` (defaults to CRLF)
+    pub line_ending: Option<LineEnding>,
+    /// Events that fail the unit as soon as they appear; like warn_events, an entry may be
+    /// "re:<regex>" to match the raw line instead of an event name
+    pub fail_fast_events: Vec<String>,
+    /// Events that fail the unit whenever they appear, even once every required event has been
+    /// seen (e.g. during post_success_capture_ms). Same syntax as fail_fast_events, so
+    /// "re:^\[ERROR\]" catches plain "[ERROR] ..." lines
+    pub forbidden_events: Vec<String>,
+    /// After SENSOR_OK, keep reading TELEMETRY events this long and summarise them
+    pub telemetry_secs: Option<u64>,
+    /// Oldest acceptable firmware build (RFC 3339, e.g. "2024-06-01T00:00:00Z"); units whose
+    /// BOOT_COMPLETE reports an older BUILD, or none at all, fail
+    pub min_build: Option<String>,
+    /// After all checks pass, keep collecting output into `events` this long before returning
+    /// (defaults to 0); the pass is reported straight away
+    pub post_success_capture_ms: Option<u64>,
+    /// Prompts answered automatically whenever the firmware prints them, e.g.
+    /// {"pattern": "Press any key to continue", "response": ""}
+    pub prompts: Vec<TestPrompt>,
+    /// Cap on the lines kept in TestResult.events (default unlimited). Past it, the first and
+    /// last halves are kept with "... N lines elided ..." between; [EVENT:...] lines are always kept
+    pub max_events: Option<usize>,
+    /// Keep the device's boot output in TestResult.boot_log instead of events: everything read
+    /// before the test command is sent, and after it until the first event arrives
+    pub capture_boot_log: bool,
+    /// Resend the test command every few seconds, up to this many times, until the first event
+    /// arrives; for firmware that drops a command sent while its UART is settling
+    pub command_retries: Option<u8>,
+    /// Operator running the test, written to the outbox file
+    pub operator: Option<String>,
+    /// Fail units whose BOOT_COMPLETE reports a temp_c above this (a hot boot points to a short)
+    pub max_boot_temp_c: Option<f64>,
+    /// The unit's eFuse MAC as read by esptool while flashing, used to identify it when the test
+    /// reports no MAC; run_flash_and_test fills it in
+    pub fallback_mac: Option<String>,
+    /// Firmware versions the unit may boot, e.g. ["2.3.*", "2.4.0"]; "*" matches any run of
+    /// characters. The unit fails when BOOT_COMPLETE reports another version. Empty disables the check.
+    pub allowed_firmware: Vec<String>,
+    /// Kind to store the result under instead of "test", for runs on a golden unit (soak,
+    /// burn-in) that mustn't count towards yield, the session counters or the outbox
+    #[serde(skip)]
+    pub(crate) record_kind: Option<&'static str>,
+}
+
+impl TestOptions {
+    /// Select the test profile and fill every tunable the caller left unset from it
+    fn resolve(mut self, app_handle: &AppHandle) -> Result<(TestProfile, TestOptions), String> {
+        let name = if self.quick { Some(profiles::QUICK_PROFILE) } else { self.profile.as_deref() };
+        let profile = profiles::resolve_profile(app_handle, name)?;
+
+        self.timeout_secs = self.timeout_secs.or(profile.timeout_secs);
+        self.idle_timeout_secs = self.idle_timeout_secs.or(profile.idle_timeout_secs);
+        self.baud = self.baud.or(profile.baud);
+        self.command = self.command.or_else(|| profile.command.clone());
+        self.line_ending = self.line_ending.or(profile.line_ending);
+        if self.fail_fast_events.is_empty() {
+            self.fail_fast_events = profile.fail_fast_events.clone();
+        }
+        if self.warn_events.is_empty() {
+            self.warn_events = profile.warn_events.clone();
+        }
+        if self.forbidden_events.is_empty() {
+            self.forbidden_events = profile.forbidden_events.clone();
+        }
+        self.min_build()?;
+        Ok((profile, self))
+    }
+
+    /// The parsed min_build threshold
+    pub fn min_build(&self) -> Result<Option<DateTime<Utc>>, String> {
+        self.min_build
+            .as_deref()
+            .map(|min| parse_build_time(min).ok_or_else(|| format!("Invalid min_build '{}': expected RFC 3339", min)))
+            .transpose()
+    }
+
+    fn timeout(&self) -> Duration {
+        let default = if self.quick { QUICK_TEST_TIMEOUT_SECS } else { DEFAULT_TEST_TIMEOUT_SECS };
+        Duration::from_secs(self.timeout_secs.unwrap_or(default))
+    }
+
+    /// Bytes that start the test, line ending included
+    fn test_command(&self) -> String {
+        let command = self.command.as_deref().unwrap_or(self.protocol.test_command());
+        format!("{}{}", command, self.line_ending.unwrap_or_default().as_str())
+    }
+}
+
+/// List the available production test profiles: the built-in ones plus test_profiles.json
+#[tauri::command]
+pub fn list_test_profiles(app_handle: AppHandle) -> Result<Vec<TestProfile>, String> {
+    profiles::load_profiles(&app_handle)
+}
+
+/// What run_device_test will enforce with a profile, defaults filled in
+#[derive(Debug, Serialize)]
+pub struct ProfileDescription {
+    pub name: String,
+    pub description: String,
+    /// Required events in checklist order, with the names the UI shows
+    pub checklist: Vec<ChecklistItem>,
+    pub fail_fast_events: Vec<String>,
+    pub forbidden_events: Vec<String>,
+    pub warn_events: Vec<String>,
+    pub timeout_secs: u64,
+    pub idle_timeout_secs: Option<u64>,
+    pub baud: u32,
+    /// Command that starts the test, without line ending
+    pub command: String,
+    pub line_ending: LineEnding,
+}
+
+/// Preview the checklist and settings a profile (default: the active one) resolves to, without a device.
+/// Fails like run_device_test would on an unknown profile or an invalid event pattern.
+#[tauri::command]
+pub fn describe_profile(app_handle: AppHandle, name: Option<String>) -> Result<ProfileDescription, String> {
+    let options = TestOptions {
+        profile: name,
+        ..TestOptions::default()
+    };
+    let (profile, options) = options.resolve(&app_handle)?;
+    let checklist = EventChecklist::compile(&profile.required_events)?;
+    Ok(ProfileDescription {
+        checklist: checklist.items().into_iter().cloned().collect(),
+        fail_fast_events: options.fail_fast_events.clone(),
+        forbidden_events: options.forbidden_events.clone(),
+        warn_events: options.warn_events.clone(),
+        timeout_secs: options.timeout().as_secs(),
+        idle_timeout_secs: options.idle_timeout_secs,
+        baud: options.baud.unwrap_or(DEFAULT_BAUD),
+        command: options.command.clone().unwrap_or_else(|| options.protocol.test_command().to_string()),
+        line_ending: options.line_ending.unwrap_or_default(),
+        name: profile.name,
+        description: profile.description,
+    })
+}
+
+/// An open device port that closes cleanly: when dropped it drains pending output,
+/// discards anything left in the buffers and returns DTR/RTS to their idle (low) state,
+/// so the next operation on the port doesn't start with stale data or a held reset line
+pub(crate) struct DevicePort(Box<dyn SerialPort>);
+
+impl Deref for DevicePort {
+    type Target = dyn SerialPort;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl DerefMut for DevicePort {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.0
+    }
+}
+
+impl Read for DevicePort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for DevicePort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Drop for DevicePort {
+    fn drop(&mut self) {
+        release_port(&mut *self.0);
+    }
+}
+
+/// DevicePort's cleanup: drain pending output, drop whatever is still buffered and leave
+/// DTR/RTS low. Best effort, since the port may already be gone.
+fn release_port(port: &mut dyn SerialPort) {
+    let _ = port.flush();
+    let _ = port.clear(ClearBuffer::All);
+    let _ = port.write_data_terminal_ready(false);
+    let _ = port.write_request_to_send(false);
+}
+
+/// Baud rate the production firmware's console runs at
+pub(crate) const DEFAULT_BAUD: u32 = 115200;
+
+/// Open a device port at 115200 baud with DTR/RTS held low so the ESP32
+/// stays in its application rather than resetting into the bootloader
+pub(crate) fn open_serial(port: &str) -> Result<DevicePort, String> {
+    open_serial_at(port, DEFAULT_BAUD)
+}
+
+/// open_serial at a specific baud rate
+pub(crate) fn open_serial_at(port: &str, baud: u32) -> Result<DevicePort, String> {
+    let serial = serial_builder(port, baud)
+        .open()
+        .map_err(|e| format!("Failed to open serial port: {}", e))?;
+    Ok(prepare_serial(serial))
+}
+
+/// Open attempts made while a port is still held by the OS or a previous process
+const OPEN_ATTEMPTS: u32 = 5;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// True for open errors that typically clear on their own, e.g. right after a flash
+/// when the OS hasn't released the port yet
+fn is_port_busy(error: &serialport::Error) -> bool {
+    if let serialport::ErrorKind::Io(kind) = error.kind() {
+        if kind == std::io::ErrorKind::PermissionDenied {
+            return true;
+        }
+    }
+    let description = error.description.to_lowercase();
+    description.contains("busy") || description.contains("denied") || description.contains("in use")
+}
+
+/// Like open_serial, but retries briefly while the port is busy, announcing the wait on `channel`
+pub(crate) fn open_serial_with_retry(handle: &AppHandle, channel: &str, port: &str) -> Result<DevicePort, String> {
+    open_serial_with_retry_at(handle, channel, port, DEFAULT_BAUD)
+}
+
+/// open_serial_with_retry at a specific baud rate
+pub(crate) fn open_serial_with_retry_at(handle: &AppHandle, channel: &str, port: &str, baud: u32) -> Result<DevicePort, String> {
+    let mut attempt = 1;
+    loop {
+        match serial_builder(port, baud).open() {
+            Ok(serial) => return Ok(prepare_serial(serial)),
+            Err(e) if attempt < OPEN_ATTEMPTS && is_port_busy(&e) => {
+                if attempt == 1 {
+                    emit_output(handle, channel, port, "Waiting for port to become available...");
+                }
+                attempt += 1;
+                std::thread::sleep(OPEN_RETRY_DELAY);
+            }
+            Err(e) => return Err(format!("Failed to open serial port: {}", e)),
+        }
+    }
+}
+
+/// Ask for DTR to stay low from the moment the port opens. Some adapters reset the ESP32
+/// into the bootloader on the DTR edge an ordinary open produces. Linux raises DTR on open
+/// regardless, so prepare_serial still drops both lines straight afterwards.
+fn serial_builder(port: &str, baud: u32) -> serialport::SerialPortBuilder {
+    serialport::new(port, baud)
+        .timeout(Duration::from_millis(100))
+        .dtr_on_open(false)
+}
+
+fn prepare_serial(mut serial: Box<dyn SerialPort>) -> DevicePort {
+    // Set DTR and RTS low to prevent ESP32 reset/bootloader mode
+    // On ESP32 boards, these lines control reset (RTS->EN) and boot mode (DTR->GPIO0)
+    let _ = serial.write_data_terminal_ready(false);
+    let _ = serial.write_request_to_send(false);
+
+    // Small delay to let the lines settle
+    std::thread::sleep(Duration::from_millis(50));
+
+    // Start from an empty input buffer in case a previous user left data behind
+    let _ = serial.clear(ClearBuffer::Input);
+
+    DevicePort(serial)
+}
+
+/// Restart the ESP32 into its application by pulsing EN through RTS, keeping DTR
+/// (GPIO0) low so it doesn't drop into the bootloader
+pub(crate) fn pulse_reset(serial: &mut dyn SerialPort) -> Result<(), String> {
+    serial
+        .write_request_to_send(true)
+        .map_err(|e| format!("Failed to reset device: {}", e))?;
+    std::thread::sleep(Duration::from_millis(100));
+    serial
+        .write_request_to_send(false)
+        .map_err(|e| format!("Failed to reset device: {}", e))?;
+    Ok(())
+}
+
+/// ROM bootloader output meaning the device is waiting for a download instead of running
+/// its application, e.g. after a reset on port open
+const BOOTLOADER_PATTERNS: [&str; 2] = ["waiting for download", "DOWNLOAD_BOOT"];
+
+fn is_bootloader_line(line: &str) -> bool {
+    BOOTLOADER_PATTERNS.iter().any(|pattern| line.contains(pattern))
+}
+
+/// Time the application needs to boot after being reset out of the bootloader
+const APP_BOOT_DELAY: Duration = Duration::from_millis(1500);
+
+/// Pause after a read that returned no data. A TimedOut read already blocks for the port
+/// timeout, but some drivers report a silent or just-closed port as an immediate empty read,
+/// which would otherwise spin the loop at full speed.
+const IDLE_BACKOFF: Duration = Duration::from_millis(20);
+
+pub(crate) fn idle_backoff() {
+    std::thread::sleep(IDLE_BACKOFF);
+}
+
+/// How long to wait for the first event before resending the test command (command_retries)
+const COMMAND_RESEND_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long to wait for a configured ready prompt before sending the command anyway
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read and discard device output until `marker` appears or `timeout` elapses,
+/// passing each discarded line to `on_line`. Returns whether the marker was seen.
+/// Partial lines are checked too, since prompts like "> " have no trailing newline.
+pub(crate) fn wait_for_marker<R: BufRead>(
+    reader: &mut R,
+    marker: &str,
+    timeout: Duration,
+    mut on_line: impl FnMut(&str),
+) -> Result<bool, String> {
+    let start = std::time::Instant::now();
+    let mut line = String::new();
+    while start.elapsed() < timeout {
+        match reader.read_line(&mut line) {
+            Ok(0) => idle_backoff(),
+            Ok(_) => {
+                let found = line.contains(marker);
+                on_line(line.trim());
+                line.clear();
+                if found {
+                    return Ok(true);
+                }
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                if line.contains(marker) {
+                    on_line(line.trim());
+                    return Ok(true);
+                }
+            }
+            Err(e) => return Err(format!("Read error: {}", e)),
+        }
+    }
+    Ok(false)
+}
+
+/// Read lines from a secondary port on a background thread until `stop` is set
+fn spawn_line_reader(serial: DevicePort, stop: Arc<AtomicBool>) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(serial);
+        let mut line = String::new();
+        while !stop.load(Ordering::SeqCst) {
+            match reader.read_line(&mut line) {
+                Ok(0) => idle_backoff(),
+                Ok(_) => {
+                    if tx.send(std::mem::take(&mut line)).is_err() {
+                        break;
+                    }
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(_) => break,
+            }
+        }
+    });
+    rx
+}
+
+/// Payload of the "test-event" event: a production event as the UI checklist shows it
+#[derive(Debug, Clone, Serialize)]
+struct TestEvent<'a> {
+    port: &'a str,
+    #[serde(flatten)]
+    item: ChecklistItem,
+}
+
+/// Emit "test-event" for each checklist entry the session has observed since the last call
+fn emit_test_events(handle: &AppHandle, port: &str, session: &mut TestSession) {
+    for item in session.take_observed() {
+        let _ = handle.emit("test-event", TestEvent { port, item });
+    }
 }
 
 /// Run production test on the device
-/// Emits "test-output" events for real-time progress
+/// Emits "test-output" events for real-time progress, and "test-event" when a production
+/// event is first seen, with its display name and checklist order (none for unlisted events)
 #[tauri::command]
-pub async fn run_device_test(app_handle: AppHandle, port: String) -> Result<TestResult, String> {
+pub async fn run_device_test(
+    app_handle: AppHandle,
+    port: String,
+    options: Option<TestOptions>,
+) -> Result<TestResult, String> {
+    validate_port(&port)?;
+    let (profile, options) = options.unwrap_or_default().resolve(&app_handle)?;
+    if let Some(log_port) = &options.log_port {
+        validate_port(log_port)?;
+    }
+    // Event patterns are compiled here so a bad one is reported before the port is touched
+    let mut session = TestSession::new(&profile, &options)?;
+    let mut operation = operations::begin(&app_handle, "test", &port);
+
     // Run the blocking serial operations in a separate thread
     let handle = app_handle.clone();
+    let control = operation.control();
     let result = tokio::task::spawn_blocking(move || {
-        let mut serial = serialport::new(&port, 115200)
-            .timeout(Duration::from_millis(100))
-            .open()
-            .map_err(|e| format!("Failed to open serial port: {}", e))?;
-
-        // Set DTR and RTS low to prevent ESP32 reset/bootloader mode
-        // On ESP32 boards, these lines control reset (RTS->EN) and boot mode (DTR->GPIO0)
-        let _ = serial.write_data_terminal_ready(false);
-        let _ = serial.write_request_to_send(false);
-        
-        // Small delay to let the lines settle
-        std::thread::sleep(Duration::from_millis(50));
+        let baud = options.baud.unwrap_or(DEFAULT_BAUD);
+        let mut serial = open_serial_with_retry_at(&handle, "test-output", &port, baud)?;
+        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
 
-        // Send TEST command
-        serial
-            .write_all(b"TEST\r\n")
-            .map_err(|e| format!("Failed to send TEST command: {}", e))?;
-        serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+        if options.reset_before_test {
+            emit_output(&handle, "test-output", &port, "Resetting device...");
+            pulse_reset(&mut *serial)?;
+        }
 
-        let mut events: Vec<String> = Vec::new();
-        let mut firmware_version: Option<String> = None;
-        let mut mac_address: Option<String> = None;
-        let mut test_passed = false;
-        
-        // Track required events for auto-detection of success
-        let mut boot_complete = false;
-        let mut wifi_connected = false;
-        let mut mqtt_connected = false;
-        let mut sensor_ok = false;
-        let mut mqtt_publish_ok = false;
+        if let Some(marker) = &options.wait_for_ready {
+            let emit = |line: &str| {
+                if !line.is_empty() {
+                    emit_output(&handle, "test-output", &port, line);
+                    session.record_boot_line(line);
+                }
+            };
+            if !wait_for_marker(&mut reader, marker, READY_TIMEOUT, emit)? {
+                emit_output(&handle, "test-output", &port, &format!("[WARN] Ready prompt '{}' not seen - sending TEST anyway", marker));
+            }
+        }
 
-        let start = std::time::Instant::now();
-        let timeout = Duration::from_secs(60);
+        // When telemetry comes out of a separate UART, read it alongside the command port
+        // and tag each line with the port it arrived on
+        let log_stop = Arc::new(AtomicBool::new(false));
+        let log_lines = match &options.log_port {
+            Some(log_port) => Some(spawn_line_reader(
+                open_serial_with_retry(&handle, "test-output", log_port)?,
+                log_stop.clone(),
+            )),
+            None => None,
+        };
+        let command_source = log_lines.as_ref().map(|_| "CMD");
 
-        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+        send_test_command(&mut serial, &options)?;
+        session.restart_clock();
+
+        let mut output = OutputSink::new(&handle, "test-output", &port, options.batch_output);
+
+        let mut start = std::time::Instant::now();
+        let timeout = options.timeout();
+        let idle_timeout = options.idle_timeout_secs.map(Duration::from_secs);
+        let mut last_output = start;
+        let mut last_sent = start;
+        let mut resends = 0;
         let mut line = String::new();
+        // Bootloader output is only acted on before the application has answered, and only once
+        let mut app_responded = false;
+        let mut left_bootloader = false;
+        let mut attempts = 1;
 
-        while start.elapsed() < timeout {
-            line.clear();
+        // A line cut off by the read timeout stays in `line` and is completed by the next read
+        while start.elapsed() < timeout && !session.is_finished() && !control.is_cancelled() {
             match reader.read_line(&mut line) {
-                Ok(0) => continue,
+                Ok(0) => idle_backoff(),
                 Ok(_) => {
-                    let trimmed = line.trim().to_string();
-                    if !trimmed.is_empty() {
-                        events.push(trimmed.clone());
-                        
-                        // Emit raw line to frontend for real-time display
-                        let _ = handle.emit("test-output", &trimmed);
-                        
-                        // Note: We don't immediately trust [EVENT:TEST_PASS] or [EVENT:TEST_FAIL]
-                        // from the device's internal test because it may run before MQTT connects.
-                        // Instead, we wait for the actual connection events.
-                        
-                        // Track production events for auto-detection and emit status
-                        if trimmed.contains("[EVENT:BOOT_COMPLETE]") {
-                            boot_complete = true;
-                            let _ = handle.emit("test-output", "[CHECK] ✓ Boot complete");
-                            if let Some(fw) = extract_param(&trimmed, "FIRMWARE") {
-                                firmware_version = Some(fw.clone());
-                                let _ = handle.emit("test-output", &format!("[INFO] Firmware: {}", fw));
-                            }
-                            if let Some(mac) = extract_param(&trimmed, "MAC") {
-                                mac_address = Some(mac.clone());
-                                let _ = handle.emit("test-output", &format!("[INFO] MAC: {}", mac));
-                            }
-                        } else if trimmed.contains("[EVENT:WIFI_CONNECTED]") {
-                            wifi_connected = true;
-                            let _ = handle.emit("test-output", "[CHECK] ✓ WiFi connected");
-                        } else if trimmed.contains("[EVENT:MQTT_CONNECTED]") {
-                            mqtt_connected = true;
-                            let _ = handle.emit("test-output", "[CHECK] ✓ MQTT connected");
-                        } else if trimmed.contains("[EVENT:SENSOR_OK]") {
-                            if !sensor_ok {
-                                sensor_ok = true;
-                                let _ = handle.emit("test-output", "[CHECK] ✓ Sensor OK");
-                            }
-                        } else if trimmed.contains("[EVENT:MQTT_PUBLISH_OK]") {
-                            if !mqtt_publish_ok {
-                                mqtt_publish_ok = true;
-                                let _ = handle.emit("test-output", "[CHECK] ✓ MQTT publish OK");
-                            }
-                        }
-                        
-                        // Auto-detect success when all required events have been seen
-                        if boot_complete && wifi_connected && mqtt_connected && sensor_ok && mqtt_publish_ok {
-                            test_passed = true;
-                            let _ = handle.emit("test-output", "[CHECK] ✓ All checks passed!");
-                            break;
+                    last_output = std::time::Instant::now();
+                    session.process_line(&line, command_source, &mut |l| output.emit(l));
+                    emit_test_events(&handle, &port, &mut session);
+                    if let Err(e) = answer_prompts(&mut serial, &mut session, &options, &mut |l| output.emit(l)) {
+                        log_stop.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                    app_responded |= line.contains("[EVENT:");
+                    if !app_responded && !left_bootloader && is_bootloader_line(&line) {
+                        // The TEST command went to the ROM bootloader: boot the app and send it again
+                        left_bootloader = true;
+                        attempts += 1;
+                        output.emit("[WARN] Device is in the bootloader - resetting into the application and resending TEST");
+                        if let Err(e) = restart_into_app(&mut serial).and_then(|_| send_test_command(&mut serial, &options)) {
+                            log_stop.store(true, Ordering::SeqCst);
+                            return Err(e);
                         }
+                        session.restart_clock();
+                        start = std::time::Instant::now();
+                        last_output = start;
+                        last_sent = start;
                     }
+                    line.clear();
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
-                Err(e) => return Err(format!("Read error: {}", e)),
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    log_stop.store(true, Ordering::SeqCst);
+                    return Err(format!("Read error: {}", e));
+                }
+            }
+
+            if let Some(log_lines) = &log_lines {
+                while let Ok(log_line) = log_lines.try_recv() {
+                    last_output = std::time::Instant::now();
+                    session.process_line(&log_line, Some("LOG"), &mut |l| output.emit(l));
+                    emit_test_events(&handle, &port, &mut session);
+                    if let Err(e) = answer_prompts(&mut serial, &mut session, &options, &mut |l| output.emit(l)) {
+                        log_stop.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                }
+            }
+            let command_retries = options.command_retries.unwrap_or(0);
+            if resends < command_retries && !session.has_events() && last_sent.elapsed() >= COMMAND_RESEND_INTERVAL {
+                resends += 1;
+                attempts += 1;
+                output.emit(&format!("[WARN] No event yet - resending TEST ({}/{})", resends, command_retries));
+                if let Err(e) = send_test_command(&mut serial, &options) {
+                    log_stop.store(true, Ordering::SeqCst);
+                    return Err(e);
+                }
+                session.restart_clock();
+                last_sent = std::time::Instant::now();
             }
+            if let Some(idle_timeout) = idle_timeout.filter(|idle| last_output.elapsed() >= *idle) {
+                let message = format!("No output from device for {}s", idle_timeout.as_secs());
+                session.fail(message, &mut |l| output.emit(l));
+            }
+            output.tick();
+        }
+        log_stop.store(true, Ordering::SeqCst);
+        if control.is_cancelled() {
+            return Err("Test cancelled".to_string());
         }
 
-        if test_passed {
-            Ok::<TestResult, String>(TestResult {
-                success: true,
-                message: "All tests passed".to_string(),
-                events,
-                firmware_version,
-                mac_address,
-            })
-        } else {
-            // Build a message showing which events were missing
-            let mut missing = Vec::new();
-            if !boot_complete { missing.push("BOOT_COMPLETE"); }
-            if !wifi_connected { missing.push("WIFI_CONNECTED"); }
-            if !mqtt_connected { missing.push("MQTT_CONNECTED"); }
-            if !sensor_ok { missing.push("SENSOR_OK"); }
-            if !mqtt_publish_ok { missing.push("MQTT_PUBLISH_OK"); }
-            
-            let message = if missing.is_empty() {
-                "Test timed out".to_string()
-            } else {
-                format!("Test timed out - missing events: {}", missing.join(", "))
-            };
-            
-            Ok(TestResult {
-                success: false,
-                message,
-                events,
-                firmware_version: None,
-                mac_address: None,
-            })
+        let mut result = session.into_result();
+        // A unit that reported no MAC (e.g. failed before BOOT_COMPLETE) is identified by its eFuse MAC,
+        // before the duplicate check so such units are checked too
+        if let (None, Some(mac)) = (&result.mac_address, &options.fallback_mac) {
+            output.emit(&format!("[INFO] No MAC from the test - identifying the unit by its eFuse MAC {}", mac));
+            result.mac_address = Some(mac.clone());
+            result.mac_source = Some("efuse".to_string());
+        }
+        let mut mac_added = false;
+        if result.success {
+            match record_passed_mac(&handle, &result, options.allow_duplicates) {
+                Ok(added) => mac_added = added,
+                Err(message) => {
+                    output.emit(&format!("[CHECK] ✗ {}", message));
+                    result.success = false;
+                    result.message = message;
+                }
+            }
+        }
+        if let (true, Some(command)) = (result.success, &options.post_pass_command) {
+            let ack = options
+                .post_pass_ack
+                .clone()
+                .unwrap_or_else(|| format!("[EVENT:{}_OK", command));
+            match send_post_pass_command(&mut *serial, &mut reader, command, &ack, &mut |l| output.emit(l)) {
+                Ok(ack_line) => {
+                    output.emit(&format!("[CHECK] ✓ {} acknowledged", command));
+                    result.post_pass_ack = Some(ack_line);
+                }
+                Err(message) => {
+                    output.emit(&format!("[CHECK] ✗ {}", message));
+                    if mac_added {
+                        forget_passed_mac(&handle, &result);
+                    }
+                    result.success = false;
+                    result.message = message;
+                }
+            }
+        }
+        result.attempts = attempts;
+        result.quality = TestQuality::assess(&result);
+        output.flush();
+        signal_result(&handle, &port, result.success);
+        match options.record_kind {
+            Some(kind) => results::store(&handle, kind, &port, result.mac_address.as_deref(), result.success, &result.message, &result),
+            None => {
+                results::record(&handle, "test", &port, result.mac_address.as_deref(), result.success, &result.message, &result);
+                outbox::write_outbox(&handle, &port, options.operator.as_deref(), &result);
+            }
         }
+
+        Ok::<TestResult, String>(result)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |r| r.success);
+    notify_complete(&app_handle, "Test", operation.port(), &result, |r| (r.success, &r.message));
+    result
+}
+
+/// Reset out of the bootloader and wait for the application to boot, discarding its banner
+fn restart_into_app(serial: &mut DevicePort) -> Result<(), String> {
+    pulse_reset(&mut **serial)?;
+    std::thread::sleep(APP_BOOT_DELAY);
+    let _ = serial.clear(ClearBuffer::Input);
+    Ok(())
+}
+
+fn send_test_command(serial: &mut DevicePort, options: &TestOptions) -> Result<(), String> {
+    serial
+        .write_all(options.test_command().as_bytes())
+        .map_err(|e| format!("Failed to send TEST command: {}", e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))
+}
+
+/// Send the answer to every prompt the session has seen since the last call
+fn answer_prompts(
+    serial: &mut DevicePort,
+    session: &mut TestSession,
+    options: &TestOptions,
+    emit: &mut dyn FnMut(&str),
+) -> Result<(), String> {
+    for response in session.take_replies() {
+        let bytes = format!("{}{}", response, options.line_ending.unwrap_or_default().as_str());
+        serial
+            .write_all(bytes.as_bytes())
+            .map_err(|e| format!("Failed to answer prompt: {}", e))?;
+        serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+        emit(&format!("[INFO] Answered prompt with '{}'", response));
+    }
+    Ok(())
+}
+
+/// Remember a passing unit's MAC for this session, rejecting it if it has already passed.
+/// Returns whether this call added the MAC, i.e. whether forget_passed_mac may remove it.
+fn record_passed_mac(app_handle: &AppHandle, result: &TestResult, allow_duplicates: bool) -> Result<bool, String> {
+    let Some(mac) = &result.mac_address else {
+        return Ok(false);
+    };
+    let state = app_handle.state::<AppState>();
+    let mut seen = state.seen_macs.lock().map_err(|e| e.to_string())?;
+    note_passed_mac(&mut seen, mac, allow_duplicates)
+}
+
+/// The duplicate check behind record_passed_mac, on the session's set of passed MACs
+fn note_passed_mac(seen: &mut HashSet<String>, mac: &str, allow_duplicates: bool) -> Result<bool, String> {
+    let added = seen.insert(mac.to_uppercase());
+    if !added && !allow_duplicates {
+        return Err(format!("Duplicate MAC {} - this unit already passed this session", mac));
+    }
+    Ok(added)
+}
+
+/// Undo record_passed_mac for a unit that failed after all, so it can be re-tested.
+/// Only call it when record_passed_mac added the MAC, or an earlier pass would be forgotten.
+fn forget_passed_mac(app_handle: &AppHandle, result: &TestResult) {
+    let Some(mac) = &result.mac_address else {
+        return;
+    };
+    let state = app_handle.state::<AppState>();
+    if let Ok(mut seen) = state.seen_macs.lock() {
+        seen.remove(&mac.to_uppercase());
+    };
+}
+
+/// How long the device has to acknowledge a post-pass command
+const POST_PASS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Send the trailer command after a pass and wait for `ack`; returns the acknowledging line
+fn send_post_pass_command<R: BufRead>(
+    serial: &mut dyn Write,
+    reader: &mut R,
+    command: &str,
+    ack: &str,
+    emit: &mut dyn FnMut(&str),
+) -> Result<String, String> {
+    serial
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .map_err(|e| format!("Failed to send {} command: {}", command, e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+    emit(&format!("Sent {} command...", command));
+
+    let mut ack_line = None;
+    let acknowledged = wait_for_marker(reader, ack, POST_PASS_TIMEOUT, |line| {
+        if line.is_empty() {
+            return;
+        }
+        emit(line);
+        if line.contains(ack) {
+            ack_line = Some(line.to_string());
+        }
+    })?;
+    match ack_line {
+        Some(line) if acknowledged => Ok(line),
+        _ => Err(format!("{} was not acknowledged (expected '{}')", command, ack)),
+    }
+}
+
+/// Forget every MAC seen this session so known units can be re-tested
+/// Emits "session-macs-cleared" with the number of MACs removed
+#[tauri::command]
+pub fn clear_session_macs(app_handle: AppHandle) -> Result<usize, String> {
+    let state = app_handle.state::<AppState>();
+    let cleared = {
+        let mut seen = state.seen_macs.lock().map_err(|e| e.to_string())?;
+        let count = seen.len();
+        seen.clear();
+        count
+    };
+    session_stats::save(&app_handle)?;
+    let _ = app_handle.emit("session-macs-cleared", cleared);
+    Ok(cleared)
+}
+
+/// Replay a captured raw serial log through the same event matching and success
+/// logic as run_device_test, emitting "test-output" and "test-event" events as if it were live
+#[tauri::command]
+pub async fn replay_test_log(
+    app_handle: AppHandle,
+    path: String,
+    options: Option<TestOptions>,
+    line_delay_ms: Option<u64>,
+) -> Result<TestResult, String> {
+    let (profile, options) = options.unwrap_or_default().resolve(&app_handle)?;
+    let mut session = TestSession::new(&profile, &options)?;
+    let line_delay = Duration::from_millis(line_delay_ms.unwrap_or(20));
+
+    let handle = app_handle.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .map_err(|e| format!("Failed to open log {}: {}", path, e))?;
+
+        let mut emit = |line: &str| emit_output(&handle, "test-output", "replay", line);
+
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            session.process_line(&line, None, &mut emit);
+            emit_test_events(&handle, "replay", &mut session);
+            if session.is_finished() {
+                break;
+            }
+            std::thread::sleep(line_delay);
+        }
+
+        Ok::<TestResult, String>(session.into_result())
     }).await.map_err(|e| format!("Task join error: {}", e))??;
 
     Ok(result)
 }
 
+/// How long to wait for BOOT_COMPLETE after a factory reset when wait_for_reboot is set
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How long the firmware has to answer GET_INFO when a factory reset is verified
+const INFO_REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// GET_INFO fields a factory reset must clear, with the value they should read afterwards
+const DEFAULT_CLEARED_FIELDS: [(&str, &str); 2] = [("SERIAL", ""), ("SSID", "")];
+
+/// Reset the device, ask it for GET_INFO and compare the reply with `expected`.
+/// Returns a description of every field that kept a value; a field missing from the reply
+/// counts as its default only when that default is empty.
+fn verify_reset_defaults(
+    serial: &mut DevicePort,
+    reader: &mut impl BufRead,
+    expected: &HashMap<String, String>,
+    emit: &mut dyn FnMut(&str),
+) -> Result<Vec<String>, String> {
+    restart_into_app(serial)?;
+    serial
+        .write_all(b"GET_INFO\r\n")
+        .map_err(|e| format!("Failed to send GET_INFO command: {}", e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+
+    let start = std::time::Instant::now();
+    let mut line = String::new();
+    while start.elapsed() < INFO_REPLY_TIMEOUT {
+        match reader.read_line(&mut line) {
+            Ok(0) => idle_backoff(),
+            Ok(_) => {
+                let trimmed = line.trim();
+                if !trimmed.is_empty() {
+                    emit(trimmed);
+                }
+                let params = parse_params(trimmed);
+                let is_info = params
+                    .iter()
+                    .any(|(key, _)| key.eq_ignore_ascii_case("MAC") || expected.keys().any(|field| field.eq_ignore_ascii_case(key)));
+                if is_info {
+                    let mut persisted: Vec<String> = expected
+                        .iter()
+                        .filter_map(|(field, default)| {
+                            let reported = params.iter().find(|(key, _)| key.eq_ignore_ascii_case(field)).map(|(_, value)| value);
+                            match reported {
+                                Some(value) if value != default => {
+                                    Some(format!("{} is still '{}' (expected '{}')", field, value, default))
+                                }
+                                None if !default.is_empty() => Some(format!("{} not reported (expected '{}')", field, default)),
+                                _ => None,
+                            }
+                        })
+                        .collect();
+                    persisted.sort();
+                    return Ok(persisted);
+                }
+                line.clear();
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(format!("Read error: {}", e)),
+        }
+    }
+    Err("No GET_INFO reply after the reset".to_string())
+}
+
 /// Factory reset the device
+/// `wait_for_ready` names a prompt to wait for before sending the command.
+/// With `wait_for_reboot`, success also requires a BOOT_COMPLETE event after the reset completes.
+/// With `verify_cleared`, the device is then reset again and GET_INFO must report every field of
+/// `expected_defaults` (default SERIAL and SSID empty) at its default; anything that persisted
+/// fails the reset and is named in the message.
 /// Emits "reset-output" events for real-time progress
 #[tauri::command]
-pub async fn factory_reset(app_handle: AppHandle, port: String) -> Result<ResetResult, String> {
+pub async fn factory_reset(
+    app_handle: AppHandle,
+    port: String,
+    wait_for_ready: Option<String>,
+    wait_for_reboot: Option<bool>,
+    verify_cleared: Option<bool>,
+    expected_defaults: Option<HashMap<String, String>>,
+) -> Result<ResetResult, String> {
+    validate_port(&port)?;
+    let mut operation = operations::begin(&app_handle, "reset", &port);
     // Run the blocking serial operations in a separate thread
     let handle = app_handle.clone();
+    let control = operation.control();
     let result = tokio::task::spawn_blocking(move || {
-        let mut serial = serialport::new(&port, 115200)
-            .timeout(Duration::from_millis(100))
-            .open()
-            .map_err(|e| format!("Failed to open serial port: {}", e))?;
-
-        // Set DTR and RTS low to prevent ESP32 reset/bootloader mode
-        // On ESP32 boards, these lines control reset (RTS->EN) and boot mode (DTR->GPIO0)
-        let _ = serial.write_data_terminal_ready(false);
-        let _ = serial.write_request_to_send(false);
-        
-        // Small delay to let the lines settle
-        std::thread::sleep(Duration::from_millis(50));
+        let mut serial = open_serial_with_retry(&handle, "reset-output", &port)?;
+        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+
+        if let Some(marker) = &wait_for_ready {
+            let emit = |line: &str| if !line.is_empty() { emit_output(&handle, "reset-output", &port, line) };
+            if !wait_for_marker(&mut reader, marker, READY_TIMEOUT, emit)? {
+                emit_output(&handle, "reset-output", &port, &format!("[WARN] Ready prompt '{}' not seen - sending FACTORY_RESET anyway", marker));
+            }
+        }
 
         // Send FACTORY_RESET command
         serial
@@ -338,132 +1674,556 @@ pub async fn factory_reset(app_handle: AppHandle, port: String) -> Result<ResetR
             .map_err(|e| format!("Failed to send FACTORY_RESET command: {}", e))?;
         serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
 
-        let _ = handle.emit("reset-output", "Sent FACTORY_RESET command...");
+        emit_output(&handle, "reset-output", &port, "Sent FACTORY_RESET command...");
 
-        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
         let start = std::time::Instant::now();
         let timeout = Duration::from_secs(30);
         let mut reset_complete = false;
         let mut line = String::new();
 
-        while start.elapsed() < timeout {
-            line.clear();
+        while start.elapsed() < timeout && !control.is_cancelled() {
             match reader.read_line(&mut line) {
-                Ok(0) => continue,
+                Ok(0) => idle_backoff(),
                 Ok(_) => {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        let _ = handle.emit("reset-output", trimmed);
+                        emit_output(&handle, "reset-output", &port, trimmed);
                     }
                     if trimmed.contains("Factory reset complete") || trimmed.contains("FACTORY_RESET_COMPLETE") {
                         reset_complete = true;
-                        let _ = handle.emit("reset-output", "[CHECK] ✓ Factory reset complete");
+                        emit_output(&handle, "reset-output", &port, "[CHECK] ✓ Factory reset complete");
                         break;
                     }
+                    line.clear();
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
                 Err(e) => return Err(format!("Read error: {}", e)),
             }
         }
+        if control.is_cancelled() {
+            return Err("Factory reset cancelled".to_string());
+        }
 
-        Ok::<ResetResult, String>(ResetResult {
-            success: reset_complete,
-            message: if reset_complete {
-                "Factory reset completed successfully".to_string()
+        // Firmware can print the completion line and then fail to come back up
+        let rebooted = if reset_complete && wait_for_reboot.unwrap_or(false) {
+            let emit = |line: &str| if !line.is_empty() { emit_output(&handle, "reset-output", &port, line) };
+            let booted = wait_for_marker(&mut reader, "[EVENT:BOOT_COMPLETE", REBOOT_TIMEOUT, emit)?;
+            if booted {
+                emit_output(&handle, "reset-output", &port, "[CHECK] ✓ Device rebooted");
             } else {
-                "Factory reset timed out or failed".to_string()
-            },
+                emit_output(&handle, "reset-output", &port, "[CHECK] ✗ Device did not reboot");
+            }
+            Some(booted)
+        } else {
+            None
+        };
+
+        let (mut success, mut message) = match (reset_complete, rebooted) {
+            (false, _) => (false, "Factory reset timed out or failed".to_string()),
+            (true, None) => (true, "Factory reset completed successfully".to_string()),
+            (true, Some(true)) => (true, "Reset complete and device rebooted".to_string()),
+            (true, Some(false)) => (false, "Reset complete but device did not reboot".to_string()),
+        };
+
+        // The completion line can be printed before NVS is actually written
+        if success && verify_cleared.unwrap_or(false) {
+            let expected = expected_defaults.unwrap_or_else(|| {
+                DEFAULT_CLEARED_FIELDS
+                    .iter()
+                    .map(|(field, default)| (field.to_string(), default.to_string()))
+                    .collect()
+            });
+            emit_output(&handle, "reset-output", &port, "Resetting to confirm settings were cleared...");
+            let mut emit = |line: &str| emit_output(&handle, "reset-output", &port, line);
+            match verify_reset_defaults(&mut serial, &mut reader, &expected, &mut emit) {
+                Ok(persisted) if persisted.is_empty() => {
+                    emit_output(&handle, "reset-output", &port, "[CHECK] ✓ Settings cleared after reboot");
+                    message = format!("{}; settings confirmed cleared", message);
+                }
+                Ok(persisted) => {
+                    emit_output(&handle, "reset-output", &port, "[CHECK] ✗ Settings persisted after reboot");
+                    success = false;
+                    message = format!("Reset reported complete but settings persisted: {}", persisted.join("; "));
+                }
+                Err(e) => {
+                    emit_output(&handle, "reset-output", &port, "[CHECK] ✗ Could not confirm settings were cleared");
+                    success = false;
+                    message = format!("Reset reported complete but could not be confirmed: {}", e);
+                }
+            }
+        }
+
+        Ok::<ResetResult, String>(ResetResult {
+            success,
+            message,
+            throughput: None,
+            detected_chip: None,
         })
-    }).await.map_err(|e| format!("Task join error: {}", e))??;
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
 
-    Ok(result)
+    operation.finish(&result, |r| r.success);
+    result
 }
 
 /// Erase the device flash
-/// Emits "erase-output" events for real-time progress
+/// `chip` is passed to esptool's --chip (default "esp32"); "auto" detects it first.
+/// Queues while every process slot is in use. `connect_timeout_secs` overrides the configured
+/// time esptool spends syncing with the bootloader.
+/// Emits "erase-output" events for real-time progress, with stderr lines also on "erase-output-err"
 #[tauri::command]
-pub async fn erase_device(app_handle: AppHandle, port: String) -> Result<ResetResult, String> {
-    let script_path = get_flasher_path(&app_handle)?;
-    let script_dir = script_path.parent()
-        .ok_or("Could not get script directory")?
-        .to_path_buf();
-    
-    // esptool.py is in the same directory as ncd_flasher.py
-    let esptool_path = script_dir.join("esptool.py");
+pub async fn erase_device(
+    app_handle: AppHandle,
+    port: String,
+    chip: Option<String>,
+    connect_timeout_secs: Option<u32>,
+) -> Result<ResetResult, String> {
+    validate_port(&port)?;
+    let mut operation = operations::begin(&app_handle, "erase", &port);
+    let esptool = resolve_esptool(&app_handle)?;
+    ensure_esptool_intact(&app_handle, &esptool)?;
+    let mut reset = config::current(&app_handle).flash_reset;
+    reset.connect_timeout_secs = connect_timeout_secs.or(reset.connect_timeout_secs);
     
     // Run the blocking operation in a separate thread
     let handle = app_handle.clone();
+    let control = operation.control();
     let result = tokio::task::spawn_blocking(move || {
-        let _ = handle.emit("erase-output", "Starting flash erase...");
-        
-        // Using esptool v4.5.1 which has improved reset timing
-        let mut child = Command::new(python_command())
-            .arg(&esptool_path)
-            .arg("--chip")
-            .arg("esp32")
-            .arg("--port")
-            .arg(&port)
-            .arg("erase_flash")
-            .current_dir(&script_dir)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+        let _slot = acquire_process_slot(&handle, &control, || {
+            emit_output(&handle, "erase-output", &port, QUEUED_MESSAGE)
+        })?;
+        erase_flash(&handle, &esptool, &port, chip.as_deref(), &reset, &control, "erase-output").map(|(result, _)| result)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
 
-        let mut success = false;
-        
-        // Read stdout in real-time
-        if let Some(stdout) = child.stdout.take() {
-            let reader = BufReader::new(stdout);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    let _ = handle.emit("erase-output", &line);
-                    if line.contains("Chip erase completed") {
-                        success = true;
-                    }
-                }
-            }
+    operation.finish(&result, |r| r.success);
+    result
+}
+
+/// Run esptool erase_flash on `port`, streaming its output to `channel`. Blocking.
+/// Returns the outcome and every line esptool printed.
+fn erase_flash(
+    handle: &AppHandle,
+    esptool: &Esptool,
+    port: &str,
+    chip: Option<&str>,
+    reset: &ResetConfig,
+    control: &OperationControl,
+    channel: &str,
+) -> Result<(ResetResult, Vec<String>), String> {
+    emit_output(handle, channel, port, "Starting flash erase...");
+
+    let selected_chip = match chip {
+        Some("auto") => {
+            emit_output(handle, channel, port, "Detecting chip type...");
+            let detected = detect_chip(handle, esptool, port, false)?;
+            emit_output(handle, channel, port, &format!("[INFO] Detected chip: {}", detected));
+            detected
         }
-        
-        // Read any remaining stderr
-        if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    let _ = handle.emit("erase-output", &line);
-                }
+        Some(chip) => chip.to_string(),
+        None => "esp32".to_string(),
+    };
+    // Only warn on a mismatch when the operator picked the chip themselves
+    let explicit_chip = chip.filter(|c| *c != "auto");
+    
+    // Using esptool v4.5.1 which has improved reset timing
+    let mut command = esptool.command();
+    command.arg("--chip").arg(&selected_chip).arg("--port").arg(port);
+    reset.apply(&mut command, port)?;
+    let mut child = command
+        .arg("erase_flash")
+        // Unbuffered so lines arrive as they're printed and phase timing is meaningful
+        .env("PYTHONUNBUFFERED", "1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    control.attach_child(child);
+
+    let mut success = false;
+    let mut meter = ThroughputMeter::default();
+    let mut flash_size = DEFAULT_FLASH_SIZE_BYTES;
+    let mut detected_chip: Option<String> = None;
+    let mut wrong_chip = false;
+    let mut output = Vec::new();
+    
+    // Read stdout in real-time
+    if let Some(stdout) = stdout {
+        for line in output_lines(stdout) {
+            emit_output(handle, channel, port, &line);
+            if let Some(chip) = parse_chip_line(&line) {
+                detected_chip = Some(chip.to_string());
             }
+            wrong_chip |= line.contains("Wrong --chip argument");
+            if line.to_lowercase().contains("flash size:") {
+                flash_size = parse_flash_size(&line).unwrap_or(flash_size);
+            }
+            meter.observe_erase(&line, flash_size);
+            if line.contains("Chip erase completed") {
+                success = true;
+            }
+            output.push(line);
         }
-        
-        let status = child.wait().map_err(|e| format!("Failed to wait for process: {}", e))?;
-        success = success || status.success();
+    }
+    
+    // Read any remaining stderr; on failure its last line is usually the actual error
+    let mut last_error = None;
+    if let Some(stderr) = stderr {
+        for line in output_lines(stderr) {
+            emit_stderr(handle, channel, port, &line);
+            wrong_chip |= line.contains("Wrong --chip argument");
+            last_error = Some(line.trim().to_string());
+            output.push(line);
+        }
+    }
+    
+    let status = control.wait_child()?;
+    if control.is_cancelled() {
+        return Err("Flash erase cancelled".to_string());
+    }
+    success = success || status.success();
 
-        if success {
-            let _ = handle.emit("erase-output", "[CHECK] ✓ Flash erase complete");
+    if wrong_chip {
+        // A cached detection no longer matches what's on the port
+        forget_chip(handle, port);
+    }
+    if let (Some(selected), Some(detected)) = (explicit_chip, detected_chip.as_deref()) {
+        if !selected.eq_ignore_ascii_case(detected) {
+            emit_output(
+                handle,
+                channel,
+                port,
+                &format!("[WARN] Detected chip {} but {} was selected - check the product", detected, selected),
+            );
         }
+    }
 
-        Ok::<ResetResult, String>(ResetResult {
-            success,
-            message: if success {
-                "Flash erased successfully".to_string()
-            } else {
-                "Flash erase failed".to_string()
-            },
-        })
-    }).await.map_err(|e| format!("Task join error: {}", e))??;
+    let throughput = meter.finish();
+    if let Some(throughput) = &throughput {
+        report_throughput(handle, channel, port, throughput);
+    }
 
-    Ok(result)
+    if success {
+        emit_output(handle, channel, port, "[CHECK] ✓ Flash erase complete");
+    }
+
+    let result = ResetResult {
+        success,
+        message: match (success, last_error) {
+            (true, _) => "Flash erased successfully".to_string(),
+            (false, Some(error)) => format!("Flash erase failed: {}", error),
+            (false, None) => "Flash erase failed".to_string(),
+        },
+        throughput,
+        detected_chip: detected_chip.or(Some(selected_chip)),
+    };
+    Ok((result, output))
 }
 
 /// Helper function to extract a parameter value from an event string
-fn extract_param(line: &str, param: &str) -> Option<String> {
+pub fn extract_param(line: &str, param: &str) -> Option<String> {
     let pattern = format!("{}=", param);
     if let Some(start) = line.find(&pattern) {
         let value_start = start + pattern.len();
         let rest = &line[value_start..];
-        let end = rest.find(' ').unwrap_or(rest.len());
+        // The last parameter of an event ends at the closing bracket
+        let end = rest.find([' ', ']']).unwrap_or(rest.len());
         Some(rest[..end].to_string())
     } else {
         None
     }
 }
+
+/// Parse a firmware build timestamp such as "2024-06-01T10:00:00Z"
+pub fn parse_build_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim_end_matches(']'))
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Extract every key=value pair from an event string, in the order they appear
+pub fn parse_params(line: &str) -> Vec<(String, String)> {
+    line.split_whitespace()
+        .filter_map(|token| {
+            let (key, value) = token.split_once('=')?;
+            let key = key.trim_start_matches('[');
+            let value = value.trim_end_matches(']');
+            if key.is_empty() || key.contains(':') {
+                return None;
+            }
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_redraws_become_separate_lines() {
+        let stream = "Connecting....\r\nWriting at 0x00010000... (10 %)\rWriting at 0x00020000... (50 %)\rWriting at 0x00030000... (100 %)\r\nStatus: Success\r\n";
+        let lines: Vec<String> = output_lines(stream.as_bytes()).collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[3], "Writing at 0x00030000... (100 %)");
+        let combined_output = lines.join("\n");
+        assert!(!combined_output.contains('\r'));
+        assert!(combined_output.contains("Status: Success"));
+    }
+
+    #[test]
+    fn merged_output_keeps_both_streams() {
+        let stdout: &'static [u8] = b"Writing at 0x00010000... (100 %)\r\nHash of data verified.\n";
+        let stderr: &'static [u8] = b"A fatal error occurred: Timed out\n";
+        let mut lines: Vec<(String, bool)> = merged_output_lines(Some(stdout), Some(stderr)).collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                ("A fatal error occurred: Timed out".to_string(), true),
+                ("Hash of data verified.".to_string(), false),
+                ("Writing at 0x00010000... (100 %)".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_first_pass_of_a_mac_is_added() {
+        let mut seen = HashSet::new();
+        assert_eq!(note_passed_mac(&mut seen, "aa:bb:cc:dd:ee:ff", false), Ok(true));
+        assert!(note_passed_mac(&mut seen, "AA:BB:CC:DD:EE:FF", false).is_err());
+        // A permitted re-test didn't add the MAC, so a failed ack must not forget the earlier pass
+        assert_eq!(note_passed_mac(&mut seen, "AA:BB:CC:DD:EE:FF", true), Ok(false));
+        assert!(seen.contains("AA:BB:CC:DD:EE:FF"));
+    }
+
+    /// A serial port stand-in: each read returns the next chunk, None is a read timeout,
+    /// and an exhausted script reads as empty
+    struct ScriptedPort {
+        chunks: std::collections::VecDeque<Option<&'static [u8]>>,
+        reads: usize,
+    }
+
+    impl ScriptedPort {
+        fn new(chunks: &[Option<&'static [u8]>]) -> Self {
+            ScriptedPort { chunks: chunks.iter().copied().collect(), reads: 0 }
+        }
+    }
+
+    impl Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads += 1;
+            match self.chunks.pop_front() {
+                Some(Some(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+                Some(None) => Err(std::io::ErrorKind::TimedOut.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn silent_port_waits_out_the_timeout_without_spinning() {
+        let mut reader = BufReader::new(ScriptedPort::new(&[]));
+        let timeout = Duration::from_millis(200);
+        let start = std::time::Instant::now();
+        assert_eq!(wait_for_marker(&mut reader, "READY", timeout, |_| {}), Ok(false));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= timeout);
+        assert!(elapsed < timeout + IDLE_BACKOFF * 5, "overran the timeout: {:?}", elapsed);
+        // Every empty read backs off, so a silent port is polled at most every IDLE_BACKOFF
+        let max_reads = (timeout.as_millis() / IDLE_BACKOFF.as_millis()) as usize + 1;
+        assert!(reader.get_ref().reads <= max_reads, "{} reads", reader.get_ref().reads);
+    }
+
+    #[test]
+    fn line_split_by_a_read_timeout_is_reassembled() {
+        let mut reader = BufReader::new(ScriptedPort::new(&[Some(b"STATUS: RE"), None, Some(b"ADY\n")]));
+        let mut lines = Vec::new();
+        let found = wait_for_marker(&mut reader, "READY", Duration::from_secs(5), |line| lines.push(line.to_string()));
+        assert_eq!(found, Ok(true));
+        assert_eq!(lines, vec!["STATUS: READY"]);
+    }
+
+    fn passed_result() -> TestResult {
+        let profile = profiles::builtin_profiles().into_iter().find(|p| p.name == "local-sensor").unwrap();
+        let mut session = TestSession::new(&profile, &TestOptions::default()).unwrap();
+        for line in ["[EVENT:BOOT_COMPLETE]", "[EVENT:SENSOR_OK]"] {
+            session.process_line(line, None, &mut |_| {});
+        }
+        session.into_result()
+    }
+
+    #[test]
+    fn quality_grades_each_kind_of_pass() {
+        let mut result = passed_result();
+        assert_eq!(TestQuality::assess(&result), TestQuality::CleanPass);
+
+        result.warnings.push("RSSI low: -82".to_string());
+        assert_eq!(TestQuality::assess(&result), TestQuality::PassWithWarnings);
+
+        // A retry outranks warnings
+        result.attempts = 2;
+        assert_eq!(TestQuality::assess(&result), TestQuality::PassWithRetry);
+
+        result.success = false;
+        assert_eq!(TestQuality::assess(&result), TestQuality::Fail);
+    }
+
+    /// The state of one physical port, shared by every handle opened on it
+    #[derive(Default)]
+    struct MockLine {
+        unread: Vec<u8>,
+        unsent: Vec<u8>,
+        sent: Vec<u8>,
+        dtr: bool,
+        rts: bool,
+        calls: Vec<&'static str>,
+    }
+
+    /// A SerialPort handle over a MockLine
+    struct MockPort(Arc<Mutex<MockLine>>);
+
+    impl Read for MockPort {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut line = self.0.lock().unwrap();
+            if line.unread.is_empty() {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            let n = buf.len().min(line.unread.len());
+            buf[..n].copy_from_slice(&line.unread[..n]);
+            line.unread.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Write for MockPort {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().unsent.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let mut line = self.0.lock().unwrap();
+            let unsent = std::mem::take(&mut line.unsent);
+            line.sent.extend(unsent);
+            line.calls.push("flush");
+            Ok(())
+        }
+    }
+
+    impl SerialPort for MockPort {
+        fn name(&self) -> Option<String> {
+            Some("MOCK".to_string())
+        }
+        fn baud_rate(&self) -> serialport::Result<u32> {
+            Ok(DEFAULT_BAUD)
+        }
+        fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+            Ok(serialport::DataBits::Eight)
+        }
+        fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+            Ok(serialport::FlowControl::None)
+        }
+        fn parity(&self) -> serialport::Result<serialport::Parity> {
+            Ok(serialport::Parity::None)
+        }
+        fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+            Ok(serialport::StopBits::One)
+        }
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(100)
+        }
+        fn set_baud_rate(&mut self, _: u32) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn set_timeout(&mut self, _: Duration) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+            self.0.lock().unwrap().rts = level;
+            Ok(())
+        }
+        fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+            self.0.lock().unwrap().dtr = level;
+            Ok(())
+        }
+        fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+            Ok(false)
+        }
+        fn bytes_to_read(&self) -> serialport::Result<u32> {
+            Ok(self.0.lock().unwrap().unread.len() as u32)
+        }
+        fn bytes_to_write(&self) -> serialport::Result<u32> {
+            Ok(self.0.lock().unwrap().unsent.len() as u32)
+        }
+        fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+            let mut line = self.0.lock().unwrap();
+            if matches!(buffer_to_clear, ClearBuffer::Input | ClearBuffer::All) {
+                line.unread.clear();
+            }
+            if matches!(buffer_to_clear, ClearBuffer::Output | ClearBuffer::All) {
+                line.unsent.clear();
+            }
+            line.calls.push("clear");
+            Ok(())
+        }
+        fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+            Ok(Box::new(MockPort(self.0.clone())))
+        }
+        fn set_break(&self) -> serialport::Result<()> {
+            Ok(())
+        }
+        fn clear_break(&self) -> serialport::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn closing_a_device_port_leaves_the_next_open_clean() {
+        let line = Arc::new(Mutex::new(MockLine::default()));
+        {
+            let mut port = DevicePort(Box::new(MockPort(line.clone())));
+            port.write_data_terminal_ready(true).unwrap();
+            port.write_request_to_send(true).unwrap();
+            port.write_all(b"TEST\r\n").unwrap();
+            // Output the device sent after the operation stopped reading
+            line.lock().unwrap().unread.extend_from_slice(b"[EVENT:SENSOR_OK]\r\n");
+        }
+        {
+            let line = line.lock().unwrap();
+            // The command went out before the buffers were cleared
+            assert_eq!(line.sent, b"TEST\r\n");
+            assert_eq!(line.calls, vec!["flush", "clear"]);
+            assert!(!line.dtr && !line.rts);
+        }
+
+        let mut next = DevicePort(Box::new(MockPort(line.clone())));
+        assert_eq!(next.bytes_to_read().unwrap(), 0);
+        let mut buf = [0u8; 16];
+        assert_eq!(next.read(&mut buf).unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+    }
+}
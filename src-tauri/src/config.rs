@@ -0,0 +1,334 @@
+use log::LevelFilter;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{probe_python, set_python_override};
+use crate::esptool::ResetConfig;
+use crate::profiles::{self, TestProfile};
+use crate::state::AppState;
+
+/// An external program plus arguments, run by the app at a defined point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// A USB vendor id, optionally narrowed to one product id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbId {
+    pub vid: u16,
+    /// None matches any product from the vendor
+    pub pid: Option<u16>,
+}
+
+impl UsbId {
+    pub fn matches(&self, vid: u16, pid: u16) -> bool {
+        self.vid == vid && self.pid.map_or(true, |p| p == pid)
+    }
+}
+
+/// USB-serial adapters commonly found on ESP32 boards, used when no allowlist is configured
+pub const DEFAULT_PORT_ALLOWLIST: [UsbId; 6] = [
+    // Silicon Labs CP210x
+    UsbId { vid: 0x10C4, pid: Some(0xEA60) },
+    // WCH CH340 and CH9102
+    UsbId { vid: 0x1A86, pid: Some(0x7523) },
+    UsbId { vid: 0x1A86, pid: Some(0x55D4) },
+    // FTDI FT232R and FT231X
+    UsbId { vid: 0x0403, pid: Some(0x6001) },
+    UsbId { vid: 0x0403, pid: Some(0x6015) },
+    // Espressif native USB
+    UsbId { vid: 0x303A, pid: None },
+];
+
+/// Station settings persisted to config.json in the app config directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    /// Run after every device test with "pass" or "fail" appended (e.g. to drive a stack light)
+    pub result_signal_command: Option<HookCommand>,
+    /// ncd_flasher.py to use instead of the bundled copy
+    pub flasher_path: Option<PathBuf>,
+    /// esptool.py or standalone esptool to use instead of the copy next to the flasher
+    pub esptool_path: Option<PathBuf>,
+    /// Python interpreter for the flasher and esptool.py instead of python3/python on PATH
+    pub python_path: Option<String>,
+    /// Write the app log (including per-operation start/end lines) in release builds too;
+    /// debug builds always log
+    pub release_logging: bool,
+    /// App log level set with set_log_level (defaults to "info")
+    pub log_level: Option<String>,
+    /// USB ids auto_select_port considers; None uses DEFAULT_PORT_ALLOWLIST
+    pub port_allowlist: Option<Vec<UsbId>>,
+    /// Reset strategy and timing for flashing and erasing
+    pub flash_reset: ResetConfig,
+    /// Regex scanned serial numbers must match; None uses provision::DEFAULT_SERIAL_PATTERN
+    pub serial_pattern: Option<String>,
+    /// Post a desktop notification when a flash or test finishes
+    pub notify_on_complete: bool,
+    /// Flasher/esptool processes allowed at once; None uses operations::DEFAULT_MAX_PROCESSES
+    pub max_processes: Option<usize>,
+    /// Only log a warning when ncd_flasher.py or esptool don't match the bundled copies,
+    /// instead of refusing to flash or erase (for development)
+    pub integrity_warn_only: bool,
+    /// USB serial number of the adapter in each fixture slot, set with register_slot
+    pub slots: BTreeMap<u32, String>,
+    /// Drop folder for one JSON result file per finished test, picked up by an external uploader
+    pub outbox_dir: Option<PathBuf>,
+    /// Station name written to outbox files; None uses the host name
+    pub station_name: Option<String>,
+    /// A second flash_firmware on a port within this long of the last one starting or
+    /// finishing is rejected; None uses commands::DEFAULT_FLASH_DEBOUNCE_MS, 0 turns it off
+    pub flash_debounce_ms: Option<u64>,
+    /// Test profile used when run_device_test doesn't name one, set with switch_profile;
+    /// None uses profiles::DEFAULT_PROFILE
+    pub active_profile: Option<String>,
+}
+
+fn config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Could not resolve config directory: {}", e))?;
+    Ok(dir.join("config.json"))
+}
+
+/// Load the saved config, falling back to defaults if it's missing or unreadable
+pub fn load(app_handle: &AppHandle) -> AppConfig {
+    let Ok(path) = config_path(app_handle) else {
+        return AppConfig::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid config {}: {}", path.display(), e);
+            AppConfig::default()
+        }),
+        Err(_) => AppConfig::default(),
+    }
+}
+
+fn save(app_handle: &AppHandle, config: &AppConfig) -> Result<(), String> {
+    let path = config_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| format!("Failed to save config: {}", e))
+}
+
+/// Snapshot of the current config
+pub fn current(app_handle: &AppHandle) -> AppConfig {
+    let state = app_handle.state::<AppState>();
+    let config = state.config.lock().map(|c| c.clone());
+    config.unwrap_or_default()
+}
+
+/// Apply a change to the in-memory config and persist it
+pub fn update(app_handle: &AppHandle, change: impl FnOnce(&mut AppConfig)) -> Result<AppConfig, String> {
+    let state = app_handle.state::<AppState>();
+    let mut config = state.config.lock().map_err(|e| e.to_string())?;
+    change(&mut config);
+    save(app_handle, &config)?;
+    Ok(config.clone())
+}
+
+/// Get the station config
+#[tauri::command]
+pub fn get_config(app_handle: AppHandle) -> AppConfig {
+    current(&app_handle)
+}
+
+/// What export_settings writes: the station config plus the test profiles from test_profiles.json
+#[derive(Debug, Serialize)]
+struct SettingsFile {
+    config: AppConfig,
+    profiles: Vec<TestProfile>,
+}
+
+/// Outcome of import_settings
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    /// Config fields that were applied, plus "profiles" when the test profiles were replaced
+    pub applied: Vec<String>,
+    /// Everything left as it was, each with the reason
+    pub rejected: Vec<String>,
+}
+
+/// Write the station config and custom test profiles to `path` as JSON, to replicate this
+/// station on others with import_settings
+#[tauri::command]
+pub fn export_settings(app_handle: AppHandle, path: String) -> Result<(), String> {
+    let settings = SettingsFile {
+        config: current(&app_handle),
+        profiles: profiles::load_file_profiles(&app_handle)?,
+    };
+    let contents = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    log::info!("Exported settings to {}", path);
+    Ok(())
+}
+
+/// Checks on an imported config field beyond its type, mostly for values that depend on
+/// what is installed on the station
+fn check_config_field(field: &str, value: &Value) -> Result<(), String> {
+    match (field, value.as_str()) {
+        ("flasher_path" | "esptool_path", Some(path)) if !Path::new(path).is_file() => {
+            Err(format!("{} not found on this station", path))
+        }
+        ("python_path", Some(python)) => probe_python(python).map(|_| ()),
+        ("serial_pattern", Some(pattern)) => Regex::new(pattern)
+            .map(|_| ())
+            .map_err(|e| format!("invalid pattern: {}", e)),
+        ("log_level", Some(level)) => level
+            .parse::<LevelFilter>()
+            .map(|_| ())
+            .map_err(|_| format!("unknown log level '{}'", level)),
+        _ => Ok(()),
+    }
+}
+
+/// Apply each valid field of an imported config section over `config`, noting in `report`
+/// which fields were applied and why the others weren't
+fn merge_config_fields(
+    config: AppConfig,
+    fields: &serde_json::Map<String, Value>,
+    report: &mut ImportReport,
+) -> Result<AppConfig, String> {
+    let mut merged = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    for (field, value) in fields {
+        if merged.get(field).is_none() {
+            report.rejected.push(format!("config.{}: unknown field", field));
+            continue;
+        }
+        let mut candidate = merged.clone();
+        candidate[field] = value.clone();
+        let checked = serde_json::from_value::<AppConfig>(candidate.clone())
+            .map_err(|e| e.to_string())
+            .and_then(|_| check_config_field(field, value));
+        match checked {
+            Ok(()) => {
+                merged = candidate;
+                report.applied.push(field.clone());
+            }
+            Err(e) => report.rejected.push(format!("config.{}: {}", field, e)),
+        }
+    }
+    serde_json::from_value(merged).map_err(|e| e.to_string())
+}
+
+/// Apply a settings file written by export_settings. Each config field is validated on its
+/// own and applied if it's valid on this station; test profiles are replaced only if the
+/// whole set is valid. Everything that couldn't be applied is listed in the report.
+#[tauri::command]
+pub fn import_settings(app_handle: AppHandle, path: String) -> Result<ImportReport, String> {
+    let contents = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let settings: Value = serde_json::from_str(&contents).map_err(|e| format!("Invalid settings file {}: {}", path, e))?;
+    let Some(sections) = settings.as_object() else {
+        return Err(format!("Invalid settings file {}: expected an object", path));
+    };
+    let mut report = ImportReport {
+        applied: Vec::new(),
+        rejected: Vec::new(),
+    };
+    for section in sections.keys().filter(|s| *s != "config" && *s != "profiles") {
+        report.rejected.push(format!("{}: unknown section", section));
+    }
+
+    match sections.get("config").map(Value::as_object) {
+        Some(Some(fields)) => {
+            let imported = merge_config_fields(current(&app_handle), fields, &mut report)?;
+            set_python_override(imported.python_path.clone());
+            if let Some(level) = imported.log_level.as_deref().and_then(|l| l.parse().ok()) {
+                log::set_max_level(level);
+            }
+            update(&app_handle, |c| *c = imported)?;
+            // Tool paths may have changed
+            *app_handle.state::<AppState>().versions.lock().map_err(|e| e.to_string())? = None;
+        }
+        Some(None) => report.rejected.push("config: expected an object".to_string()),
+        None => {}
+    }
+
+    match sections.get("profiles").map(Value::as_array) {
+        Some(Some(entries)) => match profiles::parse_profiles(entries.clone()) {
+            Ok(parsed) => {
+                profiles::save_file_profiles(&app_handle, &parsed)?;
+                report.applied.push("profiles".to_string());
+            }
+            Err(e) => report.rejected.push(format!("profiles: {}", e)),
+        },
+        Some(None) => report.rejected.push("profiles: expected a list of profiles".to_string()),
+        None => {}
+    }
+
+    log::info!(
+        "Imported settings from {}: {} applied, {} rejected",
+        path,
+        report.applied.len(),
+        report.rejected.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(fields: Value) -> (AppConfig, ImportReport) {
+        let mut report = ImportReport {
+            applied: Vec::new(),
+            rejected: Vec::new(),
+        };
+        let config = merge_config_fields(AppConfig::default(), fields.as_object().unwrap(), &mut report).unwrap();
+        (config, report)
+    }
+
+    #[test]
+    fn import_applies_valid_fields_and_reports_the_rest() {
+        let (config, report) = import(serde_json::json!({
+            "serial_pattern": "^/dev/ttyUSB",
+            "log_level": "debug",
+            "flash_debounce_ms": "soon",
+            "no_such_setting": true,
+        }));
+        assert_eq!(report.applied, vec!["log_level", "serial_pattern"]);
+        assert_eq!(config.serial_pattern.as_deref(), Some("^/dev/ttyUSB"));
+        assert_eq!(config.log_level.as_deref(), Some("debug"));
+        assert_eq!(config.flash_debounce_ms, None);
+        assert_eq!(report.rejected.len(), 2);
+        assert!(report.rejected[0].starts_with("config.flash_debounce_ms: invalid type"));
+        assert_eq!(report.rejected[1], "config.no_such_setting: unknown field");
+    }
+
+    #[test]
+    fn import_rejects_values_this_station_cannot_use() {
+        let (config, report) = import(serde_json::json!({
+            "serial_pattern": "([",
+            "log_level": "chatty",
+            "flasher_path": "/nonexistent/ncd_flasher.py",
+        }));
+        assert!(report.applied.is_empty());
+        assert_eq!(report.rejected.len(), 3);
+        assert!(report.rejected.iter().any(|r| r == "config.log_level: unknown log level 'chatty'"));
+        assert!(report.rejected.iter().any(|r| r.starts_with("config.serial_pattern: invalid pattern")));
+        assert!(report.rejected.iter().any(|r| r == "config.flasher_path: /nonexistent/ncd_flasher.py not found on this station"));
+        assert!(config.flasher_path.is_none());
+    }
+
+    #[test]
+    fn imported_profiles_are_validated_as_a_set() {
+        let valid = serde_json::json!({"name": "bench", "required_events": ["BOOT_COMPLETE"]});
+        assert_eq!(profiles::parse_profiles(vec![valid.clone()]).unwrap()[0].name, "bench");
+        let errors = profiles::parse_profiles(vec![valid.clone(), valid]).unwrap_err();
+        assert!(errors.contains("bench: defined more than once"));
+        let empty = serde_json::json!({"name": "empty", "required_events": []});
+        assert!(profiles::parse_profiles(vec![empty]).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use flate2::Crc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{idle_backoff, open_serial_with_retry_at, validate_port, DevicePort, DEFAULT_BAUD};
+use crate::mirror::emit_output;
+use crate::operations;
+use crate::results;
+use crate::stages::FlashProgress;
+
+const DEFAULT_CHUNK_SIZE: usize = 1024;
+/// Frames the bootloader may NAK before the transfer is abandoned
+const MAX_CHUNK_RETRIES: u32 = 3;
+/// How long the bootloader has to answer UPDATE with READY
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the bootloader has to ACK or NAK a chunk, or confirm DONE
+const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Result of flash_custom_protocol
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CustomFlashResult {
+    pub success: bool,
+    pub message: String,
+    pub bytes_sent: usize,
+    pub chunks: usize,
+    /// Chunks resent after a NAK or a missing ACK
+    pub retries: u32,
+}
+
+/// Next non-empty line from the bootloader, or None once `timeout` has passed
+fn read_reply<R: BufRead>(reader: &mut R, timeout: Duration) -> Result<Option<String>, String> {
+    let start = Instant::now();
+    let mut line = String::new();
+    while start.elapsed() < timeout {
+        match reader.read_line(&mut line) {
+            Ok(0) => idle_backoff(),
+            Ok(_) if line.trim().is_empty() => line.clear(),
+            Ok(_) => return Ok(Some(line.trim().to_string())),
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(format!("Read error: {}", e)),
+        }
+    }
+    Ok(None)
+}
+
+fn send(serial: &mut DevicePort, bytes: &[u8]) -> Result<(), String> {
+    serial.write_all(bytes).map_err(|e| format!("Write error: {}", e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Flash an image through our own serial bootloader rather than esptool.
+///
+/// The exchange, one line per message:
+/// - host `UPDATE <size> <crc32>`, bootloader `READY`
+/// - per chunk, host `CHUNK <index> <len> <crc32>` followed by `len` raw bytes;
+///   bootloader `ACK`, or `NAK` to have it resent (up to 3 times)
+/// - host `DONE`, bootloader `ACK` once the image is verified and committed
+///
+/// `chunk_size` defaults to 1024 bytes and `baud` to 115200. Emits "flash-output"
+/// lines and "flash-progress" events.
+#[tauri::command]
+pub async fn flash_custom_protocol(
+    app_handle: AppHandle,
+    port: String,
+    bin_path: String,
+    chunk_size: Option<usize>,
+    baud: Option<u32>,
+) -> Result<CustomFlashResult, String> {
+    validate_port(&port)?;
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+    if chunk_size == 0 {
+        return Err("Chunk size must be greater than zero".to_string());
+    }
+    let image = fs::read(&bin_path).map_err(|e| format!("Failed to read {}: {}", bin_path, e))?;
+    if image.is_empty() {
+        return Err(format!("{} is empty", bin_path));
+    }
+    let mut operation = operations::begin(&app_handle, "flash", &port);
+
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let emit = |line: &str| emit_output(&handle, "flash-output", &port, line);
+        let mut serial = open_serial_with_retry_at(&handle, "flash-output", &port, baud.unwrap_or(DEFAULT_BAUD))?;
+        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+
+        let chunks: Vec<&[u8]> = image.chunks(chunk_size).collect();
+        let mut outcome = CustomFlashResult {
+            success: false,
+            message: String::new(),
+            bytes_sent: 0,
+            chunks: chunks.len(),
+            retries: 0,
+        };
+
+        send(&mut serial, format!("UPDATE {} {:08x}\r\n", image.len(), crc32(&image)).as_bytes())?;
+        emit(&format!("Sent UPDATE ({} bytes in {} chunks)", image.len(), chunks.len()));
+        match read_reply(&mut reader, READY_TIMEOUT)? {
+            Some(reply) if reply.contains("READY") => emit(&reply),
+            reply => {
+                outcome.message = match reply {
+                    Some(reply) => format!("Bootloader refused UPDATE: {}", reply),
+                    None => "Bootloader did not answer UPDATE with READY".to_string(),
+                };
+                return Ok(outcome);
+            }
+        }
+
+        let mut last_percent = None;
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut attempt = 0;
+            loop {
+                if control.is_cancelled() {
+                    return Err("Flash cancelled".to_string());
+                }
+                let mut frame = format!("CHUNK {} {} {:08x}\r\n", index, chunk.len(), crc32(chunk)).into_bytes();
+                frame.extend_from_slice(chunk);
+                send(&mut serial, &frame)?;
+
+                let reply = read_reply(&mut reader, ACK_TIMEOUT)?;
+                if reply.as_deref().is_some_and(|r| r.contains("ACK") && !r.contains("NAK")) {
+                    break;
+                }
+                attempt += 1;
+                if attempt > MAX_CHUNK_RETRIES {
+                    outcome.message = format!("Chunk {} was not acknowledged after {} retries", index, MAX_CHUNK_RETRIES);
+                    return Ok(outcome);
+                }
+                outcome.retries += 1;
+                let reason = reply.unwrap_or_else(|| "no reply".to_string());
+                emit(&format!("[WARN] Resending chunk {} ({})", index, reason));
+            }
+
+            outcome.bytes_sent += chunk.len();
+            let percent = (outcome.bytes_sent * 100 / image.len()) as u8;
+            if last_percent != Some(percent) {
+                last_percent = Some(percent);
+                let _ = handle.emit("flash-progress", FlashProgress { port: port.clone(), percent });
+            }
+        }
+
+        send(&mut serial, b"DONE\r\n")?;
+        match read_reply(&mut reader, ACK_TIMEOUT)? {
+            Some(reply) if reply.contains("ACK") && !reply.contains("NAK") => {
+                outcome.success = true;
+                outcome.message = format!("Image flashed ({} bytes)", image.len());
+            }
+            Some(reply) => outcome.message = format!("Bootloader rejected the image: {}", reply),
+            None => outcome.message = "Bootloader did not confirm the image".to_string(),
+        }
+        Ok(outcome)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    if let Ok(flashed) = &result {
+        let check = if flashed.success { "✓" } else { "✗" };
+        emit_output(&app_handle, "flash-output", operation.port(), &format!("[CHECK] {} {}", check, flashed.message));
+        results::record(&app_handle, "flash", operation.port(), None, flashed.success, &flashed.message, flashed);
+    }
+    operation.finish(&result, |r| r.success);
+    result
+}
@@ -0,0 +1,100 @@
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::commands::{idle_backoff, open_serial_with_retry, parse_params, validate_port};
+use crate::mirror::emit_output;
+use crate::operations;
+
+/// Overall limit for a GET_CONFIG response
+const CONFIG_TIMEOUT: Duration = Duration::from_secs(5);
+/// A key=value response is complete once the device has been quiet this long
+const CONFIG_QUIET: Duration = Duration::from_millis(500);
+
+/// Config keys whose values never appear in emitted output
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["pass", "secret", "token", "key", "psk", "cert"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+/// Copy of a config line with secret values replaced by "****"
+fn mask_secrets(line: &str) -> String {
+    if let Ok(serde_json::Value::Object(mut object)) = serde_json::from_str::<serde_json::Value>(line) {
+        for (key, value) in object.iter_mut() {
+            if is_secret_key(key) {
+                *value = serde_json::Value::String("****".to_string());
+            }
+        }
+        return serde_json::Value::Object(object).to_string();
+    }
+    line.split_whitespace()
+        .map(|token| match token.split_once('=') {
+            Some((key, _)) if is_secret_key(key.trim_start_matches('[')) => format!("{}=****", key),
+            _ => token.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Read the configuration stored on the device (NVS) with GET_CONFIG.
+/// Accepts either a single JSON object line or a series of key=value lines; the parsed
+/// values are returned as-is, while secrets are masked in the "config-output" lines.
+#[tauri::command]
+pub async fn read_device_config(app_handle: AppHandle, port: String) -> Result<serde_json::Value, String> {
+    validate_port(&port)?;
+    let mut operation = operations::begin(&app_handle, "read_config", &port);
+    let handle = app_handle.clone();
+    let control = operation.control();
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut serial = open_serial_with_retry(&handle, "config-output", &port)?;
+        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+        serial
+            .write_all(b"GET_CONFIG\r\n")
+            .map_err(|e| format!("Failed to send GET_CONFIG command: {}", e))?;
+        serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+
+        let start = Instant::now();
+        let mut last_data: Option<Instant> = None;
+        let mut config = serde_json::Map::new();
+        let mut line = String::new();
+
+        while start.elapsed() < CONFIG_TIMEOUT && !control.is_cancelled() {
+            if last_data.is_some_and(|at| at.elapsed() >= CONFIG_QUIET) {
+                break;
+            }
+            match reader.read_line(&mut line) {
+                Ok(0) => idle_backoff(),
+                Ok(_) => {
+                    let trimmed = line.trim();
+                    if !trimmed.is_empty() {
+                        emit_output(&handle, "config-output", &port, &mask_secrets(trimmed));
+                        if let Ok(value @ serde_json::Value::Object(_)) = serde_json::from_str(trimmed) {
+                            return Ok(value);
+                        }
+                        for (key, value) in parse_params(trimmed) {
+                            config.insert(key, serde_json::Value::String(value));
+                            last_data = Some(Instant::now());
+                        }
+                    }
+                    line.clear();
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Read error: {}", e)),
+            }
+        }
+
+        if control.is_cancelled() {
+            return Err("Config read cancelled".to_string());
+        }
+        if config.is_empty() {
+            return Err("Device did not return its config".to_string());
+        }
+        Ok(serde_json::Value::Object(config))
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |_| true);
+    result
+}
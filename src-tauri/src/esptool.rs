@@ -0,0 +1,687 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::{get_flasher_path, merged_output_lines, output_lines, python_available, python_command, validate_port, FlashResult, QUEUED_MESSAGE};
+use crate::config;
+use crate::integrity::ensure_esptool_intact;
+use crate::mirror::{emit_output, emit_stderr};
+use crate::operations::{self, acquire_process_slot, OperationControl};
+use crate::results;
+use crate::stages::FlashProgress;
+use crate::state::AppState;
+use crate::throughput::{report_throughput, ThroughputMeter};
+
+/// Start of the app partition in the default partition table
+pub const APP_PARTITION_OFFSET: u32 = 0x10000;
+
+/// A resolved esptool: either esptool.py run through Python or a standalone executable
+#[derive(Debug, Clone)]
+pub struct Esptool {
+    path: PathBuf,
+}
+
+impl Esptool {
+    fn new(path: PathBuf) -> Self {
+        Esptool { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A command ready for esptool arguments, run from the tool's own directory
+    pub fn command(&self) -> Command {
+        let mut command = if self.path.extension().is_some_and(|ext| ext == "py") {
+            let mut command = Command::new(python_command());
+            command.arg(&self.path);
+            command
+        } else {
+            Command::new(&self.path)
+        };
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+/// File names an esptool may have, binary first; esptool.py only counts when Python is available
+fn esptool_names() -> Vec<&'static str> {
+    let mut names = vec![if cfg!(target_os = "windows") { "esptool.exe" } else { "esptool" }];
+    if python_available() {
+        names.push("esptool.py");
+    }
+    names
+}
+
+/// Look for an esptool executable in the directories on PATH
+fn find_on_path() -> Option<PathBuf> {
+    let names = esptool_names();
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Find esptool: the path set with set_esptool_path, then esptool next to ncd_flasher.py,
+/// then esptool on PATH. Without Python only standalone binaries are considered.
+pub fn resolve_esptool(app_handle: &AppHandle) -> Result<Esptool, String> {
+    let mut searched = Vec::new();
+
+    if let Some(custom_path) = config::current(app_handle).esptool_path {
+        let needs_python = custom_path.extension().is_some_and(|ext| ext == "py");
+        if custom_path.is_file() && (!needs_python || python_available()) {
+            return Ok(Esptool::new(custom_path));
+        }
+        let reason = if needs_python && custom_path.is_file() { " (needs Python)" } else { "" };
+        searched.push(format!("custom: {}{}", custom_path.display(), reason));
+    }
+
+    match get_flasher_path(app_handle) {
+        Ok(flasher_path) => {
+            for name in esptool_names() {
+                let beside_flasher = flasher_path.with_file_name(name);
+                if beside_flasher.is_file() {
+                    return Ok(Esptool::new(beside_flasher));
+                }
+                searched.push(format!("flasher dir: {}", beside_flasher.display()));
+            }
+        }
+        Err(_) => searched.push("flasher dir: ncd_flasher.py not found".to_string()),
+    }
+
+    if let Some(on_path) = find_on_path() {
+        return Ok(Esptool::new(on_path));
+    }
+    searched.push(format!("PATH: {}", esptool_names().join(", ")));
+
+    Err(format!("Could not find esptool. Searched: {}", searched.join(", ")))
+}
+
+/// Use a specific esptool (esptool.py or standalone binary); None restores the default search
+#[tauri::command]
+pub fn set_esptool_path(app_handle: AppHandle, path: Option<String>) -> Result<(), String> {
+    let path = path.map(PathBuf::from);
+    if let Some(path) = &path {
+        if !path.is_file() {
+            return Err(format!("esptool not found: {}", path.display()));
+        }
+    }
+    config::update(&app_handle, |c| c.esptool_path = path)?;
+    Ok(())
+}
+
+/// How the chip is put into the bootloader before an esptool run and reset afterwards
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetStrategy {
+    /// DTR/RTS auto-reset circuit (esptool default_reset / hard_reset)
+    #[default]
+    Classic,
+    /// Open the port at 1200 baud to drop native-USB boards into the bootloader, then no_reset
+    Unix1200bps,
+    /// USB-JTAG-Serial peripheral reset on chips with native USB
+    UsbReset,
+    /// Leave the chip alone; it must already be in the bootloader
+    NoReset,
+}
+
+impl ResetStrategy {
+    /// esptool's --before and --after values for this strategy
+    pub fn before_after(self) -> (&'static str, &'static str) {
+        match self {
+            ResetStrategy::Classic => ("default_reset", "hard_reset"),
+            ResetStrategy::Unix1200bps => ("no_reset", "hard_reset"),
+            ResetStrategy::UsbReset => ("usb_reset", "hard_reset"),
+            ResetStrategy::NoReset => ("no_reset", "no_reset"),
+        }
+    }
+}
+
+/// How long to wait for a board to re-enumerate after the 1200 baud touch
+const TOUCH_1200BPS_SETTLE: Duration = Duration::from_millis(1000);
+
+/// Reset behaviour for flashing and erasing, saved with set_flash_reset
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResetConfig {
+    pub strategy: ResetStrategy,
+    /// How long the boot pin is held after the reset pulse; None keeps esptool's 50 ms.
+    /// Boards with large caps on EN often need 500 ms or more.
+    pub reset_delay_ms: Option<u64>,
+    /// How long esptool keeps trying to sync with the bootloader; None keeps esptool's
+    /// default of 7 attempts. Slow adapters may need longer.
+    pub connect_timeout_secs: Option<u32>,
+}
+
+impl ResetConfig {
+    /// Prepare `port` and add the reset arguments to an esptool or ncd_flasher.py command.
+    /// For esptool, call this before adding the subcommand.
+    pub fn apply(&self, command: &mut Command, port: &str) -> Result<(), String> {
+        if self.strategy == ResetStrategy::Unix1200bps {
+            touch_1200bps(port)?;
+        }
+        if let Some(delay_ms) = self.reset_delay_ms {
+            command.env("ESPTOOL_CFGFILE", write_reset_config(delay_ms)?);
+        }
+        if let Some(secs) = self.connect_timeout_secs {
+            // Read by esptool's argument parser, including when ncd_flasher.py calls it in-process
+            command.env("ESPTOOL_CONNECT_ATTEMPTS", connect_attempts(secs).to_string());
+        }
+        let (before, after) = self.strategy.before_after();
+        command.arg("--before").arg(before).arg("--after").arg(after);
+        Ok(())
+    }
+}
+
+/// esptool has no connect timeout in seconds, only a number of attempts; each is a reset
+/// pulse plus a burst of sync packets, about a second in all
+fn connect_attempts(timeout_secs: u32) -> u32 {
+    timeout_secs.max(1)
+}
+
+/// Open and close the port at 1200 baud, which native-USB boards treat as a request to enter the bootloader
+fn touch_1200bps(port: &str) -> Result<(), String> {
+    let serial = serialport::new(port, 1200)
+        .timeout(Duration::from_millis(100))
+        .open()
+        .map_err(|e| format!("Failed to open {} at 1200 baud: {}", port, e))?;
+    drop(serial);
+    thread::sleep(TOUCH_1200BPS_SETTLE);
+    Ok(())
+}
+
+/// Write an esptool config file carrying the reset delay; esptool reads it via ESPTOOL_CFGFILE
+fn write_reset_config(delay_ms: u64) -> Result<PathBuf, String> {
+    let path = std::env::temp_dir().join(format!("sensor-programmer-esptool-{}ms.cfg", delay_ms));
+    let contents = format!("[esptool]\nreset_delay = {:.3}\n", delay_ms as f64 / 1000.0);
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Set the reset strategy and timing used by flash_firmware and erase_device
+#[tauri::command]
+pub fn set_flash_reset(app_handle: AppHandle, reset: ResetConfig) -> Result<(), String> {
+    config::update(&app_handle, |c| c.flash_reset = reset)?;
+    Ok(())
+}
+
+/// Chip families esptool knows, as (name printed by esptool, value for --chip).
+/// Longer names come first so "ESP32-S3" isn't mistaken for plain "ESP32".
+const CHIP_FAMILIES: [(&str, &str); 8] = [
+    ("ESP32-S3", "esp32s3"),
+    ("ESP32-S2", "esp32s2"),
+    ("ESP32-C6", "esp32c6"),
+    ("ESP32-C3", "esp32c3"),
+    ("ESP32-C2", "esp32c2"),
+    ("ESP32-H2", "esp32h2"),
+    ("ESP8266", "esp8266"),
+    ("ESP32", "esp32"),
+];
+
+/// Map a chip description such as "ESP32-D0WD-V3 (revision v3.0)" to its --chip value
+pub fn chip_family(description: &str) -> Option<&'static str> {
+    let description = description.trim().to_uppercase();
+    CHIP_FAMILIES
+        .iter()
+        .find(|(name, _)| description.starts_with(name))
+        .map(|(_, arg)| *arg)
+}
+
+/// Extract the chip family from an esptool output line, if it reports one
+/// ("Chip is ESP32-D0WD-V3 (revision v3.0)" or "Detecting chip type... ESP32")
+pub fn parse_chip_line(line: &str) -> Option<&'static str> {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix("Chip is ") {
+        chip_family(rest)
+    } else if let Some(rest) = line.strip_prefix("Detecting chip type...") {
+        chip_family(rest)
+    } else {
+        None
+    }
+}
+
+/// Extract the MAC from esptool's "MAC: 24:0a:c4:12:34:56" line
+pub fn parse_mac_line(line: &str) -> Option<String> {
+    let mac = line.trim().strip_prefix("MAC: ")?.trim();
+    let is_mac = mac.len() == 17
+        && mac
+            .split(':')
+            .all(|octet| octet.len() == 2 && octet.chars().all(|c| c.is_ascii_hexdigit()));
+    is_mac.then(|| mac.to_uppercase())
+}
+
+/// Identify the chip on `port` by running esptool's chip_id command.
+/// Results are cached per port; pass `refresh` to ignore the cache.
+pub fn detect_chip(app_handle: &AppHandle, esptool: &Esptool, port: &str, refresh: bool) -> Result<String, String> {
+    let state = app_handle.state::<AppState>();
+    if !refresh {
+        if let Some(chip) = state.detected_chips.lock().ok().and_then(|c| c.get(port).cloned()) {
+            return Ok(chip);
+        }
+    }
+
+    let output = esptool
+        .command()
+        .arg("--port")
+        .arg(port)
+        .arg("chip_id")
+        .output()
+        .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let chip = stdout
+        .lines()
+        .find_map(parse_chip_line)
+        .ok_or_else(|| format!("Could not detect chip type on {}", port))?
+        .to_string();
+
+    if let Ok(mut cache) = state.detected_chips.lock() {
+        cache.insert(port.to_string(), chip.clone());
+    }
+    Ok(chip)
+}
+
+/// Drop a cached chip type, e.g. after esptool reports the wrong --chip for a port
+pub fn forget_chip(app_handle: &AppHandle, port: &str) {
+    let state = app_handle.state::<AppState>();
+    if let Ok(mut cache) = state.detected_chips.lock() {
+        cache.remove(port);
+    };
+}
+
+/// Payload of the "read-flash-progress" event
+#[derive(Debug, Clone, Serialize)]
+struct ReadFlashProgress<'a> {
+    port: &'a str,
+    percent: u8,
+}
+
+/// Extract the percentage from esptool's "262144 (6 %)" progress output
+fn parse_progress_percent(line: &str) -> Option<u8> {
+    let start = line.rfind('(')? + 1;
+    let end = start + line[start..].find('%')?;
+    line[start..end].trim().parse().ok()
+}
+
+/// Dump `length` bytes of device flash starting at `offset` to `out_path` using esptool read_flash.
+/// `chip` works as in erase_device ("auto" detects it). Emits "read-flash-output" lines and
+/// "read-flash-progress" percentages; returns the number of bytes written.
+#[tauri::command]
+pub async fn read_flash(
+    app_handle: AppHandle,
+    port: String,
+    chip: Option<String>,
+    offset: u32,
+    length: u32,
+    out_path: String,
+) -> Result<u64, String> {
+    validate_port(&port)?;
+    if length == 0 {
+        return Err("Length must be greater than zero".to_string());
+    }
+    // esptool writes next to out_path and the dump is only moved into place once complete, so
+    // an existing file survives a failed or cancelled read. Creating that file up front also
+    // fails before talking to the device if the directory isn't writable.
+    let part_path = partial_path(Path::new(&out_path))?;
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&part_path)
+        .map_err(|e| format!("Cannot write to {}: {}", part_path.display(), e))?;
+
+    let mut operation = operations::begin(&app_handle, "read_flash", &port);
+    let esptool = match resolve_esptool(&app_handle) {
+        Ok(esptool) => esptool,
+        Err(e) => {
+            let _ = fs::remove_file(&part_path);
+            return Err(e);
+        }
+    };
+
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let dumped = dump_flash(&handle, &esptool, &control, &port, chip.as_deref(), (offset, length), &part_path);
+        let dumped = dumped.and_then(|bytes| {
+            fs::rename(&part_path, &out_path).map_err(|e| format!("Failed to move the dump to {}: {}", out_path, e))?;
+            Ok(bytes)
+        });
+        match &dumped {
+            Ok(bytes) => emit_output(&handle, "read-flash-output", &port, &format!("[CHECK] ✓ Read {} bytes to {}", bytes, out_path)),
+            Err(_) => {
+                let _ = fs::remove_file(&part_path);
+            }
+        }
+        dumped
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |_| true);
+    result
+}
+
+/// Where read_flash has esptool write before moving the dump to `out_path`
+fn partial_path(out_path: &Path) -> Result<PathBuf, String> {
+    let name = out_path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file path", out_path.display()))?;
+    Ok(out_path.with_file_name(format!(".{}.part", name.to_string_lossy())))
+}
+
+/// Run esptool read_flash into `path`, streaming its output. Blocking. Returns the bytes read.
+fn dump_flash(
+    handle: &AppHandle,
+    esptool: &Esptool,
+    control: &OperationControl,
+    port: &str,
+    chip: Option<&str>,
+    (offset, length): (u32, u32),
+    path: &Path,
+) -> Result<u64, String> {
+    let selected_chip = match chip {
+        Some("auto") => detect_chip(handle, esptool, port, false)?,
+        Some(chip) => chip.to_string(),
+        None => "esp32".to_string(),
+    };
+
+    let mut child = esptool
+        .command()
+        .arg("--chip")
+        .arg(&selected_chip)
+        .arg("--port")
+        .arg(port)
+        .arg("read_flash")
+        .arg(format!("0x{:x}", offset))
+        .arg(format!("0x{:x}", length))
+        .arg(path)
+        .env("PYTHONUNBUFFERED", "1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    control.attach_child(child);
+
+    if let Some(stdout) = stdout {
+        let mut last_percent = None;
+        for line in output_lines(stdout) {
+            let line = line.trim();
+            match parse_progress_percent(line) {
+                Some(percent) if last_percent != Some(percent) => {
+                    last_percent = Some(percent);
+                    let _ = handle.emit("read-flash-progress", ReadFlashProgress { port, percent });
+                }
+                Some(_) => {}
+                None => emit_output(handle, "read-flash-output", port, line),
+            }
+        }
+    }
+    if let Some(stderr) = stderr {
+        for line in output_lines(stderr) {
+            emit_stderr(handle, "read-flash-output", port, &line);
+        }
+    }
+
+    let status = control.wait_child()?;
+    if control.is_cancelled() {
+        return Err("Flash read cancelled".to_string());
+    }
+    if !status.success() {
+        return Err(format!("esptool read_flash failed on {}", port));
+    }
+    fs::metadata(path)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Write an image file to flash at `offset` with esptool write_flash, streaming output to `channel`.
+/// Used to restore a backup taken with read_flash; runs as a "restore" operation in a process
+/// slot, so cancel_all stops it and it counts towards max_processes.
+pub async fn write_flash_image(
+    app_handle: &AppHandle,
+    port: &str,
+    offset: u32,
+    image_path: &Path,
+    channel: &str,
+) -> Result<(), String> {
+    let esptool = resolve_esptool(app_handle)?;
+    ensure_esptool_intact(app_handle, &esptool)?;
+    let mut operation = operations::begin(app_handle, "restore", port);
+
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let (port, image_path, channel) = (port.to_string(), image_path.to_path_buf(), channel.to_string());
+    let result = tokio::task::spawn_blocking(move || {
+        let _slot = acquire_process_slot(&handle, &control, || {
+            emit_output(&handle, &channel, &port, QUEUED_MESSAGE)
+        })?;
+        let chip = detect_chip(&handle, &esptool, &port, false)?;
+        let mut child = esptool
+            .command()
+            .arg("--chip")
+            .arg(&chip)
+            .arg("--port")
+            .arg(&port)
+            .arg("write_flash")
+            .arg(format!("0x{:x}", offset))
+            .arg(&image_path)
+            .env("PYTHONUNBUFFERED", "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        control.attach_child(child);
+
+        for (line, is_stderr) in merged_output_lines(stdout, stderr) {
+            if is_stderr {
+                emit_stderr(&handle, &channel, &port, &line);
+            } else {
+                emit_output(&handle, &channel, &port, &line);
+            }
+        }
+
+        let status = control.wait_child()?;
+        if control.is_cancelled() {
+            Err("Firmware restore cancelled".to_string())
+        } else if status.success() {
+            Ok(())
+        } else {
+            Err(format!("esptool write_flash failed on {}", port))
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |_| true);
+    result
+}
+
+/// Everything needed to run esptool commands against one chip during an operation
+struct EsptoolSession<'a> {
+    app_handle: &'a AppHandle,
+    esptool: &'a Esptool,
+    port: &'a str,
+    chip: &'a str,
+    reset: &'a ResetConfig,
+    control: &'a OperationControl,
+    channel: &'a str,
+}
+
+impl EsptoolSession<'_> {
+    /// Run esptool with `args`, passing each output line to `on_line` after streaming it to
+    /// the channel. Blocking. Returns whether esptool exited successfully and the lines it printed.
+    fn run(&self, args: &[String], on_line: &mut dyn FnMut(&str)) -> Result<(bool, Vec<String>), String> {
+        let mut command = self.esptool.command();
+        command.arg("--chip").arg(self.chip).arg("--port").arg(self.port);
+        self.reset.apply(&mut command, self.port)?;
+        let mut child = command
+            .args(args)
+            .env("PYTHONUNBUFFERED", "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        self.control.attach_child(child);
+
+        let mut lines = Vec::new();
+        for line in stdout.into_iter().flat_map(output_lines) {
+            emit_output(self.app_handle, self.channel, self.port, &line);
+            on_line(&line);
+            lines.push(line);
+        }
+        for line in stderr.into_iter().flat_map(output_lines) {
+            emit_stderr(self.app_handle, self.channel, self.port, &line);
+            on_line(&line);
+            lines.push(line);
+        }
+        let status = self.control.wait_child()?;
+        Ok((status.success(), lines))
+    }
+}
+
+/// True when esptool verify_flash reported a matching digest
+fn verify_passed(exited_ok: bool, lines: &[String]) -> bool {
+    exited_ok && lines.iter().any(|line| line.contains("verify OK"))
+}
+
+/// Verify `firmware` against the flash at `offset` (default 0x10000, the app partition) and
+/// only write it if they differ, verifying again afterwards. FlashResult.path_taken says
+/// whether the write was skipped ("verified") or done ("flashed").
+/// Emits "flash-output" lines and "flash-progress" percentages while writing.
+#[tauri::command]
+pub async fn flash_if_needed(
+    app_handle: AppHandle,
+    port: String,
+    firmware: String,
+    offset: Option<u32>,
+) -> Result<FlashResult, String> {
+    validate_port(&port)?;
+    if !Path::new(&firmware).is_file() {
+        return Err(format!("Firmware image not found: {}", firmware));
+    }
+    let offset = format!("0x{:x}", offset.unwrap_or(APP_PARTITION_OFFSET));
+    let esptool = resolve_esptool(&app_handle)?;
+    ensure_esptool_intact(&app_handle, &esptool)?;
+    let reset = config::current(&app_handle).flash_reset;
+    let mut operation = operations::begin(&app_handle, "flash", &port);
+
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let _slot = acquire_process_slot(&handle, &control, || {
+            emit_output(&handle, "flash-output", &port, QUEUED_MESSAGE)
+        })?;
+        let chip = detect_chip(&handle, &esptool, &port, false)?;
+        let session = EsptoolSession {
+            app_handle: &handle,
+            esptool: &esptool,
+            port: &port,
+            chip: &chip,
+            reset: &reset,
+            control: &control,
+            channel: "flash-output",
+        };
+        let verify_args = ["verify_flash".to_string(), offset.clone(), firmware.clone()];
+        let mut output = Vec::new();
+        let mut detected_mac = None;
+        let mut observe = |line: &str| {
+            if let Some(mac) = parse_mac_line(line) {
+                detected_mac = Some(mac);
+            }
+        };
+
+        emit_output(&handle, "flash-output", &port, "[INFO] Verifying the image already on the chip");
+        let (exited_ok, lines) = session.run(&verify_args, &mut observe)?;
+        let already_flashed = verify_passed(exited_ok, &lines);
+        output.extend(lines);
+        if control.is_cancelled() {
+            return Err("Firmware flash cancelled".to_string());
+        }
+
+        let mut meter = ThroughputMeter::default();
+        let (success, failed_phase, message, path_taken) = if already_flashed {
+            (true, None, "Already flashed, verified OK".to_string(), "verified")
+        } else {
+            emit_output(&handle, "flash-output", &port, "[INFO] Image differs - writing it");
+            let write_args = ["write_flash".to_string(), offset.clone(), firmware.clone()];
+            let mut last_percent = None;
+            let mut on_write = |line: &str| {
+                observe(line);
+                meter.observe_write(line);
+                if let Some(percent) = parse_progress_percent(line).filter(|p| last_percent != Some(*p)) {
+                    last_percent = Some(percent);
+                    let _ = handle.emit("flash-progress", FlashProgress { port: port.clone(), percent });
+                }
+            };
+            let (written, lines) = session.run(&write_args, &mut on_write)?;
+            output.extend(lines);
+            if control.is_cancelled() {
+                return Err("Firmware flash cancelled".to_string());
+            }
+
+            if !written {
+                (false, Some("flash"), "Firmware write failed".to_string(), "flashed")
+            } else {
+                let (exited_ok, lines) = session.run(&verify_args, &mut observe)?;
+                let verified = verify_passed(exited_ok, &lines);
+                output.extend(lines);
+                if verified {
+                    (true, None, "Firmware flashed and verified".to_string(), "flashed")
+                } else {
+                    (false, Some("verify"), "Firmware written but verification failed".to_string(), "flashed")
+                }
+            }
+        };
+
+        let throughput = meter.finish();
+        if let Some(throughput) = &throughput {
+            report_throughput(&handle, "flash-output", &port, throughput);
+        }
+        let check = if success { "✓" } else { "✗" };
+        emit_output(&handle, "flash-output", &port, &format!("[CHECK] {} {}", check, message));
+
+        let result = FlashResult {
+            success,
+            message,
+            output: output.join("\n"),
+            throughput,
+            detected_chip: Some(chip),
+            detected_mac,
+            error_category: None,
+            failed_phase: failed_phase.map(str::to_string),
+            path_taken: Some(path_taken.to_string()),
+        };
+        results::record(&handle, "flash", &port, result.detected_mac.as_deref(), result.success, &result.message, &result);
+        Ok(result)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |r| r.success);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_dump_sits_next_to_the_output() {
+        let part = partial_path(Path::new("/tmp/dumps/unit.bin")).unwrap();
+        assert_eq!(part, Path::new("/tmp/dumps/.unit.bin.part"));
+        assert!(partial_path(Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn progress_percent_is_parsed() {
+        assert_eq!(parse_progress_percent("262144 (6 %)"), Some(6));
+        assert_eq!(parse_progress_percent("Read 1048576 bytes"), None);
+    }
+}
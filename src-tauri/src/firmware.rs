@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// First byte of every ESP32 application image
+const ESP_IMAGE_MAGIC: u8 = 0xE9;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ValidationResult {
+    pub valid: bool,
+    pub size: u64,
+    /// Lowercase hex digest of the file contents
+    pub sha256: Option<String>,
+    /// Why the file was rejected
+    pub reason: Option<String>,
+}
+
+impl ValidationResult {
+    fn invalid(size: u64, sha256: Option<String>, reason: String) -> Self {
+        ValidationResult {
+            valid: false,
+            size,
+            sha256,
+            reason: Some(reason),
+        }
+    }
+}
+
+/// Hash a file, returning its size, SHA-256 and first byte
+pub(crate) fn hash_file(path: &Path) -> Result<(u64, String, Option<u8>), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut size = 0u64;
+    let mut first_byte = None;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        first_byte.get_or_insert(buffer[0]);
+        size += read as u64;
+        hasher.update(&buffer[..read]);
+    }
+    let digest = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((size, digest, first_byte))
+}
+
+/// Check a local firmware file before flashing: it must exist, be non-empty, match
+/// `expected_sha256` when given, and start with the ESP32 image magic byte when it's an
+/// app image (`app_image`, default true for .bin files)
+#[tauri::command]
+pub fn validate_firmware(
+    path: String,
+    expected_sha256: Option<String>,
+    app_image: Option<bool>,
+) -> Result<ValidationResult, String> {
+    let path = Path::new(&path);
+    if !path.is_file() {
+        return Ok(ValidationResult::invalid(0, None, format!("File not found: {}", path.display())));
+    }
+
+    let (size, sha256, first_byte) = hash_file(path)?;
+    if size == 0 {
+        return Ok(ValidationResult::invalid(size, Some(sha256), "File is empty".to_string()));
+    }
+    if let Some(expected) = expected_sha256 {
+        let expected = expected.trim().to_lowercase();
+        if expected != sha256 {
+            let reason = format!("SHA-256 mismatch: expected {}, got {}", expected, sha256);
+            return Ok(ValidationResult::invalid(size, Some(sha256), reason));
+        }
+    }
+    let app_image = app_image.unwrap_or_else(|| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bin")));
+    if app_image && first_byte != Some(ESP_IMAGE_MAGIC) {
+        let reason = format!(
+            "Not an ESP32 app image: first byte is 0x{:02X}, expected 0x{:02X}",
+            first_byte.unwrap_or_default(),
+            ESP_IMAGE_MAGIC
+        );
+        return Ok(ValidationResult::invalid(size, Some(sha256), reason));
+    }
+
+    Ok(ValidationResult {
+        valid: true,
+        size,
+        sha256: Some(sha256),
+        reason: None,
+    })
+}
@@ -0,0 +1,104 @@
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::commands::{idle_backoff, open_serial, parse_params, validate_port};
+use crate::esptool::{parse_mac_line, resolve_esptool};
+use crate::mirror::emit_output;
+use crate::operations;
+
+/// How long the application firmware has to answer GET_INFO before esptool is tried
+const INFO_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// MAC with separators dropped and hex uppercased, so "24:0a:c4:12:34:56" equals "240AC4123456"
+fn normalize_mac(mac: &str) -> String {
+    mac.chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Ask the application firmware for its MAC with GET_INFO (a MAC=... parameter in the reply)
+fn query_info_mac(handle: &AppHandle, port: &str) -> Result<Option<String>, String> {
+    let mut serial = open_serial(port)?;
+    let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+    serial
+        .write_all(b"GET_INFO\r\n")
+        .map_err(|e| format!("Failed to send GET_INFO command: {}", e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+
+    let start = Instant::now();
+    let mut line = String::new();
+    while start.elapsed() < INFO_TIMEOUT {
+        match reader.read_line(&mut line) {
+            Ok(0) => idle_backoff(),
+            Ok(_) => {
+                let trimmed = line.trim();
+                if !trimmed.is_empty() {
+                    emit_output(handle, "identity-output", port, trimmed);
+                }
+                let mac = parse_params(trimmed)
+                    .into_iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("MAC"))
+                    .map(|(_, mac)| mac);
+                if mac.is_some() {
+                    return Ok(mac);
+                }
+                line.clear();
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(format!("Read error: {}", e)),
+        }
+    }
+    Ok(None)
+}
+
+/// Read the MAC from the ROM bootloader with esptool chip_id, for units without working firmware
+fn query_esptool_mac(handle: &AppHandle, port: &str) -> Result<Option<String>, String> {
+    let esptool = resolve_esptool(handle)?;
+    let output = esptool
+        .command()
+        .arg("--port")
+        .arg(port)
+        .arg("chip_id")
+        .output()
+        .map_err(|e| format!("Failed to execute esptool: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).lines().find_map(parse_mac_line))
+}
+
+/// Confirm the device on `port` is the one the operator expects before an erase or flash.
+/// COM numbers get recycled on Windows, so after a replug the same port name can reach a
+/// different unit. Asks the firmware with GET_INFO and falls back to esptool chip_id.
+/// Returns the MAC read from the device; emits "identity-output" lines.
+#[tauri::command]
+pub async fn verify_device_identity(app_handle: AppHandle, port: String, expected_mac: String) -> Result<String, String> {
+    validate_port(&port)?;
+    let expected = normalize_mac(&expected_mac);
+    if expected.len() != 12 {
+        return Err(format!("Invalid MAC address: {}", expected_mac));
+    }
+    let mut operation = operations::begin(&app_handle, "identity", &port);
+
+    let handle = app_handle.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let mac = match query_info_mac(&handle, &port)? {
+            Some(mac) => mac,
+            None => {
+                emit_output(&handle, "identity-output", &port, "No GET_INFO reply - reading the MAC with esptool");
+                query_esptool_mac(&handle, &port)?
+                    .ok_or_else(|| format!("Could not read the MAC of the device on {}", port))?
+            }
+        };
+        if normalize_mac(&mac) != expected {
+            return Err(format!(
+                "Port {} now points to a different device (MAC mismatch): expected {}, found {}",
+                port, expected_mac, mac
+            ));
+        }
+        emit_output(&handle, "identity-output", &port, &format!("[CHECK] ✓ Device MAC {} matches", mac));
+        Ok(mac)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |_| true);
+    result
+}
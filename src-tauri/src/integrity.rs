@@ -0,0 +1,116 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tauri::AppHandle;
+
+use crate::commands::get_flasher_path;
+use crate::config;
+use crate::esptool::{resolve_esptool, Esptool};
+use crate::firmware::hash_file;
+
+/// Copies of the scripts this build shipped with; their hashes are the known-good values
+const BUNDLED_FLASHER: &[u8] = include_bytes!("../resources/flasher/ncd_flasher.py");
+const BUNDLED_ESPTOOL: &[u8] = include_bytes!("../resources/flasher/esptool.py");
+
+/// Result of checking one script against the copy bundled with the app
+#[derive(Debug, Serialize)]
+pub struct IntegrityCheck {
+    pub path: String,
+    /// SHA-256 of the bundled copy
+    pub expected: String,
+    /// SHA-256 of the file on disk; None when it couldn't be found or read
+    pub actual: Option<String>,
+    pub ok: bool,
+}
+
+fn hex_digest(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn check_file(path: &Path, bundled: &[u8]) -> IntegrityCheck {
+    let expected = hex_digest(bundled);
+    let actual = match hash_file(path) {
+        Ok((_, digest, _)) => Some(digest),
+        Err(e) => {
+            log::warn!("Integrity check could not hash {}: {}", path.display(), e);
+            None
+        }
+    };
+    IntegrityCheck {
+        path: path.display().to_string(),
+        ok: actual.as_deref() == Some(expected.as_str()),
+        expected,
+        actual,
+    }
+}
+
+fn check_flasher(app_handle: &AppHandle) -> IntegrityCheck {
+    match get_flasher_path(app_handle) {
+        Ok(path) => check_file(&path, BUNDLED_FLASHER),
+        Err(_) => IntegrityCheck {
+            path: "ncd_flasher.py".to_string(),
+            expected: hex_digest(BUNDLED_FLASHER),
+            actual: None,
+            ok: false,
+        },
+    }
+}
+
+fn check_esptool(app_handle: &AppHandle) -> IntegrityCheck {
+    match resolve_esptool(app_handle) {
+        Ok(esptool) => check_file(esptool.path(), BUNDLED_ESPTOOL),
+        Err(_) => IntegrityCheck {
+            path: "esptool.py".to_string(),
+            expected: hex_digest(BUNDLED_ESPTOOL),
+            actual: None,
+            ok: false,
+        },
+    }
+}
+
+/// Refuse a failed check, or only log it when integrity_warn_only is set
+fn enforce(app_handle: &AppHandle, check: IntegrityCheck) -> Result<(), String> {
+    if check.ok {
+        return Ok(());
+    }
+    let found = check.actual.as_deref().unwrap_or("unreadable");
+    let problem = format!(
+        "{} does not match the bundled copy (expected SHA-256 {}, found {})",
+        check.path, check.expected, found
+    );
+    if config::current(app_handle).integrity_warn_only {
+        log::warn!("{}; continuing because integrity_warn_only is set", problem);
+        return Ok(());
+    }
+    Err(format!(
+        "Tooling integrity check failed: {}. Reinstall the app, or turn on warn-only integrity checks for development",
+        problem
+    ))
+}
+
+/// Check ncd_flasher.py at `path` before running it
+pub(crate) fn ensure_flasher_intact(app_handle: &AppHandle, path: &Path) -> Result<(), String> {
+    enforce(app_handle, check_file(path, BUNDLED_FLASHER))
+}
+
+/// Check the resolved esptool before running it
+pub(crate) fn ensure_esptool_intact(app_handle: &AppHandle, esptool: &Esptool) -> Result<(), String> {
+    enforce(app_handle, check_file(esptool.path(), BUNDLED_ESPTOOL))
+}
+
+/// Hash the ncd_flasher.py and esptool the app would run and compare them with the copies
+/// bundled in this build. A custom path or an esptool from PATH won't match.
+#[tauri::command]
+pub async fn verify_tooling_integrity(app_handle: AppHandle) -> Result<Vec<IntegrityCheck>, String> {
+    tokio::task::spawn_blocking(move || vec![check_flasher(&app_handle), check_esptool(&app_handle)])
+        .await
+        .map_err(|e| format!("Task join error: {}", e))
+}
+
+/// Let flashing and erasing go ahead with tooling that fails the integrity check, logging a
+/// warning instead (for development with modified scripts)
+#[tauri::command]
+pub fn set_integrity_warn_only(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    config::update(&app_handle, |c| c.integrity_warn_only = enabled)?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::commands::{idle_backoff, open_serial, validate_port};
+use crate::operations;
+
+/// How long to wait for each PONG before counting the round as lost
+const PONG_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_ITERATIONS: u32 = 20;
+
+/// Round-trip PING -> PONG timings in milliseconds, over the rounds that got a reply
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub iterations: u32,
+    /// Rounds that timed out without a PONG
+    pub lost: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Send one PING and time the PONG; None if it doesn't arrive within PONG_TIMEOUT
+fn ping_once<R: BufRead>(serial: &mut dyn Write, reader: &mut R) -> Result<Option<Duration>, String> {
+    let start = Instant::now();
+    serial
+        .write_all(b"PING\r\n")
+        .map_err(|e| format!("Failed to send PING: {}", e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+
+    let mut line = String::new();
+    while start.elapsed() < PONG_TIMEOUT {
+        match reader.read_line(&mut line) {
+            Ok(0) => idle_backoff(),
+            Ok(_) if line.contains("PONG") => return Ok(Some(start.elapsed())),
+            Ok(_) => line.clear(),
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(format!("Read error: {}", e)),
+        }
+    }
+    Ok(None)
+}
+
+/// Measure command round-trip latency on a port by timing `iterations` PING/PONG exchanges
+/// (default 20). Useful for spotting slow USB hubs.
+#[tauri::command]
+pub async fn measure_latency(app_handle: AppHandle, port: String, iterations: Option<u32>) -> Result<LatencyStats, String> {
+    validate_port(&port)?;
+    let iterations = iterations.unwrap_or(DEFAULT_ITERATIONS).max(1);
+    let mut operation = operations::begin(&app_handle, "latency", &port);
+
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut serial = open_serial(&port)?;
+        let mut reader = BufReader::new(serial.try_clone().map_err(|e| e.to_string())?);
+
+        let mut samples = Vec::new();
+        for _ in 0..iterations {
+            if control.is_cancelled() {
+                return Err("Latency measurement cancelled".to_string());
+            }
+            if let Some(elapsed) = ping_once(&mut *serial, &mut reader)? {
+                samples.push(elapsed.as_secs_f64() * 1000.0);
+            }
+        }
+        if samples.is_empty() {
+            return Err(format!("No PONG received from {} in {} attempts", port, iterations));
+        }
+
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let p95_index = ((samples.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+        Ok(LatencyStats {
+            iterations,
+            lost: iterations - samples.len() as u32,
+            min_ms: samples[0],
+            avg_ms: samples.iter().sum::<f64>() / samples.len() as f64,
+            max_ms: samples[samples.len() - 1],
+            p95_ms: samples[p95_index],
+        })
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |_| true);
+    result
+}
@@ -1,25 +1,131 @@
+use tauri::Manager;
+
+mod batch;
+mod burnin;
+mod capabilities;
+mod checklist;
 mod commands;
+mod config;
+mod custom_flash;
+mod device_config;
+mod esptool;
+mod firmware;
+mod identity;
+mod integrity;
+mod latency;
+mod logging;
+mod loopback;
+mod mirror;
+mod mqtt;
+mod operations;
+mod outbox;
+mod port_recovery;
+mod profiles;
+mod protocol;
+mod provision;
+mod resets;
+mod results;
+mod self_test;
+mod session;
+mod session_stats;
+mod signal;
+mod slots;
+mod soak;
+mod stages;
+mod state;
+mod throughput;
+mod versions;
+mod workflows;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
     .plugin(tauri_plugin_shell::init())
+    .manage(state::AppState::default())
     .setup(|app| {
-      if cfg!(debug_assertions) {
-        app.handle().plugin(
-          tauri_plugin_log::Builder::default()
-            .level(log::LevelFilter::Info)
-            .build(),
-        )?;
+      let loaded = config::load(app.handle());
+      logging::init(app.handle(), &loaded)?;
+      commands::set_python_override(loaded.python_path.clone());
+      if let Ok(mut config) = app.state::<state::AppState>().config.lock() {
+        *config = loaded;
       }
+      session_stats::restore(app.handle());
+      session_stats::start_autosave(app.handle().clone());
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
       commands::list_serial_ports,
+      commands::auto_select_port,
+      commands::set_port_allowlist,
+      port_recovery::force_release_port,
+      slots::register_slot,
+      slots::resolve_slot_port,
       commands::flash_firmware,
+      commands::set_flash_debounce,
+      custom_flash::flash_custom_protocol,
+      esptool::flash_if_needed,
       commands::run_device_test,
+      commands::list_test_profiles,
+      commands::describe_profile,
+      commands::replay_test_log,
+      batch::run_batch_test,
+      burnin::run_burnin_test,
+      soak::run_soak,
+      workflows::run_flash_and_test,
+      commands::clear_session_macs,
+      session_stats::get_session_stats,
+      session_stats::start_new_session,
+      session_stats::switch_profile,
       commands::factory_reset,
       commands::erase_device,
+      commands::set_flasher_path,
+      commands::set_python_path,
+      esptool::set_esptool_path,
+      esptool::set_flash_reset,
+      esptool::read_flash,
+      firmware::validate_firmware,
+      identity::verify_device_identity,
+      integrity::verify_tooling_integrity,
+      integrity::set_integrity_warn_only,
+      latency::measure_latency,
+      loopback::test_loopback,
+      mqtt::check_mqtt,
+      operations::get_active_operations,
+      operations::cancel_all_operations,
+      operations::set_max_processes,
+      operations::get_operation_log,
+      operations::acknowledge_operation_log,
+      config::get_config,
+      config::export_settings,
+      config::import_settings,
+      versions::get_versions,
+      capabilities::get_capabilities,
+      self_test::run_self_test,
+      device_config::read_device_config,
+      provision::set_serial_pattern,
+      provision::accept_scanned_serial,
+      provision::provision_device,
+      provision::set_device_time,
+      logging::set_log_level,
+      logging::get_log_level,
+      signal::set_result_signal_command,
+      signal::set_notify_on_complete,
+      outbox::set_outbox_dir,
+      outbox::set_station_name,
+      mirror::enable_event_mirror,
+      mirror::disable_event_mirror,
+      mirror::get_event_mirror,
+      mirror::set_console_stream,
+      results::export_unit_report,
+      results::diff_test_results,
+      results::get_event_timeline,
+      results::finalize_unit,
+      results::update_unit_metadata,
+      results::count_results,
+      results::prune_results,
+      results::archive_results,
+      results::get_yield,
+      results::export_yield_csv,
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
@@ -0,0 +1,52 @@
+use log::LevelFilter;
+use tauri::AppHandle;
+
+use crate::config::{self, AppConfig};
+
+/// Level used when none has been chosen
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unknown log level '{}' (use off, error, warn, info, debug or trace)", level))
+}
+
+/// Register the log plugin (always in debug builds, in release only with `release_logging`).
+/// The plugin itself passes everything through; the effective level is the global
+/// max level, which set_log_level can change at runtime.
+pub fn init(app_handle: &AppHandle, config: &AppConfig) -> tauri::Result<()> {
+    if !cfg!(debug_assertions) && !config.release_logging {
+        return Ok(());
+    }
+    app_handle.plugin(
+        tauri_plugin_log::Builder::default()
+            .level(LevelFilter::Trace)
+            .build(),
+    )?;
+    let level = config
+        .log_level
+        .as_deref()
+        .and_then(|level| parse_level(level).ok())
+        .unwrap_or(DEFAULT_LEVEL);
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Change the app log level ("off", "error", "warn", "info", "debug" or "trace");
+/// the choice is saved and applied again at startup
+#[tauri::command]
+pub fn set_log_level(app_handle: AppHandle, level: String) -> Result<(), String> {
+    let filter = parse_level(&level)?;
+    log::set_max_level(filter);
+    config::update(&app_handle, |c| c.log_level = Some(filter.as_str().to_lowercase()))?;
+    log::info!("Log level set to {}", filter);
+    Ok(())
+}
+
+/// Current app log level
+#[tauri::command]
+pub fn get_log_level() -> String {
+    log::max_level().as_str().to_lowercase()
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+use crate::commands::{idle_backoff, open_serial_at, validate_port, DEFAULT_BAUD};
+use crate::operations;
+use crate::results::now_millis;
+
+/// How long the echoed pattern has to come back
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Result of test_loopback
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoopbackResult {
+    pub success: bool,
+    pub message: String,
+    pub baud: u32,
+    /// Time from writing the pattern to reading all of it back
+    pub round_trip_ms: Option<f64>,
+}
+
+/// Check a fixture's serial path with TX shorted to RX: write a known pattern at `baud`
+/// (default 115200) and expect the same bytes back. Fixture self-test; no device is involved.
+#[tauri::command]
+pub async fn test_loopback(app_handle: AppHandle, port: String, baud: Option<u32>) -> Result<LoopbackResult, String> {
+    validate_port(&port)?;
+    let baud = baud.unwrap_or(DEFAULT_BAUD);
+    let mut operation = operations::begin(&app_handle, "loopback", &port);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let mut serial = open_serial_at(&port, baud)?;
+        // Unique per run so stale bytes from an earlier attempt can't pass
+        let pattern = format!("LOOPBACK-{}-0123456789ABCDEF\r\n", now_millis());
+
+        let start = Instant::now();
+        serial
+            .write_all(pattern.as_bytes())
+            .map_err(|e| format!("Failed to write loopback pattern: {}", e))?;
+        serial.flush().map_err(|e| format!("Failed to flush: {}", e))?;
+
+        let mut received = Vec::new();
+        let mut buffer = [0u8; 256];
+        while start.elapsed() < LOOPBACK_TIMEOUT && received.len() < pattern.len() {
+            match serial.read(&mut buffer) {
+                Ok(0) => idle_backoff(),
+                Ok(read) => received.extend_from_slice(&buffer[..read]),
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Read error: {}", e)),
+            }
+        }
+        let round_trip_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        let (success, message) = if received.is_empty() {
+            (false, "No loopback — check jig wiring or short TX to RX".to_string())
+        } else if received.starts_with(pattern.as_bytes()) {
+            (true, format!("Loopback OK at {} baud in {:.1} ms", baud, round_trip_ms))
+        } else {
+            (
+                false,
+                format!(
+                    "Loopback data mismatch: sent {} bytes, got {:?}",
+                    pattern.len(),
+                    String::from_utf8_lossy(&received)
+                ),
+            )
+        };
+        Ok(LoopbackResult {
+            success,
+            message,
+            baud,
+            round_trip_ms: success.then_some(round_trip_ms),
+        })
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |r| r.success);
+    result
+}
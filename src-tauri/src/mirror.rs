@@ -0,0 +1,326 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::operations;
+use crate::state::AppState;
+
+/// Destination for mirrored events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorTarget {
+    /// Append newline-delimited JSON to a file
+    File(PathBuf),
+    /// Serve newline-delimited JSON to any client connected to 127.0.0.1:<port>
+    Tcp(u16),
+}
+
+/// One mirrored line, serialized as a single JSON object
+#[derive(Debug, Serialize)]
+struct MirroredEvent<'a> {
+    operation: &'a str,
+    port: &'a str,
+    payload: &'a str,
+    timestamp_ms: u128,
+}
+
+/// Payload of the "console-stream" event: one output line from any operation on any port
+#[derive(Debug, Clone, Serialize)]
+struct ConsoleLine<'a> {
+    port: &'a str,
+    /// "flash", "test", "erase", ... (the "*-output" channel without its suffix)
+    operation: &'a str,
+    line: &'a str,
+    timestamp_ms: u128,
+}
+
+/// A TCP client that doesn't take a line within this long is disconnected
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+/// Lines waiting for the TCP writer thread; further lines are dropped until it catches up
+const TCP_QUEUE_LINES: usize = 4096;
+
+enum Sink {
+    File(File),
+    Tcp {
+        /// Lines for the writer thread, so a slow client never blocks the caller
+        lines: SyncSender<String>,
+        stop: Arc<AtomicBool>,
+        port: u16,
+    },
+}
+
+/// An active event mirror; dropping it closes the file or stops the TCP listener
+pub struct EventMirror {
+    target: MirrorTarget,
+    sink: Sink,
+}
+
+impl EventMirror {
+    pub fn open(target: MirrorTarget) -> Result<Self, String> {
+        let sink = match &target {
+            MirrorTarget::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Failed to open mirror file {}: {}", path.display(), e))?;
+                Sink::File(file)
+            }
+            MirrorTarget::Tcp(port) => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, *port))
+                    .map_err(|e| format!("Failed to listen on 127.0.0.1:{}: {}", port, e))?;
+                let clients: Arc<Mutex<Vec<TcpStream>>> = Arc::new(Mutex::new(Vec::new()));
+                let stop = Arc::new(AtomicBool::new(false));
+
+                let accept_clients = clients.clone();
+                let accept_stop = stop.clone();
+                std::thread::spawn(move || {
+                    for stream in listener.incoming() {
+                        if accept_stop.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let Ok(stream) = stream else {
+                            continue;
+                        };
+                        if stream.set_write_timeout(Some(CLIENT_WRITE_TIMEOUT)).is_err() {
+                            continue;
+                        }
+                        if let Ok(mut clients) = accept_clients.lock() {
+                            clients.push(stream);
+                        }
+                    }
+                });
+
+                // Ends when the mirror is dropped and the sender with it
+                let (lines, queued) = sync_channel::<String>(TCP_QUEUE_LINES);
+                std::thread::spawn(move || {
+                    for line in queued {
+                        if let Ok(mut clients) = clients.lock() {
+                            // Drop any client whose connection has gone away or stopped reading
+                            clients.retain_mut(|client| writeln!(client, "{}", line).is_ok());
+                        }
+                    }
+                });
+
+                Sink::Tcp {
+                    lines,
+                    stop,
+                    port: *port,
+                }
+            }
+        };
+
+        Ok(EventMirror { target, sink })
+    }
+
+    pub fn target(&self) -> &MirrorTarget {
+        &self.target
+    }
+
+    fn write_line(&mut self, line: &str) {
+        match &mut self.sink {
+            Sink::File(file) => {
+                let _ = writeln!(file, "{}", line);
+                let _ = file.flush();
+            }
+            Sink::Tcp { lines, .. } => {
+                let _ = lines.try_send(line.to_string());
+            }
+        }
+    }
+}
+
+impl Drop for EventMirror {
+    fn drop(&mut self) {
+        if let Sink::Tcp { stop, port, .. } = &self.sink {
+            // Wake the blocking accept loop so the listener thread can exit
+            stop.store(true, Ordering::SeqCst);
+            let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, *port));
+        }
+    }
+}
+
+/// Emit a line on an "*-output" channel, mirror it to the external sink if one is enabled
+/// and keep it in the logs of the operations running on `port`
+pub fn emit_output(handle: &AppHandle, channel: &str, port: &str, payload: &str) {
+    let _ = handle.emit(channel, payload);
+    mirror_line(handle, channel, port, payload);
+    stream_to_console(handle, channel, port, payload);
+    operations::record_output(handle, port, payload);
+}
+
+/// Payload of the "*-output-err" events
+#[derive(Debug, Clone, Serialize)]
+struct StderrLine<'a> {
+    port: &'a str,
+    line: &'a str,
+    stream: &'static str,
+}
+
+/// Emit a line a tool wrote to stderr: on `channel` with the rest of its output, so the
+/// combined view keeps its order, and on "<channel>-err" tagged with stream "stderr"
+pub fn emit_stderr(handle: &AppHandle, channel: &str, port: &str, line: &str) {
+    emit_output(handle, channel, port, line);
+    let payload = StderrLine { port, line, stream: "stderr" };
+    let _ = handle.emit(&format!("{}-err", channel), payload);
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+/// Forward one line to the combined "console-stream" event, if it is turned on
+fn stream_to_console(handle: &AppHandle, channel: &str, port: &str, line: &str) {
+    if !handle.state::<AppState>().console_stream.load(Ordering::Relaxed) {
+        return;
+    }
+    let event = ConsoleLine {
+        port,
+        operation: channel.strip_suffix("-output").unwrap_or(channel),
+        line,
+        timestamp_ms: now_ms(),
+    };
+    let _ = handle.emit("console-stream", event);
+}
+
+/// Write one line to the external mirror, if one is enabled
+fn mirror_line(handle: &AppHandle, channel: &str, port: &str, payload: &str) {
+    let state = handle.state::<AppState>();
+    let Ok(mut mirror) = state.event_mirror.lock() else {
+        return;
+    };
+    if let Some(mirror) = mirror.as_mut() {
+        let event = MirroredEvent {
+            operation: channel.strip_suffix("-output").unwrap_or(channel),
+            port,
+            payload,
+            timestamp_ms: now_ms(),
+        };
+        if let Ok(line) = serde_json::to_string(&event) {
+            mirror.write_line(&line);
+        }
+    }
+}
+
+/// Longest a line waits in a batch before it is sent
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+/// Batch size that triggers an immediate send
+const BATCH_MAX_LINES: usize = 32;
+
+/// Lines generated by the tester itself rather than the device; always sent on their own
+fn is_status_line(line: &str) -> bool {
+    line.starts_with("[CHECK]") || line.starts_with("[INFO]") || line.starts_with("[WARN]")
+}
+
+/// Sends a command's output to the UI line by line, or when batching, collects raw device
+/// lines and sends them as a single "<channel>-batch" array to keep a chatty boot from
+/// flooding IPC. Status lines always go out immediately on the normal channel.
+/// The mirror still receives every line individually.
+pub struct OutputSink {
+    handle: AppHandle,
+    channel: String,
+    port: String,
+    batching: bool,
+    pending: Vec<String>,
+    pending_since: Option<Instant>,
+}
+
+impl OutputSink {
+    pub fn new(handle: &AppHandle, channel: &str, port: &str, batching: bool) -> Self {
+        OutputSink {
+            handle: handle.clone(),
+            channel: channel.to_string(),
+            port: port.to_string(),
+            batching,
+            pending: Vec::new(),
+            pending_since: None,
+        }
+    }
+
+    pub fn emit(&mut self, line: &str) {
+        if !self.batching || is_status_line(line) {
+            // Keep ordering: anything already queued goes first
+            self.flush();
+            emit_output(&self.handle, &self.channel, &self.port, line);
+            return;
+        }
+        self.pending.push(line.to_string());
+        self.pending_since.get_or_insert_with(Instant::now);
+        if self.pending.len() >= BATCH_MAX_LINES {
+            self.flush();
+        }
+    }
+
+    /// Send the pending batch if it has waited long enough; call this between reads
+    pub fn tick(&mut self) {
+        if self.pending_since.is_some_and(|since| since.elapsed() >= BATCH_INTERVAL) {
+            self.flush();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        self.pending_since = None;
+        if self.pending.is_empty() {
+            return;
+        }
+        let lines = std::mem::take(&mut self.pending);
+        let _ = self.handle.emit(&format!("{}-batch", self.channel), &lines);
+        for line in &lines {
+            mirror_line(&self.handle, &self.channel, &self.port, line);
+            stream_to_console(&self.handle, &self.channel, &self.port, line);
+            operations::record_output(&self.handle, &self.port, line);
+        }
+    }
+}
+
+impl Drop for OutputSink {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Start mirroring all "*-output" events to a file or local TCP socket,
+/// replacing any mirror that is already active
+#[tauri::command]
+pub fn enable_event_mirror(app_handle: AppHandle, target: MirrorTarget) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let mut current = state.event_mirror.lock().map_err(|e| e.to_string())?;
+    // Drop the previous mirror first so a TCP target can rebind the same port
+    *current = None;
+    *current = Some(EventMirror::open(target)?);
+    Ok(())
+}
+
+/// Stop mirroring events
+#[tauri::command]
+pub fn disable_event_mirror(app_handle: AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    *state.event_mirror.lock().map_err(|e| e.to_string())? = None;
+    Ok(())
+}
+
+/// Turn the combined "console-stream" event on or off. While on, every "*-output" line from
+/// every port is also sent there tagged with its port and operation, for a supervisor view
+/// of several stations; the per-operation events are sent either way.
+#[tauri::command]
+pub fn set_console_stream(app_handle: AppHandle, enabled: bool) {
+    app_handle.state::<AppState>().console_stream.store(enabled, Ordering::Relaxed);
+}
+
+/// Report the active mirror target, if any
+#[tauri::command]
+pub fn get_event_mirror(app_handle: AppHandle) -> Result<Option<MirrorTarget>, String> {
+    let state = app_handle.state::<AppState>();
+    let mirror = state.event_mirror.lock().map_err(|e| e.to_string())?;
+    Ok(mirror.as_ref().map(|m| m.target().clone()))
+}
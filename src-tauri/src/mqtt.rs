@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MQTT_PORT: u16 = 1883;
+/// Budget for the TCP connect, and again for the CONNACK when a CONNECT is sent
+const MQTT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Result of check_mqtt
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MqttCheck {
+    pub reachable: bool,
+    /// TCP connect time, plus the CONNECT/CONNACK round trip when one was sent
+    pub latency_ms: Option<f64>,
+    /// CONNACK return code (0 = accepted, 4/5 = credentials refused) when a CONNECT was sent
+    pub connack_code: Option<u8>,
+    pub error: Option<String>,
+}
+
+impl MqttCheck {
+    fn unreachable(error: String) -> Self {
+        MqttCheck {
+            reachable: false,
+            latency_ms: None,
+            connack_code: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// An MQTT 3.1.1 CONNECT packet with a clean session and no credentials
+fn connect_packet(client_id: &str) -> Vec<u8> {
+    let mut body = vec![0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3C];
+    body.extend_from_slice(&(client_id.len() as u16).to_be_bytes());
+    body.extend_from_slice(client_id.as_bytes());
+
+    // Remaining length is always under 128 here, so it fits in one byte
+    let mut packet = vec![0x10, body.len() as u8];
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Send CONNECT and return the CONNACK return code
+fn mqtt_handshake(stream: &mut TcpStream) -> Result<u8, String> {
+    stream
+        .set_read_timeout(Some(MQTT_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let client_id = format!("sensor-programmer-{}", std::process::id());
+    stream
+        .write_all(&connect_packet(&client_id))
+        .map_err(|e| format!("Failed to send CONNECT: {}", e))?;
+
+    let mut connack = [0u8; 4];
+    stream
+        .read_exact(&mut connack)
+        .map_err(|e| format!("No CONNACK from broker: {}", e))?;
+    if connack[0] != 0x20 || connack[1] != 0x02 {
+        return Err(format!("Unexpected reply to CONNECT: {:02X?}", connack));
+    }
+    // Be polite: DISCONNECT before dropping the socket
+    let _ = stream.write_all(&[0xE0, 0x00]);
+    Ok(connack[3])
+}
+
+fn check(host: &str, port: u16, mqtt_connect: bool) -> MqttCheck {
+    let addr = match (host, port).to_socket_addrs().map(|mut addrs| addrs.next()) {
+        Ok(Some(addr)) => addr,
+        Ok(None) => return MqttCheck::unreachable(format!("No address found for {}", host)),
+        Err(e) => return MqttCheck::unreachable(format!("Could not resolve {}: {}", host, e)),
+    };
+
+    let start = Instant::now();
+    let mut stream = match TcpStream::connect_timeout(&addr, MQTT_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(e) => return MqttCheck::unreachable(format!("Could not connect to {}:{}: {}", host, port, e)),
+    };
+    if !mqtt_connect {
+        return MqttCheck {
+            reachable: true,
+            latency_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+            connack_code: None,
+            error: None,
+        };
+    }
+
+    match mqtt_handshake(&mut stream) {
+        Ok(code) => MqttCheck {
+            reachable: true,
+            latency_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+            connack_code: Some(code),
+            error: (code != 0).then(|| format!("Broker refused CONNECT (return code {})", code)),
+        },
+        Err(e) => MqttCheck::unreachable(e),
+    }
+}
+
+/// Confirm the MQTT broker is reachable from this station with a TCP connect to `host:port`
+/// (default 1883). With `mqtt_connect`, also send an MQTT CONNECT and report the CONNACK code.
+#[tauri::command]
+pub async fn check_mqtt(host: String, port: Option<u16>, mqtt_connect: Option<bool>) -> Result<MqttCheck, String> {
+    let host = host.trim().to_string();
+    if host.is_empty() {
+        return Err("Host cannot be empty".to_string());
+    }
+    let port = port.unwrap_or(DEFAULT_MQTT_PORT);
+    let mqtt_connect = mqtt_connect.unwrap_or(false);
+    let result = tokio::task::spawn_blocking(move || check(&host, port, mqtt_connect))
+        .await
+        .map_err(|e| format!("Task join error: {}", e))?;
+    Ok(result)
+}
@@ -0,0 +1,375 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::config;
+use crate::state::AppState;
+
+/// An in-flight device operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationInfo {
+    pub operation_id: String,
+    /// "flash", "test", "reset", "erase", "latency", "loopback", "read_flash", "read_config",
+    /// "identity", "provision", "batch", "burnin", "soak" or "restore"
+    pub kind: String,
+    pub port: String,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+}
+
+/// Shared between an operation's worker and the registry so the operation can be
+/// cancelled from outside: the worker polls `is_cancelled` and hands over any child
+/// process it spawns so it can be killed
+#[derive(Debug, Clone, Default)]
+pub struct OperationControl {
+    cancelled: Arc<AtomicBool>,
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl OperationControl {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Track a spawned process; take its stdout/stderr first, then call `wait_child`
+    pub fn attach_child(&self, child: Child) {
+        if let Ok(mut slot) = self.child.lock() {
+            *slot = Some(child);
+        }
+    }
+
+    /// Wait for the attached child to exit
+    pub fn wait_child(&self) -> Result<ExitStatus, String> {
+        let child = self.child.lock().map_err(|e| e.to_string())?.take();
+        let mut child = child.ok_or("No process attached to operation")?;
+        child.wait().map_err(|e| format!("Failed to wait for process: {}", e))
+    }
+
+    /// Flag the operation as cancelled and kill its child process, if any
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Ok(mut slot) = self.child.lock() {
+            if let Some(child) = slot.as_mut() {
+                let _ = child.kill();
+            }
+        }
+    }
+}
+
+/// Output lines kept per operation for get_operation_log
+const OPERATION_LOG_LINES: usize = 500;
+/// Finished logs kept while waiting for acknowledge_operation_log; the oldest go first
+const FINISHED_LOGS_KEPT: usize = 20;
+
+/// Recent output of one operation, so a reloaded UI can catch up. Kept after the
+/// operation ends until acknowledged with acknowledge_operation_log.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationLog {
+    pub info: OperationInfo,
+    /// The last OPERATION_LOG_LINES lines emitted on the operation's port, oldest first
+    pub lines: VecDeque<String>,
+    /// Older lines that no longer fit
+    pub dropped: u64,
+    pub finished: bool,
+    /// "pass", "fail" or "error" once finished
+    pub outcome: Option<String>,
+}
+
+/// Append an output line to the log of every operation running on `port`
+pub fn record_output(app_handle: &AppHandle, port: &str, line: &str) {
+    let state = app_handle.state::<AppState>();
+    let Ok(operations) = state.operations.lock() else {
+        return;
+    };
+    let Ok(mut logs) = state.operation_logs.lock() else {
+        return;
+    };
+    for op in operations.iter().filter(|op| op.info.port == port) {
+        if let Some(log) = logs.iter_mut().find(|log| log.info.operation_id == op.info.operation_id) {
+            if log.lines.len() >= OPERATION_LOG_LINES {
+                log.lines.pop_front();
+                log.dropped += 1;
+            }
+            log.lines.push_back(line.to_string());
+        }
+    }
+}
+
+/// Mark an operation's log finished, dropping the oldest finished logs beyond FINISHED_LOGS_KEPT
+fn finish_log(app_handle: &AppHandle, operation_id: &str, outcome: &str) {
+    let state = app_handle.state::<AppState>();
+    let Ok(mut logs) = state.operation_logs.lock() else {
+        return;
+    };
+    if let Some(log) = logs.iter_mut().find(|log| log.info.operation_id == operation_id) {
+        log.finished = true;
+        log.outcome = Some(outcome.to_string());
+    }
+    let mut excess = logs.iter().filter(|log| log.finished).count().saturating_sub(FINISHED_LOGS_KEPT);
+    logs.retain(|log| {
+        let drop = excess > 0 && log.finished;
+        if drop {
+            excess -= 1;
+        }
+        !drop
+    });
+}
+
+/// Registry entry for an in-flight operation
+pub struct ActiveOperation {
+    pub info: OperationInfo,
+    control: OperationControl,
+}
+
+/// Keeps an operation registered for as long as it is alive, and logs its start and end
+/// to the app log under its operation_id so log lines can be tied to one command
+pub struct OperationGuard {
+    app_handle: AppHandle,
+    operation_id: String,
+    kind: String,
+    port: String,
+    started: Instant,
+    finished: bool,
+    control: OperationControl,
+}
+
+impl OperationGuard {
+    /// Cancellation/child-process handle to move into the operation's worker
+    pub fn control(&self) -> OperationControl {
+        self.control.clone()
+    }
+
+    /// Port the operation runs on
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// Log how the operation ended. `passed` decides pass/fail for a completed run;
+    /// errors are logged at error level with their message.
+    pub fn finish<T>(&mut self, result: &Result<T, String>, passed: impl FnOnce(&T) -> bool) {
+        self.finished = true;
+        let duration_ms = self.started.elapsed().as_millis();
+        let outcome = match result {
+            Ok(value) => {
+                let outcome = if passed(value) { "pass" } else { "fail" };
+                log::info!(
+                    "[{}] end kind={} port={} duration_ms={} outcome={}",
+                    self.operation_id,
+                    self.kind,
+                    self.port,
+                    duration_ms,
+                    outcome
+                );
+                outcome
+            }
+            Err(e) => {
+                log::error!(
+                    "[{}] end kind={} port={} duration_ms={} outcome=error: {}",
+                    self.operation_id,
+                    self.kind,
+                    self.port,
+                    duration_ms,
+                    e
+                );
+                "error"
+            }
+        };
+        finish_log(&self.app_handle, &self.operation_id, outcome);
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Bailed out with `?` before reaching finish()
+            log::error!(
+                "[{}] end kind={} port={} duration_ms={} outcome=error",
+                self.operation_id,
+                self.kind,
+                self.port,
+                self.started.elapsed().as_millis()
+            );
+            finish_log(&self.app_handle, &self.operation_id, "error");
+        }
+        let state = self.app_handle.state::<AppState>();
+        if let Ok(mut operations) = state.operations.lock() {
+            operations.retain(|op| op.info.operation_id != self.operation_id);
+        };
+    }
+}
+
+/// Register a new operation; it is removed again when the returned guard is dropped
+pub fn begin(app_handle: &AppHandle, kind: &str, port: &str) -> OperationGuard {
+    let state = app_handle.state::<AppState>();
+    let operation_id = format!("op-{}", state.next_operation_id.fetch_add(1, Ordering::SeqCst) + 1);
+    let info = OperationInfo {
+        operation_id: operation_id.clone(),
+        kind: kind.to_string(),
+        port: port.to_string(),
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default(),
+    };
+    let control = OperationControl::default();
+    if let Ok(mut logs) = state.operation_logs.lock() {
+        logs.push(OperationLog {
+            info: info.clone(),
+            lines: VecDeque::new(),
+            dropped: 0,
+            finished: false,
+            outcome: None,
+        });
+    }
+    if let Ok(mut operations) = state.operations.lock() {
+        operations.push(ActiveOperation {
+            info,
+            control: control.clone(),
+        });
+    }
+    log::info!("[{}] start kind={} port={}", operation_id, kind, port);
+    OperationGuard {
+        app_handle: app_handle.clone(),
+        operation_id,
+        kind: kind.to_string(),
+        port: port.to_string(),
+        started: Instant::now(),
+        finished: false,
+        control,
+    }
+}
+
+/// List the operations currently running, oldest first
+#[tauri::command]
+pub fn get_active_operations(app_handle: AppHandle) -> Result<Vec<OperationInfo>, String> {
+    let state = app_handle.state::<AppState>();
+    let operations = state.operations.lock().map_err(|e| e.to_string())?;
+    Ok(operations.iter().map(|op| op.info.clone()).collect())
+}
+
+/// Recent output of an operation, running or finished but not yet acknowledged,
+/// so a reloaded UI can re-sync before listening for new events
+#[tauri::command]
+pub fn get_operation_log(app_handle: AppHandle, operation_id: String) -> Result<OperationLog, String> {
+    let state = app_handle.state::<AppState>();
+    let logs = state.operation_logs.lock().map_err(|e| e.to_string())?;
+    logs.iter()
+        .find(|log| log.info.operation_id == operation_id)
+        .cloned()
+        .ok_or_else(|| format!("No log for operation '{}'", operation_id))
+}
+
+/// Discard a finished operation's log once the UI has shown its outcome.
+/// Returns false if the operation is still running (its log is kept).
+#[tauri::command]
+pub fn acknowledge_operation_log(app_handle: AppHandle, operation_id: String) -> Result<bool, String> {
+    let state = app_handle.state::<AppState>();
+    let mut logs = state.operation_logs.lock().map_err(|e| e.to_string())?;
+    let before = logs.len();
+    logs.retain(|log| !(log.finished && log.info.operation_id == operation_id));
+    Ok(logs.len() < before)
+}
+
+/// Cancel the operations running on one port and kill their child processes. Returns how many there were.
+pub fn cancel_port(app_handle: &AppHandle, port: &str) -> usize {
+    let state = app_handle.state::<AppState>();
+    let Ok(operations) = state.operations.lock() else {
+        return 0;
+    };
+    let on_port: Vec<&ActiveOperation> = operations.iter().filter(|op| op.info.port == port).collect();
+    for op in &on_port {
+        op.control.cancel();
+    }
+    on_port.len()
+}
+
+/// Emergency stop: cancel every in-flight operation on every port and kill any
+/// flasher/esptool processes they started. Emits "all-cancelled" with the count.
+#[tauri::command]
+pub fn cancel_all_operations(app_handle: AppHandle) -> Result<usize, String> {
+    let state = app_handle.state::<AppState>();
+    let operations = state.operations.lock().map_err(|e| e.to_string())?;
+    for op in operations.iter() {
+        op.control.cancel();
+    }
+    let count = operations.len();
+    drop(operations);
+
+    log::warn!("Cancelled all operations ({})", count);
+    let _ = app_handle.emit("all-cancelled", count);
+    Ok(count)
+}
+
+/// Flasher/esptool processes allowed at once when max_processes isn't configured
+pub const DEFAULT_MAX_PROCESSES: usize = 4;
+/// How often a queued operation rechecks for cancellation while it waits for a slot
+const SLOT_POLL: Duration = Duration::from_millis(250);
+
+/// Counts the flasher/esptool processes running, so a big fixture can't spawn one per port at once
+#[derive(Default)]
+pub struct ProcessSlots {
+    in_use: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// A claimed process slot, freed when dropped
+pub struct ProcessSlot {
+    app_handle: AppHandle,
+}
+
+impl Drop for ProcessSlot {
+    fn drop(&mut self) {
+        let state = self.app_handle.state::<AppState>();
+        if let Ok(mut in_use) = state.process_slots.in_use.lock() {
+            *in_use = in_use.saturating_sub(1);
+        }
+        state.process_slots.freed.notify_one();
+    }
+}
+
+/// Claim a slot before spawning a flasher or esptool process. When every slot is taken,
+/// calls `on_queued` once and blocks until one frees or the operation is cancelled.
+pub fn acquire_process_slot(
+    app_handle: &AppHandle,
+    control: &OperationControl,
+    on_queued: impl FnOnce(),
+) -> Result<ProcessSlot, String> {
+    let limit = config::current(app_handle).max_processes.unwrap_or(DEFAULT_MAX_PROCESSES).max(1);
+    let state = app_handle.state::<AppState>();
+    let mut in_use = state.process_slots.in_use.lock().map_err(|e| e.to_string())?;
+    let mut on_queued = Some(on_queued);
+    while *in_use >= limit {
+        if let Some(on_queued) = on_queued.take() {
+            on_queued();
+        }
+        if control.is_cancelled() {
+            return Err("Cancelled while queued".to_string());
+        }
+        in_use = state
+            .process_slots
+            .freed
+            .wait_timeout(in_use, SLOT_POLL)
+            .map_err(|e| e.to_string())?
+            .0;
+    }
+    *in_use += 1;
+    Ok(ProcessSlot {
+        app_handle: app_handle.clone(),
+    })
+}
+
+/// Cap how many flasher/esptool processes run at once (None restores the default of 4)
+#[tauri::command]
+pub fn set_max_processes(app_handle: AppHandle, limit: Option<usize>) -> Result<(), String> {
+    if limit == Some(0) {
+        return Err("The process limit must be at least 1".to_string());
+    }
+    config::update(&app_handle, |c| c.max_processes = limit)?;
+    // Waiters recheck against the new limit
+    app_handle.state::<AppState>().process_slots.freed.notify_all();
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::commands::TestResult;
+use crate::config;
+
+/// What write_outbox puts in each file: the full result plus who and where produced it
+#[derive(Debug, Serialize)]
+struct OutboxFile<'a> {
+    station: String,
+    operator: Option<&'a str>,
+    app_version: String,
+    port: &'a str,
+    /// RFC 3339 UTC
+    completed_at: String,
+    result: &'a TestResult,
+}
+
+/// The station name set with set_station_name, else the computer's host name
+fn station_name(configured: Option<String>) -> String {
+    configured
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok().map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Write `contents` to `dir/name` through a temporary file and a rename, so a folder watcher
+/// never sees a partial file
+fn write_atomically(dir: &Path, name: &str, contents: &str) -> Result<PathBuf, String> {
+    let path = dir.join(name);
+    let temp_path = dir.join(format!(".{}.tmp", name));
+    fs::write(&temp_path, contents).map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, &path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        format!("Failed to move {} into place: {}", path.display(), e)
+    })?;
+    Ok(path)
+}
+
+/// When an outbox directory is configured, drop one JSON file per finished test into it,
+/// named by MAC and completion time, for an external uploader. Failures are logged rather
+/// than returned, like results::record.
+pub fn write_outbox(app_handle: &AppHandle, port: &str, operator: Option<&str>, result: &TestResult) {
+    let config = config::current(app_handle);
+    let Some(dir) = config.outbox_dir else {
+        return;
+    };
+    let now = Utc::now();
+    let mac = result
+        .mac_address
+        .as_deref()
+        .map(|mac| mac.replace([':', '-'], "").to_uppercase())
+        .unwrap_or_else(|| "UNKNOWN".to_string());
+    let name = format!("{}_{}.json", mac, now.format("%Y%m%dT%H%M%S%.3fZ"));
+    let file = OutboxFile {
+        station: station_name(config.station_name),
+        operator,
+        app_version: app_handle.package_info().version.to_string(),
+        port,
+        completed_at: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+        result,
+    };
+    let written = serde_json::to_string_pretty(&file)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| write_atomically(&dir, &name, &contents));
+    match written {
+        Ok(path) => log::info!("Wrote outbox file {}", path.display()),
+        Err(e) => log::error!("Failed to write outbox file for {}: {}", port, e),
+    }
+}
+
+/// Directory that receives one result file per finished test (None stops writing them).
+/// It is created if missing.
+#[tauri::command]
+pub fn set_outbox_dir(app_handle: AppHandle, path: Option<String>) -> Result<(), String> {
+    let dir = path.map(PathBuf::from);
+    if let Some(dir) = &dir {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    config::update(&app_handle, |c| c.outbox_dir = dir)?;
+    Ok(())
+}
+
+/// Name this station in outbox files; None uses the computer's host name
+#[tauri::command]
+pub fn set_station_name(app_handle: AppHandle, name: Option<String>) -> Result<(), String> {
+    let name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    config::update(&app_handle, |c| c.station_name = name)?;
+    Ok(())
+}
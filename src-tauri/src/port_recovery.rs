@@ -0,0 +1,117 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::AppHandle;
+
+use crate::commands::{open_serial, validate_port};
+use crate::mirror::emit_output;
+use crate::operations;
+
+/// Waits between attempts to open a locked port, about 8 seconds in all
+const REOPEN_BACKOFF_MS: [u64; 5] = [250, 500, 1000, 2000, 4000];
+
+/// Result of force_release_port
+#[derive(Debug, Serialize)]
+pub struct PortReleaseResult {
+    /// True once the port could be opened
+    pub released: bool,
+    pub message: String,
+    /// What was tried, in order
+    pub steps: Vec<String>,
+}
+
+/// Ask Windows to restart the USB-serial device behind `port` (pnputil /restart-device).
+/// Usually needs an elevated app; returns whether the restart was accepted.
+#[cfg(target_os = "windows")]
+fn restart_device(port: &str) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // The port name is interpolated into the script, so only accept COM<n>
+    let is_com = port.len() > 3
+        && port[..3].eq_ignore_ascii_case("COM")
+        && port[3..].chars().all(|c| c.is_ascii_digit());
+    if !is_com {
+        return Err(format!("{} is not a COM port", port));
+    }
+    let script = format!(
+        "$d = Get-PnpDevice -Class Ports -PresentOnly | Where-Object {{ $_.FriendlyName -like '*({})' }}; \
+         if (-not $d) {{ exit 2 }}; pnputil /restart-device $d.InstanceId | Out-Null; exit $LASTEXITCODE",
+        port.to_uppercase()
+    );
+    let status = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script.as_str()])
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()
+        .map_err(|e| format!("Failed to run PowerShell: {}", e))?;
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(2) => Err(format!("No device found for {}", port)),
+        _ => Err("pnputil could not restart the device (needs administrator rights)".to_string()),
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn restart_device(_port: &str) -> Result<(), String> {
+    Err("Device restart is only available on Windows".to_string())
+}
+
+/// Try to recover a port that every open rejects, e.g. "Access is denied" after a crashed
+/// process left it locked. Cancels this app's own operations on the port, asks Windows to
+/// restart the device, then retries the open with backoff. When `released` is false the
+/// operator should unplug and replug the device. Emits "port-output" lines.
+#[tauri::command]
+pub async fn force_release_port(app_handle: AppHandle, port: String) -> Result<PortReleaseResult, String> {
+    validate_port(&port)?;
+    let handle = app_handle.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut steps = Vec::new();
+        let mut step = |text: String| {
+            emit_output(&handle, "port-output", &port, &text);
+            steps.push(text);
+        };
+
+        let cancelled = operations::cancel_port(&handle, &port);
+        if cancelled > 0 {
+            step(format!("Cancelled {} operation(s) of this app on {}", cancelled, port));
+        }
+
+        let mut last_error = match open_serial(&port) {
+            Ok(_) => {
+                step(format!("{} opened", port));
+                return PortReleaseResult {
+                    released: true,
+                    message: format!("{} is available", port),
+                    steps,
+                };
+            }
+            Err(e) => e,
+        };
+        step(format!("{} is locked: {}", port, last_error));
+
+        match restart_device(&port) {
+            Ok(()) => step("Restarted the USB-serial device".to_string()),
+            Err(e) => step(format!("Device restart skipped: {}", e)),
+        }
+
+        for (attempt, delay_ms) in REOPEN_BACKOFF_MS.iter().enumerate() {
+            std::thread::sleep(Duration::from_millis(*delay_ms));
+            match open_serial(&port) {
+                Ok(_) => {
+                    step(format!("{} opened on retry {}", port, attempt + 1));
+                    return PortReleaseResult {
+                        released: true,
+                        message: format!("{} is available again", port),
+                        steps,
+                    };
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        step(format!("{} is still locked after {} retries: {}", port, REOPEN_BACKOFF_MS.len(), last_error));
+        PortReleaseResult {
+            released: false,
+            message: format!("{} is still locked - unplug and replug the device", port),
+            steps,
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e))
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+use crate::checklist::{EventMatcher, RequiredEvent};
+use crate::config;
+use crate::protocol::LineEnding;
+
+/// Profile used when the caller doesn't select one and none was made active with switch_profile
+pub const DEFAULT_PROFILE: &str = "full";
+/// Profile used by quick mode
+pub const QUICK_PROFILE: &str = "quick";
+
+/// A named production test configuration: which events a unit must report to pass,
+/// plus the run_device_test defaults for that product line. TestOptions set explicitly
+/// by the caller override the profile.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestProfile {
+    pub name: String,
+    pub description: String,
+    /// Event names, or "re:<regex>" to match the raw line with a regular expression
+    /// (also for fail_fast_events, forbidden_events and warn_events). An entry may instead be an object
+    /// `{"event", "display_name", "order"}` to control how the UI checklist shows it.
+    pub required_events: Vec<RequiredEvent>,
+    pub timeout_secs: Option<u64>,
+    /// Fail once the device has been silent this long
+    pub idle_timeout_secs: Option<u64>,
+    pub baud: Option<u32>,
+    /// Command that starts the test, without line ending (defaults to the protocol's)
+    pub command: Option<String>,
+    pub line_ending: Option<LineEnding>,
+    /// Events that fail the unit as soon as they appear
+    pub fail_fast_events: Vec<String>,
+    /// Events that fail the unit at any point, even after every required event has been seen
+    pub forbidden_events: Vec<String>,
+    pub warn_events: Vec<String>,
+}
+
+fn events(names: &[&str]) -> Vec<RequiredEvent> {
+    names.iter().map(|n| RequiredEvent::Definition(n.to_string())).collect()
+}
+
+/// Profiles compiled into the app
+pub fn builtin_profiles() -> Vec<TestProfile> {
+    vec![
+        TestProfile {
+            name: "full".to_string(),
+            description: "Full production test: boot, WiFi, MQTT, sensor and publish".to_string(),
+            required_events: events(&[
+                "BOOT_COMPLETE",
+                "WIFI_CONNECTED",
+                "MQTT_CONNECTED",
+                "SENSOR_OK",
+                "MQTT_PUBLISH_OK",
+            ]),
+            ..TestProfile::default()
+        },
+        TestProfile {
+            name: "local-sensor".to_string(),
+            description: "Local-only test for boards without networking: boot and sensor".to_string(),
+            required_events: events(&["BOOT_COMPLETE", "SENSOR_OK"]),
+            ..TestProfile::default()
+        },
+        TestProfile {
+            name: QUICK_PROFILE.to_string(),
+            description: "Quick incoming-inspection check: boots and the sensor responds".to_string(),
+            required_events: events(&["BOOT_COMPLETE", "SENSOR_OK"]),
+            ..TestProfile::default()
+        },
+    ]
+}
+
+fn profiles_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Could not resolve config directory: {}", e))?;
+    Ok(dir.join("test_profiles.json"))
+}
+
+fn check_definition(errors: &mut Vec<String>, at: &str, value: &Value) {
+    match value.as_str() {
+        Some(name) if !name.trim().is_empty() => {
+            if let Err(e) = EventMatcher::compile(name) {
+                errors.push(format!("{}: {}", at, e));
+            }
+        }
+        _ => errors.push(format!("{}: expected an event name", at)),
+    }
+}
+
+/// A required_events entry written as an object with event, display_name and order
+fn check_required_entry(errors: &mut Vec<String>, at: &str, fields: &serde_json::Map<String, Value>) {
+    match fields.get("event") {
+        Some(event) => check_definition(errors, &format!("{}.event", at), event),
+        None => errors.push(format!("{}.event: required", at)),
+    }
+    for (field, value) in fields {
+        match field.as_str() {
+            "event" => {}
+            "display_name" if !value.as_str().is_some_and(|n| !n.trim().is_empty()) => {
+                errors.push(format!("{}.{}: expected a non-empty string", at, field))
+            }
+            "order" if !value.as_u64().is_some_and(|v| v <= u32::MAX as u64) => {
+                errors.push(format!("{}.{}: expected a whole number", at, field))
+            }
+            "display_name" | "order" => {}
+            _ => errors.push(format!("{}.{}: unknown field", at, field)),
+        }
+    }
+}
+
+fn check_events(errors: &mut Vec<String>, at: &str, value: &Value, required: bool) {
+    match value.as_array() {
+        Some(items) if required && items.is_empty() => errors.push(format!("{}: must list at least one event", at)),
+        Some(items) => {
+            for (i, item) in items.iter().enumerate() {
+                let at = format!("{}[{}]", at, i);
+                match item.as_object() {
+                    Some(fields) if required => check_required_entry(errors, &at, fields),
+                    _ => check_definition(errors, &at, item),
+                }
+            }
+        }
+        None => errors.push(format!("{}: expected a list of event names", at)),
+    }
+}
+
+/// Check one profile object from test_profiles.json, appending an error per bad field
+fn validate_profile(index: usize, profile: &Value, errors: &mut Vec<String>) {
+    let Some(fields) = profile.as_object() else {
+        errors.push(format!("profile {}: expected an object", index));
+        return;
+    };
+    let name = fields.get("name").and_then(Value::as_str).unwrap_or_default();
+    let at = |field: &str| {
+        if name.is_empty() {
+            format!("profile {}.{}", index, field)
+        } else {
+            format!("{}.{}", name, field)
+        }
+    };
+
+    if name.trim().is_empty() {
+        errors.push(at("name") + ": required");
+    }
+    if !fields.contains_key("required_events") {
+        errors.push(at("required_events") + ": required");
+    }
+    for (field, value) in fields {
+        match field.as_str() {
+            // null leaves an optional setting unset, as in an exported profile
+            "timeout_secs" | "idle_timeout_secs" | "baud" | "command" | "line_ending" if value.is_null() => {}
+            "name" | "description" if !value.is_string() => errors.push(at(field) + ": expected a string"),
+            "name" | "description" => {}
+            "required_events" => check_events(errors, &at(field), value, true),
+            "fail_fast_events" | "forbidden_events" | "warn_events" => check_events(errors, &at(field), value, false),
+            "timeout_secs" | "idle_timeout_secs" if !value.as_u64().is_some_and(|v| v > 0) => {
+                errors.push(at(field) + ": expected a whole number of seconds above 0")
+            }
+            "timeout_secs" | "idle_timeout_secs" => {}
+            "baud" if !value.as_u64().is_some_and(|v| v > 0 && v <= u32::MAX as u64) => {
+                errors.push(at(field) + ": expected a baud rate such as 115200")
+            }
+            "baud" => {}
+            "command" if !value.as_str().is_some_and(|c| !c.trim().is_empty()) => {
+                errors.push(at(field) + ": expected a non-empty string")
+            }
+            "command" => {}
+            "line_ending" if serde_json::from_value::<LineEnding>(value.clone()).is_err() => {
+                errors.push(at(field) + ": expected \"crlf\", \"lf\", \"cr\" or \"none\"")
+            }
+            "line_ending" => {}
+            _ => errors.push(at(field) + ": unknown field"),
+        }
+    }
+}
+
+/// Profiles from test_profiles.json in the app config directory (a JSON array of profiles).
+/// Returns an empty list when the file doesn't exist, and every field error when it's invalid.
+pub fn load_file_profiles(app_handle: &AppHandle) -> Result<Vec<TestProfile>, String> {
+    let path = profiles_path(app_handle)?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let invalid = |detail: String| format!("Invalid {}: {}", path.display(), detail);
+    let entries: Vec<Value> = serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+    parse_profiles(entries).map_err(invalid)
+}
+
+/// Validate profile objects as found in test_profiles.json, returning every field error at once
+pub fn parse_profiles(entries: Vec<Value>) -> Result<Vec<TestProfile>, String> {
+    let mut errors = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        validate_profile(index, entry, &mut errors);
+    }
+    let mut names: Vec<&str> = entries.iter().filter_map(|e| e["name"].as_str()).collect();
+    names.sort_unstable();
+    for pair in names.windows(2).filter(|pair| pair[0] == pair[1]) {
+        errors.push(format!("{}: defined more than once", pair[0]));
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+
+    entries
+        .into_iter()
+        .map(|entry| serde_json::from_value(entry).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Replace test_profiles.json with `profiles`
+pub fn save_file_profiles(app_handle: &AppHandle, profiles: &[TestProfile]) -> Result<(), String> {
+    let path = profiles_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(profiles).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
+
+/// Built-in profiles plus those from test_profiles.json; a file profile replaces a built-in of the same name
+pub fn load_profiles(app_handle: &AppHandle) -> Result<Vec<TestProfile>, String> {
+    let mut profiles = builtin_profiles();
+    for profile in load_file_profiles(app_handle)? {
+        match profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+    }
+    Ok(profiles)
+}
+
+/// The profile tests use when they don't name one
+pub fn active_profile(app_handle: &AppHandle) -> String {
+    config::current(app_handle)
+        .active_profile
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Look up a profile by name, falling back to the active profile when none is given
+pub fn resolve_profile(app_handle: &AppHandle, name: Option<&str>) -> Result<TestProfile, String> {
+    let active = active_profile(app_handle);
+    let name = name.unwrap_or(&active);
+    load_profiles(app_handle)?
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| format!("Unknown test profile '{}'", name))
+}
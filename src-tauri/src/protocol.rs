@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+/// Default JSON key naming the event in json-protocol output
+pub const DEFAULT_JSON_EVENT_FIELD: &str = "event";
+
+/// Wire format spoken by the device firmware
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// `TEST` command and `[EVENT:NAME key=value]` lines
+    #[default]
+    Text,
+    /// Line-delimited JSON: `{"cmd":"test"}` and `{"event":"NAME", ...}`
+    Json,
+}
+
+impl Protocol {
+    /// Command that starts the production test, without line ending
+    pub fn test_command(self) -> &'static str {
+        match self {
+            Protocol::Text => "TEST",
+            Protocol::Json => "{\"cmd\":\"test\"}",
+        }
+    }
+}
+
+/// Terminator appended to commands sent to the device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Crlf,
+    Lf,
+    Cr,
+    None,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf => "\n",
+            LineEnding::Cr => "\r",
+            LineEnding::None => "",
+        }
+    }
+}
+
+/// Parameter name the text protocol uses for a JSON key, so firmware and MAC
+/// are picked up the same way for both protocols
+fn param_name(key: &str) -> String {
+    match key.to_ascii_lowercase().as_str() {
+        "firmware" | "firmware_version" | "fw" => "FIRMWARE".to_string(),
+        "mac" | "mac_address" => "MAC".to_string(),
+        _ => key.to_string(),
+    }
+}
+
+/// Rewrite a JSON event line as the equivalent `[EVENT:NAME key=value ...]` text line.
+/// Returns None for lines that aren't JSON objects or carry no `event_field`.
+/// Scalar fields become parameters; nested objects, arrays and nulls are dropped.
+pub fn json_to_event_line(line: &str, event_field: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    let object = value.as_object()?;
+    let event = object.get(event_field)?.as_str()?;
+
+    let mut text = format!("[EVENT:{}", event);
+    for (key, value) in object {
+        if key == event_field {
+            continue;
+        }
+        let value = match value {
+            serde_json::Value::String(s) => s.split_whitespace().collect::<Vec<_>>().join("_"),
+            serde_json::Value::Number(n) => n.to_string(),
+            serde_json::Value::Bool(b) => b.to_string(),
+            _ => continue,
+        };
+        text.push_str(&format!(" {}={}", param_name(key), value));
+    }
+    text.push(']');
+    Some(text)
+}
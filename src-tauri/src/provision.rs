@@ -0,0 +1,252 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::{extract_param, idle_backoff, open_serial_with_retry, validate_port, wait_for_marker, DevicePort};
+use crate::config;
+use crate::mirror::emit_output;
+use crate::operations;
+use crate::results;
+use crate::state::AppState;
+
+/// Serial number format used when no pattern has been configured
+pub const DEFAULT_SERIAL_PATTERN: &str = r"^[A-Za-z0-9-]{4,32}$";
+/// How long the device has to acknowledge SET_SERIAL, SET_TIME or GET_TIME
+const PROVISION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest difference between the time set and the time read back that still counts as set
+const MAX_CLOCK_DRIFT_SECS: i64 = 3;
+
+/// Payload of the "serial-scanned" event
+#[derive(Debug, Clone, Serialize)]
+struct SerialScanned<'a> {
+    serial: &'a str,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProvisionResult {
+    pub success: bool,
+    pub message: String,
+    pub serial: String,
+}
+
+fn serial_regex(app_handle: &AppHandle) -> Result<Regex, String> {
+    let pattern = config::current(app_handle)
+        .serial_pattern
+        .unwrap_or_else(|| DEFAULT_SERIAL_PATTERN.to_string());
+    Regex::new(&pattern).map_err(|e| format!("Invalid serial pattern '{}': {}", pattern, e))
+}
+
+/// Set the regex scanned serial numbers must match; None restores DEFAULT_SERIAL_PATTERN
+#[tauri::command]
+pub fn set_serial_pattern(app_handle: AppHandle, pattern: Option<String>) -> Result<(), String> {
+    if let Some(pattern) = &pattern {
+        Regex::new(pattern).map_err(|e| format!("Invalid serial pattern '{}': {}", pattern, e))?;
+    }
+    config::update(&app_handle, |c| c.serial_pattern = pattern)?;
+    Ok(())
+}
+
+/// Accept a serial number from a barcode scanner: validate it against the configured pattern
+/// and hold it as the pending serial for the next provision_device. Emits "serial-scanned".
+#[tauri::command]
+pub fn accept_scanned_serial(app_handle: AppHandle, serial: String) -> Result<String, String> {
+    // Scanners in keyboard mode usually append Enter
+    let serial = serial.trim().to_string();
+    if !serial_regex(&app_handle)?.is_match(&serial) {
+        return Err(format!("Scanned serial '{}' does not match the expected format", serial));
+    }
+
+    let state = app_handle.state::<AppState>();
+    *state.pending_serial.lock().map_err(|e| e.to_string())? = Some(serial.clone());
+    log::info!("Scanned serial {}", serial);
+    let _ = app_handle.emit("serial-scanned", SerialScanned { serial: &serial });
+    Ok(serial)
+}
+
+fn send_command(serial: &mut DevicePort, command: &str) -> Result<(), String> {
+    let name = command.split_whitespace().next().unwrap_or(command);
+    serial
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .map_err(|e| format!("Failed to send {} command: {}", name, e))?;
+    serial.flush().map_err(|e| format!("Failed to flush: {}", e))
+}
+
+/// Write a serial number to the device with SET_SERIAL and wait for its SERIAL_SET event.
+/// Without `serial`, the pending serial from accept_scanned_serial is used and cleared on success.
+/// Emits "provision-output" events for real-time progress
+#[tauri::command]
+pub async fn provision_device(app_handle: AppHandle, port: String, serial: Option<String>) -> Result<ProvisionResult, String> {
+    validate_port(&port)?;
+    let state = app_handle.state::<AppState>();
+    let from_scan = serial.is_none();
+    let serial = match serial {
+        Some(serial) => serial.trim().to_string(),
+        None => state
+            .pending_serial
+            .lock()
+            .map_err(|e| e.to_string())?
+            .clone()
+            .ok_or("No serial number given and none has been scanned")?,
+    };
+    if !serial_regex(&app_handle)?.is_match(&serial) {
+        return Err(format!("Serial '{}' does not match the expected format", serial));
+    }
+
+    let mut operation = operations::begin(&app_handle, "provision", &port);
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut serial_port = open_serial_with_retry(&handle, "provision-output", &port)?;
+        let mut reader = BufReader::new(serial_port.try_clone().map_err(|e| e.to_string())?);
+
+        send_command(&mut serial_port, &format!("SET_SERIAL {}", serial))?;
+        emit_output(&handle, "provision-output", &port, &format!("Sent SET_SERIAL {}", serial));
+
+        let mut stored = None;
+        let on_line = |line: &str| {
+            if line.is_empty() {
+                return;
+            }
+            emit_output(&handle, "provision-output", &port, line);
+            if line.contains("[EVENT:SERIAL_SET") {
+                stored = extract_param(line, "SERIAL").map(|s| s.trim_end_matches(']').to_string());
+            }
+        };
+        let acknowledged = wait_for_marker(&mut reader, "[EVENT:SERIAL_SET", PROVISION_TIMEOUT, on_line)?;
+        if control.is_cancelled() {
+            return Err("Provisioning cancelled".to_string());
+        }
+
+        // Firmware that echoes the stored value must echo what was sent
+        let (success, message) = match stored {
+            _ if !acknowledged => (false, "Device did not acknowledge SET_SERIAL".to_string()),
+            Some(stored) if stored != serial => (false, format!("Device stored serial '{}' instead of '{}'", stored, serial)),
+            _ => (true, format!("Serial {} written", serial)),
+        };
+        let check = if success { "✓" } else { "✗" };
+        emit_output(&handle, "provision-output", &port, &format!("[CHECK] {} {}", check, message));
+
+        let provisioned = ProvisionResult { success, message, serial };
+        results::record(&handle, "provision", &port, None, provisioned.success, &provisioned.message, &provisioned);
+        Ok(provisioned)
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |r| r.success);
+    // Only clear the scan that was used, in case the next unit was scanned meanwhile
+    if let (true, Ok(provisioned)) = (from_scan, &result) {
+        if let Ok(mut pending) = state.pending_serial.lock() {
+            if provisioned.success && pending.as_deref() == Some(provisioned.serial.as_str()) {
+                *pending = None;
+            }
+        }
+    }
+    result
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeSetResult {
+    pub success: bool,
+    pub message: String,
+    /// Unix time sent to the device
+    pub epoch_secs: u64,
+    /// Unix time the device reported back, when verified
+    pub device_epoch_secs: Option<u64>,
+}
+
+/// Device time from a GET_TIME reply: "TIME=1700000000" anywhere in the line, or a bare number
+fn parse_device_time(line: &str) -> Option<u64> {
+    extract_param(line, "TIME")
+        .map(|t| t.trim_end_matches(']').to_string())
+        .unwrap_or_else(|| line.trim().to_string())
+        .parse()
+        .ok()
+}
+
+/// Set the device clock with SET_TIME <epoch> and wait for TIME_SET_OK. `epoch_secs` defaults to
+/// the host's current time. With `verify`, the time is read back with GET_TIME and must be within
+/// a few seconds of what was set. Emits "provision-output" events for real-time progress
+#[tauri::command]
+pub async fn set_device_time(
+    app_handle: AppHandle,
+    port: String,
+    epoch_secs: Option<u64>,
+    verify: Option<bool>,
+) -> Result<TimeSetResult, String> {
+    validate_port(&port)?;
+    let mut operation = operations::begin(&app_handle, "provision", &port);
+    let handle = app_handle.clone();
+    let control = operation.control();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut serial_port = open_serial_with_retry(&handle, "provision-output", &port)?;
+        let mut reader = BufReader::new(serial_port.try_clone().map_err(|e| e.to_string())?);
+        let emit = |line: &str| if !line.is_empty() { emit_output(&handle, "provision-output", &port, line) };
+
+        let epoch_secs = match epoch_secs {
+            Some(epoch) => epoch,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| format!("System clock error: {}", e))?
+                .as_secs(),
+        };
+        send_command(&mut serial_port, &format!("SET_TIME {}", epoch_secs))?;
+        emit(&format!("Sent SET_TIME {}", epoch_secs));
+        let acknowledged = wait_for_marker(&mut reader, "TIME_SET_OK", PROVISION_TIMEOUT, emit)?;
+        if control.is_cancelled() {
+            return Err("Setting device time cancelled".to_string());
+        }
+        if !acknowledged {
+            emit("[CHECK] ✗ Device did not acknowledge SET_TIME");
+            return Ok(TimeSetResult {
+                success: false,
+                message: "Device did not acknowledge SET_TIME".to_string(),
+                epoch_secs,
+                device_epoch_secs: None,
+            });
+        }
+        emit("[CHECK] ✓ Device time set");
+        if !verify.unwrap_or(false) {
+            return Ok(TimeSetResult {
+                success: true,
+                message: format!("Device time set to {}", epoch_secs),
+                epoch_secs,
+                device_epoch_secs: None,
+            });
+        }
+
+        send_command(&mut serial_port, "GET_TIME")?;
+        let start = Instant::now();
+        let mut device_epoch_secs = None;
+        let mut line = String::new();
+        while device_epoch_secs.is_none() && start.elapsed() < PROVISION_TIMEOUT {
+            match reader.read_line(&mut line) {
+                Ok(0) => idle_backoff(),
+                Ok(_) => {
+                    emit(line.trim());
+                    device_epoch_secs = parse_device_time(&line);
+                    line.clear();
+                }
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(format!("Read error: {}", e)),
+            }
+        }
+        let (success, message) = match device_epoch_secs {
+            None => (false, "Device did not report its time".to_string()),
+            Some(device) if (device as i64 - epoch_secs as i64).abs() > MAX_CLOCK_DRIFT_SECS => {
+                (false, format!("Device time {} differs from {} set", device, epoch_secs))
+            }
+            Some(_) => (true, format!("Device time set to {} and verified", epoch_secs)),
+        };
+        emit(&format!("[CHECK] {} {}", if success { "✓" } else { "✗" }, message));
+        Ok(TimeSetResult {
+            success,
+            message,
+            epoch_secs,
+            device_epoch_secs,
+        })
+    }).await.map_err(|e| format!("Task join error: {}", e)).and_then(|r| r);
+
+    operation.finish(&result, |r| r.success);
+    result
+}
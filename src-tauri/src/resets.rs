@@ -0,0 +1,37 @@
+/// Reset reasons the ESP32 ROM reports after a normal power-up or requested restart
+const EXPECTED_RESETS: [&str; 2] = ["POWERON_RESET", "SW_RESET"];
+
+/// A reset or power event reported by the ESP32 ROM bootloader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResetEvent {
+    /// Reason name, e.g. "RTCWDT_RTC_RESET", or "BROWNOUT" for the brownout detector
+    pub reason: String,
+    pub power_fault: bool,
+}
+
+impl ResetEvent {
+    /// True for resets that don't happen on a healthy unit
+    pub fn is_abnormal(&self) -> bool {
+        !EXPECTED_RESETS.contains(&self.reason.as_str())
+    }
+}
+
+/// Recognise ROM reset output: "Brownout detector was triggered" or
+/// "rst:0x10 (RTCWDT_RTC_RESET),boot:0x13 (SPI_FAST_FLASH_BOOT)"
+pub fn parse_reset_line(line: &str) -> Option<ResetEvent> {
+    if line.contains("Brownout detector was triggered") {
+        return Some(ResetEvent {
+            reason: "BROWNOUT".to_string(),
+            power_fault: true,
+        });
+    }
+
+    let rest = &line[line.find("rst:0x")?..];
+    let start = rest.find('(')? + 1;
+    let end = start + rest[start..].find(')')?;
+    let reason = rest[start..end].trim().to_string();
+    Some(ResetEvent {
+        power_fault: reason.contains("BROWN_OUT"),
+        reason,
+    })
+}
@@ -0,0 +1,912 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, OpenOptions};
+use chrono::DateTime;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::checklist::{check_label, event_names};
+use crate::commands::EventTime;
+use crate::session_stats;
+use crate::state::AppState;
+
+/// Current Unix time in milliseconds
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// One stored operation outcome. `data` holds the full command result
+/// (TestResult, FlashResult, ...), including the raw device log where there is one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultRecord {
+    pub id: String,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    /// "test", "flash", ...
+    pub kind: String,
+    pub port: String,
+    pub mac: Option<String>,
+    pub success: bool,
+    pub message: String,
+    pub data: serde_json::Value,
+    /// Fields set after the fact with update_unit_metadata (serial, batch, rework note, ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// One entry per update_unit_metadata call, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audit: Vec<AuditEntry>,
+}
+
+/// A metadata change made to a stored record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    pub operator: Option<String>,
+    /// The fields as given in that update
+    pub fields: BTreeMap<String, String>,
+}
+
+fn store_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not resolve data directory: {}", e))?;
+    Ok(dir.join("results.jsonl"))
+}
+
+/// Append a result to the store. Failures are logged rather than returned so that
+/// a full disk never turns a passing unit into an error.
+pub fn record(
+    app_handle: &AppHandle,
+    kind: &str,
+    port: &str,
+    mac: Option<&str>,
+    success: bool,
+    message: &str,
+    data: &impl Serialize,
+) {
+    store(app_handle, kind, port, mac, success, message, data);
+    session_stats::count(app_handle, kind, success);
+}
+
+/// Append a record to the store like record, without counting it towards the session
+pub fn store(
+    app_handle: &AppHandle,
+    kind: &str,
+    port: &str,
+    mac: Option<&str>,
+    success: bool,
+    message: &str,
+    data: &impl Serialize,
+) {
+    if let Err(e) = try_record(app_handle, kind, port, mac, success, message, data) {
+        log::error!("Failed to store {} result for {}: {}", kind, port, e);
+    }
+}
+
+fn try_record(
+    app_handle: &AppHandle,
+    kind: &str,
+    port: &str,
+    mac: Option<&str>,
+    success: bool,
+    message: &str,
+    data: &impl Serialize,
+) -> Result<ResultRecord, String> {
+    let state = app_handle.state::<AppState>();
+    let timestamp = now_millis();
+    let sequence = state.next_result_id.fetch_add(1, Ordering::SeqCst);
+    let record = ResultRecord {
+        id: format!("{}-{}", timestamp, sequence),
+        timestamp,
+        kind: kind.to_string(),
+        port: port.to_string(),
+        mac: mac.map(|m| m.to_uppercase()),
+        success,
+        message: message.to_string(),
+        data: serde_json::to_value(data).map_err(|e| e.to_string())?,
+        metadata: BTreeMap::new(),
+        audit: Vec::new(),
+    };
+    let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+
+    let path = store_path(app_handle)?;
+    let _guard = state.results_lock.lock().map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+    Ok(record)
+}
+
+/// Open the results store for appending without writing anything, to prove it can be
+/// recorded to. Returns its path.
+pub fn check_store_writable(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let state = app_handle.state::<AppState>();
+    let path = store_path(app_handle)?;
+    let _guard = state.results_lock.lock().map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Raw lines of the store; callers must hold results_lock
+fn read_store_lines(path: &Path) -> Result<Vec<String>, String> {
+    match fs::File::open(path) {
+        Ok(file) => Ok(BufReader::new(file).lines().map_while(Result::ok).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to open results store: {}", e)),
+    }
+}
+
+/// Every stored record, oldest first. Unparseable lines are skipped.
+pub fn load_records(app_handle: &AppHandle) -> Result<Vec<ResultRecord>, String> {
+    let path = store_path(app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.results_lock.lock().map_err(|e| e.to_string())?;
+    let mut records: Vec<ResultRecord> = read_store_lines(&path)?
+        .iter()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    records.sort_by_key(|r| r.timestamp);
+    Ok(records)
+}
+
+/// Summary of what's in the results store
+#[derive(Debug, Serialize)]
+pub struct ResultCounts {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// Unix ms of the oldest and newest records
+    pub oldest: Option<u64>,
+    pub newest: Option<u64>,
+}
+
+/// Count the records in the results store
+#[tauri::command]
+pub fn count_results(app_handle: AppHandle) -> Result<ResultCounts, String> {
+    let records = load_records(&app_handle)?;
+    let passed = records.iter().filter(|r| r.success).count();
+    Ok(ResultCounts {
+        total: records.len(),
+        passed,
+        failed: records.len() - passed,
+        oldest: records.first().map(|r| r.timestamp),
+        newest: records.last().map(|r| r.timestamp),
+    })
+}
+
+/// Remove every record older than `before` (Unix ms) from the store, first passing the removed
+/// lines to `take`. Holds results_lock throughout so in-flight tests can't append mid-rewrite.
+/// Unparseable lines are kept. Returns the number of records removed.
+fn remove_older_than(
+    app_handle: &AppHandle,
+    before: u64,
+    take: impl FnOnce(&[String]) -> Result<(), String>,
+) -> Result<usize, String> {
+    let path = store_path(app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.results_lock.lock().map_err(|e| e.to_string())?;
+
+    let (removed, kept): (Vec<String>, Vec<String>) = read_store_lines(&path)?.into_iter().partition(|line| {
+        serde_json::from_str::<ResultRecord>(line).is_ok_and(|r| r.timestamp < before)
+    });
+    if removed.is_empty() {
+        return Ok(0);
+    }
+    take(&removed)?;
+    replace_store(&path, &kept)?;
+    Ok(removed.len())
+}
+
+/// Rewrite the store with `lines`; callers must hold results_lock. The lines are written beside
+/// the store and swapped in, so a crash never leaves it half written.
+fn replace_store(path: &Path, lines: &[String]) -> Result<(), String> {
+    let temp_path = path.with_extension("jsonl.tmp");
+    let mut contents = lines.join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    fs::write(&temp_path, contents).map_err(|e| format!("Failed to write results store: {}", e))?;
+    fs::rename(&temp_path, path).map_err(|e| format!("Failed to replace results store: {}", e))
+}
+
+/// Delete stored results older than `before` (Unix ms); returns how many were deleted
+#[tauri::command]
+pub fn prune_results(app_handle: AppHandle, before: u64) -> Result<usize, String> {
+    let removed = remove_older_than(&app_handle, before, |_| Ok(()))?;
+    log::info!("Pruned {} results older than {}", removed, before);
+    Ok(removed)
+}
+
+/// Move stored results older than `before` (Unix ms) into a zip at `path` holding results.jsonl.
+/// `path` must not exist yet. The store is only rewritten once the zip has been written.
+/// Returns how many were archived.
+#[tauri::command]
+pub fn archive_results(app_handle: AppHandle, path: String, before: u64) -> Result<usize, String> {
+    let archived = remove_older_than(&app_handle, before, |lines| {
+        let mut contents = lines.join("\n");
+        contents.push('\n');
+        write_zip(Path::new(&path), "results.jsonl", contents.as_bytes())
+    })?;
+    log::info!("Archived {} results older than {} to {}", archived, before, path);
+    Ok(archived)
+}
+
+/// Write a zip archive holding a single deflated file
+fn write_zip(path: &Path, name: &str, contents: &[u8]) -> Result<(), String> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(contents).map_err(|e| e.to_string())?;
+    let compressed = encoder.finish().map_err(|e| e.to_string())?;
+    let mut crc = Crc::new();
+    crc.update(contents);
+
+    let too_large = || format!("Archive too large ({} bytes)", contents.len());
+    let size = u32::try_from(contents.len()).map_err(|_| too_large())?;
+    let compressed_size = u32::try_from(compressed.len()).map_err(|_| too_large())?;
+    let name_len = name.len() as u16;
+    // DOS date for 1980-01-01; the records carry their own timestamps
+    let (dos_time, dos_date) = (0u16, 0x21u16);
+
+    let mut entry = Vec::new();
+    for field in [20u16, 0, 8, dos_time, dos_date] {
+        entry.extend_from_slice(&field.to_le_bytes());
+    }
+    for field in [crc.sum(), compressed_size, size] {
+        entry.extend_from_slice(&field.to_le_bytes());
+    }
+
+    let mut zip = Vec::with_capacity(compressed.len() + 2 * name.len() + 100);
+    // Local file header
+    zip.extend_from_slice(&0x04034b50u32.to_le_bytes());
+    zip.extend_from_slice(&entry);
+    zip.extend_from_slice(&name_len.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+    zip.extend_from_slice(name.as_bytes());
+    zip.extend_from_slice(&compressed);
+
+    // Central directory
+    let directory_offset = zip.len() as u32;
+    zip.extend_from_slice(&0x02014b50u32.to_le_bytes());
+    zip.extend_from_slice(&20u16.to_le_bytes());
+    zip.extend_from_slice(&entry);
+    for field in [name_len, 0, 0, 0, 0] {
+        zip.extend_from_slice(&field.to_le_bytes());
+    }
+    zip.extend_from_slice(&0u32.to_le_bytes());
+    zip.extend_from_slice(&0u32.to_le_bytes());
+    zip.extend_from_slice(name.as_bytes());
+    let directory_size = zip.len() as u32 - directory_offset;
+
+    // End of central directory
+    zip.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    for field in [0u16, 0, 1, 1] {
+        zip.extend_from_slice(&field.to_le_bytes());
+    }
+    zip.extend_from_slice(&directory_size.to_le_bytes());
+    zip.extend_from_slice(&directory_offset.to_le_bytes());
+    zip.extend_from_slice(&0u16.to_le_bytes());
+
+    // Never replace an earlier archive: its records are already gone from the store
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => format!("Archive {} already exists", path.display()),
+        _ => format!("Failed to create archive {}: {}", path.display(), e),
+    })?;
+    file.write_all(&zip).and_then(|_| file.sync_all()).map_err(|e| {
+        let _ = fs::remove_file(path);
+        format!("Failed to write archive {}: {}", path.display(), e)
+    })
+}
+
+/// Everything known about one unit, as written by export_unit_report
+#[derive(Debug, Serialize)]
+struct UnitReport {
+    unit: String,
+    generated_at: u64,
+    records: Vec<ResultRecord>,
+}
+
+/// Write every stored record for a unit (matched by MAC or port name) to one JSON file,
+/// ordered by time. Returns the path written.
+#[tauri::command]
+pub fn export_unit_report(app_handle: AppHandle, mac_or_port: String, path: String) -> Result<String, String> {
+    let key = mac_or_port.trim();
+    let records: Vec<ResultRecord> = load_records(&app_handle)?
+        .into_iter()
+        .filter(|r| r.port == key || r.mac.as_deref().is_some_and(|m| m.eq_ignore_ascii_case(key)))
+        .collect();
+    if records.is_empty() {
+        return Err(format!("No stored results for '{}'", key));
+    }
+
+    let report = UnitReport {
+        unit: key.to_string(),
+        generated_at: now_millis(),
+        records,
+    };
+    let contents = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| format!("Failed to write report {}: {}", path, e))?;
+    Ok(path)
+}
+
+/// A unit's final result rendered as a text certificate, as returned by finalize_unit
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitCertificate {
+    pub mac: String,
+    pub passed: bool,
+    /// Id of the stored test result the certificate was built from
+    pub record_id: String,
+    pub text: String,
+    /// Where the certificate was written, when a path was given
+    pub path: Option<String>,
+}
+
+fn render_certificate(record: &ResultRecord, mac: &str, operator: Option<&str>) -> String {
+    let tested = DateTime::from_timestamp_millis(record.timestamp as i64)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| record.timestamp.to_string());
+    let field = |name: &str| record.data[name].as_str().unwrap_or("unknown").to_string();
+    let rows = [
+        ("Unit", mac.to_string()),
+        ("Result", if record.success { "PASS" } else { "FAIL" }.to_string()),
+        ("Message", record.message.clone()),
+        ("Tested", tested),
+        ("Firmware", field("firmware_version")),
+        ("Port", record.port.clone()),
+        ("Operator", operator.unwrap_or("unknown").to_string()),
+        ("Record", record.id.clone()),
+    ];
+
+    let mut text = String::from("Production Test Certificate\n===========================\n");
+    for (label, value) in rows {
+        text.push_str(&format!("{:<10}{}\n", format!("{}:", label), value));
+    }
+    text
+}
+
+/// Render the latest stored test result for a unit as a text certificate, optionally writing it
+/// to `path`. Emits "unit-finalized" with the certificate so the UI can also capture its result panel.
+#[tauri::command]
+pub fn finalize_unit(
+    app_handle: AppHandle,
+    mac: String,
+    operator: Option<String>,
+    path: Option<String>,
+) -> Result<UnitCertificate, String> {
+    let mac = mac.trim().to_uppercase();
+    let record = load_records(&app_handle)?
+        .into_iter()
+        .rev()
+        .find(|r| r.kind == "test" && r.mac.as_deref() == Some(mac.as_str()))
+        .ok_or_else(|| format!("No stored test result for {}", mac))?;
+
+    let text = render_certificate(&record, &mac, operator.as_deref());
+    if let Some(path) = &path {
+        fs::write(path, &text).map_err(|e| format!("Failed to write certificate {}: {}", path, e))?;
+    }
+
+    let certificate = UnitCertificate {
+        mac,
+        passed: record.success,
+        record_id: record.id,
+        text,
+        path,
+    };
+    let _ = app_handle.emit("unit-finalized", &certificate);
+    Ok(certificate)
+}
+
+/// Merge `fields` into the metadata of the latest stored record for a unit, e.g. after it was
+/// re-serialized, moved to another batch or reworked, and append an audit entry. Returns the
+/// updated record.
+#[tauri::command]
+pub fn update_unit_metadata(
+    app_handle: AppHandle,
+    mac: String,
+    fields: HashMap<String, String>,
+    operator: Option<String>,
+) -> Result<ResultRecord, String> {
+    let mac = mac.trim().to_uppercase();
+    if fields.is_empty() {
+        return Err("No metadata fields given".to_string());
+    }
+    let fields: BTreeMap<String, String> = fields.into_iter().collect();
+
+    let path = store_path(&app_handle)?;
+    let state = app_handle.state::<AppState>();
+    let _guard = state.results_lock.lock().map_err(|e| e.to_string())?;
+    let mut lines = read_store_lines(&path)?;
+
+    // Latest by timestamp; ties go to the line written last
+    let (index, mut record) = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| Some((i, serde_json::from_str::<ResultRecord>(line).ok()?)))
+        .filter(|(_, r)| r.mac.as_deref() == Some(mac.as_str()))
+        .max_by_key(|(i, r)| (r.timestamp, *i))
+        .ok_or_else(|| format!("No stored results for {}", mac))?;
+
+    record.metadata.extend(fields.clone());
+    record.audit.push(AuditEntry {
+        timestamp: now_millis(),
+        operator,
+        fields,
+    });
+    lines[index] = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+    replace_store(&path, &lines)?;
+    log::info!("Updated metadata of result {} for {}", record.id, mac);
+    Ok(record)
+}
+
+/// A top-level result field whose value differs between two runs
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub a: Value,
+    pub b: Value,
+}
+
+/// An event check that passed in one run and not in the other
+#[derive(Debug, Serialize)]
+pub struct CheckFlip {
+    pub event: String,
+    pub label: String,
+    pub passed_a: bool,
+    pub passed_b: bool,
+}
+
+/// Differences between two stored test runs
+#[derive(Debug, Serialize)]
+pub struct TestDiff {
+    pub a: ResultRecord,
+    pub b: ResultRecord,
+    /// success, message, firmware_version, mac_address, reset_reason where they differ
+    pub changed_fields: Vec<FieldChange>,
+    pub flipped_checks: Vec<CheckFlip>,
+    /// Sensor readings that differ, as [a, b] (null where a run has no reading)
+    pub sensor_changes: BTreeMap<String, [Option<f64>; 2]>,
+    pub warnings_only_in_a: Vec<String>,
+    pub warnings_only_in_b: Vec<String>,
+}
+
+fn find_test_record(records: &[ResultRecord], id: &str) -> Result<ResultRecord, String> {
+    let record = records
+        .iter()
+        .find(|r| r.id == id)
+        .ok_or_else(|| format!("No stored result with id '{}'", id))?;
+    if record.kind != "test" {
+        return Err(format!("Result '{}' is a {} result, not a test", id, record.kind));
+    }
+    Ok(record.clone())
+}
+
+/// Event names seen anywhere in a stored TestResult's event log
+fn seen_events(data: &Value) -> BTreeSet<String> {
+    data["events"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .flat_map(event_names)
+        .collect()
+}
+
+fn string_list(data: &Value, field: &str) -> BTreeSet<String> {
+    data[field]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Compare two stored test runs by result id
+#[tauri::command]
+pub fn diff_test_results(app_handle: AppHandle, id_a: String, id_b: String) -> Result<TestDiff, String> {
+    let records = load_records(&app_handle)?;
+    let a = find_test_record(&records, &id_a)?;
+    let b = find_test_record(&records, &id_b)?;
+    Ok(diff_records(a, b))
+}
+
+fn diff_records(a: ResultRecord, b: ResultRecord) -> TestDiff {
+    let changed_fields = ["success", "message", "firmware_version", "mac_address", "reset_reason"]
+        .iter()
+        .filter(|field| a.data[**field] != b.data[**field])
+        .map(|field| FieldChange {
+            field: field.to_string(),
+            a: a.data[*field].clone(),
+            b: b.data[*field].clone(),
+        })
+        .collect();
+
+    let (events_a, events_b) = (seen_events(&a.data), seen_events(&b.data));
+    let flipped_checks = events_a
+        .symmetric_difference(&events_b)
+        .map(|event| CheckFlip {
+            event: event.clone(),
+            label: check_label(event),
+            passed_a: events_a.contains(event),
+            passed_b: events_b.contains(event),
+        })
+        .collect();
+
+    let reading = |data: &Value, name: &str| data["sensor_readings"][name].as_f64();
+    let sensor_names: BTreeSet<String> = [&a.data, &b.data]
+        .iter()
+        .filter_map(|data| data["sensor_readings"].as_object())
+        .flat_map(|readings| readings.keys().cloned())
+        .collect();
+    let sensor_changes = sensor_names
+        .into_iter()
+        .filter_map(|name| {
+            let pair = [reading(&a.data, &name), reading(&b.data, &name)];
+            (pair[0] != pair[1]).then_some((name, pair))
+        })
+        .collect();
+
+    let (warnings_a, warnings_b) = (string_list(&a.data, "warnings"), string_list(&b.data, "warnings"));
+    TestDiff {
+        changed_fields,
+        flipped_checks,
+        sensor_changes,
+        warnings_only_in_a: warnings_a.difference(&warnings_b).cloned().collect(),
+        warnings_only_in_b: warnings_b.difference(&warnings_a).cloned().collect(),
+        a,
+        b,
+    }
+}
+
+/// One bar of the event waterfall
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    pub event_name: String,
+    /// Milliseconds since the test command was sent
+    pub t_ms: u64,
+    pub delta_from_prev_ms: u64,
+}
+
+/// Each event of a stored test run with its time since the test command and since the
+/// previous event, for a waterfall chart of where a run spent its time
+#[tauri::command]
+pub fn get_event_timeline(app_handle: AppHandle, result_id: String) -> Result<Vec<TimelineEntry>, String> {
+    let records = load_records(&app_handle)?;
+    let record = find_test_record(&records, &result_id)?;
+    event_timeline(&record)
+}
+
+fn event_timeline(record: &ResultRecord) -> Result<Vec<TimelineEntry>, String> {
+    let result_id = &record.id;
+    if record.data.get("event_times").is_none() {
+        return Err(format!("Result '{}' was recorded without event timestamps", result_id));
+    }
+    let mut times: Vec<EventTime> = serde_json::from_value(record.data["event_times"].clone())
+        .map_err(|e| format!("Invalid event times in result '{}': {}", result_id, e))?;
+    times.sort_by_key(|e| e.t_ms);
+
+    let mut previous = 0;
+    Ok(times
+        .into_iter()
+        .map(|time| {
+            let entry = TimelineEntry {
+                event_name: time.event,
+                t_ms: time.t_ms,
+                delta_from_prev_ms: time.t_ms - previous,
+            };
+            previous = time.t_ms;
+            entry
+        })
+        .collect())
+}
+
+/// Yield figures for a set of test records. A unit is a MAC address; tests that never
+/// reported a MAC count towards `tests` but can't be tied to a unit.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct YieldStats {
+    pub tests: usize,
+    pub passed_tests: usize,
+    pub units: usize,
+    /// Units whose first test passed
+    pub first_pass_units: usize,
+    /// Units that passed eventually, retests included
+    pub passed_units: usize,
+    pub unidentified_tests: usize,
+    /// first_pass_units / units, 0-1
+    pub first_pass_yield: f64,
+    /// passed_units / units, 0-1
+    pub final_yield: f64,
+}
+
+impl YieldStats {
+    fn from_records<'a>(records: impl IntoIterator<Item = &'a ResultRecord>) -> Self {
+        let mut stats = YieldStats::default();
+        // Per unit: (first test passed, any test passed); records arrive oldest first
+        let mut units: BTreeMap<&str, (bool, bool)> = BTreeMap::new();
+        for record in records {
+            stats.tests += 1;
+            stats.passed_tests += record.success as usize;
+            match &record.mac {
+                Some(mac) => {
+                    let unit = units.entry(mac).or_insert((record.success, false));
+                    unit.1 |= record.success;
+                }
+                None => stats.unidentified_tests += 1,
+            }
+        }
+        stats.units = units.len();
+        stats.first_pass_units = units.values().filter(|(first, _)| *first).count();
+        stats.passed_units = units.values().filter(|(_, any)| *any).count();
+        if stats.units > 0 {
+            stats.first_pass_yield = stats.first_pass_units as f64 / stats.units as f64;
+            stats.final_yield = stats.passed_units as f64 / stats.units as f64;
+        }
+        stats
+    }
+}
+
+/// Yield for one UTC day; units are counted on every day they were tested
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyYield {
+    /// YYYY-MM-DD
+    pub date: String,
+    #[serde(flatten)]
+    pub stats: YieldStats,
+}
+
+/// First-pass and final yield over a time range, as returned by get_yield
+#[derive(Debug, Clone, Serialize)]
+pub struct YieldReport {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    #[serde(flatten)]
+    pub stats: YieldStats,
+    pub days: Vec<DailyYield>,
+}
+
+fn utc_date(timestamp: u64) -> String {
+    DateTime::from_timestamp_millis(timestamp as i64)
+        .map(|t| t.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn yield_report(app_handle: &AppHandle, since: Option<u64>, until: Option<u64>) -> Result<YieldReport, String> {
+    let records = load_records(app_handle)?;
+    let tests: Vec<&ResultRecord> = records
+        .iter()
+        .filter(|r| r.kind == "test")
+        .filter(|r| since.map_or(true, |since| r.timestamp >= since))
+        .filter(|r| until.map_or(true, |until| r.timestamp < until))
+        .collect();
+
+    let mut by_day: BTreeMap<String, Vec<&ResultRecord>> = BTreeMap::new();
+    for record in &tests {
+        by_day.entry(utc_date(record.timestamp)).or_default().push(record);
+    }
+    Ok(YieldReport {
+        since,
+        until,
+        stats: YieldStats::from_records(tests.iter().copied()),
+        days: by_day
+            .into_iter()
+            .map(|(date, records)| DailyYield {
+                date,
+                stats: YieldStats::from_records(records),
+            })
+            .collect(),
+    })
+}
+
+/// Compute first-pass and final yield from stored test results between `since` and `until`
+/// (Unix ms, until exclusive; either may be omitted), with a per-day breakdown
+#[tauri::command]
+pub fn get_yield(app_handle: AppHandle, since: Option<u64>, until: Option<u64>) -> Result<YieldReport, String> {
+    yield_report(&app_handle, since, until)
+}
+
+/// Write get_yield's per-day breakdown to a CSV file at `path`, with a final "total" row.
+/// Returns the path written.
+#[tauri::command]
+pub fn export_yield_csv(app_handle: AppHandle, path: String, since: Option<u64>, until: Option<u64>) -> Result<String, String> {
+    let report = yield_report(&app_handle, since, until)?;
+    let row = |label: &str, s: &YieldStats| {
+        format!(
+            "{},{},{},{},{},{},{},{:.4},{:.4}\n",
+            label, s.tests, s.passed_tests, s.units, s.first_pass_units, s.passed_units, s.unidentified_tests,
+            s.first_pass_yield, s.final_yield
+        )
+    };
+    let mut csv = String::from(
+        "date,tests,passed_tests,units,first_pass_units,passed_units,unidentified_tests,first_pass_yield,final_yield\n",
+    );
+    for day in &report.days {
+        csv.push_str(&row(&day.date, &day.stats));
+    }
+    csv.push_str(&row("total", &report.stats));
+    fs::write(&path, csv).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_record(mac: &str, success: bool) -> ResultRecord {
+        ResultRecord {
+            id: String::new(),
+            timestamp: 0,
+            kind: "test".to_string(),
+            port: "COM3".to_string(),
+            mac: Some(mac.to_string()),
+            success,
+            message: String::new(),
+            data: serde_json::Value::Null,
+            metadata: BTreeMap::new(),
+            audit: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn retest_pass_is_not_first_pass() {
+        let records = [test_record("AA:BB:CC:DD:EE:FF", false), test_record("AA:BB:CC:DD:EE:FF", true)];
+        let stats = YieldStats::from_records(&records);
+        assert_eq!(stats.units, 1);
+        assert_eq!(stats.first_pass_units, 0);
+        assert_eq!(stats.passed_units, 1);
+        assert_eq!(stats.unidentified_tests, 0);
+        assert_eq!(stats.first_pass_yield, 0.0);
+        assert_eq!(stats.final_yield, 1.0);
+    }
+
+    fn test_run(id: &str, data: Value) -> ResultRecord {
+        ResultRecord {
+            id: id.to_string(),
+            success: data["success"].as_bool().unwrap_or(false),
+            data,
+            ..test_record("AA:BB:CC:DD:EE:FF", false)
+        }
+    }
+
+    #[test]
+    fn diff_reports_what_changed_between_runs() {
+        let a = test_run(
+            "a",
+            serde_json::json!({
+                "success": true,
+                "message": "All tests passed",
+                "firmware_version": "1.2",
+                "events": ["[EVENT:BOOT_COMPLETE]", "[EVENT:SENSOR_OK TEMP=21.5]"],
+                "sensor_readings": {"TEMP": 21.5, "HUM": 40.0},
+                "warnings": ["RSSI low: -82"],
+            }),
+        );
+        let b = test_run(
+            "b",
+            serde_json::json!({
+                "success": false,
+                "message": "Test timed out - missing events: SENSOR_OK",
+                "firmware_version": "1.2",
+                "events": ["[EVENT:BOOT_COMPLETE]"],
+                "sensor_readings": {"HUM": 40.0},
+                "warnings": [],
+            }),
+        );
+        let diff = diff_records(a, b);
+        let fields: Vec<&str> = diff.changed_fields.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["success", "message"]);
+        assert_eq!(diff.flipped_checks.len(), 1);
+        assert_eq!(diff.flipped_checks[0].label, "Sensor OK");
+        assert!(diff.flipped_checks[0].passed_a && !diff.flipped_checks[0].passed_b);
+        assert_eq!(diff.sensor_changes.len(), 1);
+        assert_eq!(diff.sensor_changes["TEMP"], [Some(21.5), None]);
+        assert_eq!(diff.warnings_only_in_a, vec!["RSSI low: -82"]);
+        assert!(diff.warnings_only_in_b.is_empty());
+    }
+
+    #[test]
+    fn timeline_orders_events_and_measures_gaps() {
+        let record = test_run(
+            "run",
+            serde_json::json!({
+                "event_times": [
+                    {"event": "SENSOR_OK", "t_ms": 2500},
+                    {"event": "BOOT_COMPLETE", "t_ms": 800},
+                    {"event": "WIFI_CONNECTED", "t_ms": 1900},
+                ],
+            }),
+        );
+        let timeline = event_timeline(&record).unwrap();
+        let bars: Vec<(&str, u64, u64)> = timeline
+            .iter()
+            .map(|e| (e.event_name.as_str(), e.t_ms, e.delta_from_prev_ms))
+            .collect();
+        assert_eq!(
+            bars,
+            vec![("BOOT_COMPLETE", 800, 800), ("WIFI_CONNECTED", 1900, 1100), ("SENSOR_OK", 2500, 600)]
+        );
+    }
+
+    #[test]
+    fn timeline_needs_recorded_event_times() {
+        let record = test_run("old", serde_json::json!({"events": []}));
+        assert_eq!(
+            event_timeline(&record).unwrap_err(),
+            "Result 'old' was recorded without event timestamps"
+        );
+    }
+
+    #[test]
+    fn yield_counts_units_and_unidentified_tests() {
+        let mut no_mac = test_record("", false);
+        no_mac.mac = None;
+        let records = [
+            test_record("AA:00:00:00:00:01", true),
+            test_record("AA:00:00:00:00:02", false),
+            test_record("AA:00:00:00:00:03", false),
+            test_record("AA:00:00:00:00:03", false),
+            no_mac,
+        ];
+        let stats = YieldStats::from_records(&records);
+        assert_eq!((stats.tests, stats.passed_tests), (5, 1));
+        assert_eq!(stats.units, 3);
+        assert_eq!(stats.unidentified_tests, 1);
+        assert_eq!((stats.first_pass_units, stats.passed_units), (1, 1));
+        assert!((stats.first_pass_yield - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(YieldStats::from_records(&[]).first_pass_yield, 0.0);
+    }
+
+    fn le_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn zip_holds_the_deflated_file_and_is_never_overwritten() {
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!("results-archive-test-{}.zip", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let contents = b"{\"id\":\"1\"}\n{\"id\":\"2\"}\n";
+        write_zip(&path, "results.jsonl", contents).unwrap();
+
+        let zip = fs::read(&path).unwrap();
+        assert_eq!(le_u32(&zip, 0), 0x04034b50);
+        let (crc, compressed_size, size) = (le_u32(&zip, 14), le_u32(&zip, 18) as usize, le_u32(&zip, 22));
+        assert_eq!(size as usize, contents.len());
+        assert_eq!(&zip[30..43], b"results.jsonl");
+        let mut inflated = Vec::new();
+        flate2::read::DeflateDecoder::new(&zip[43..43 + compressed_size])
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, contents);
+        let mut expected_crc = Crc::new();
+        expected_crc.update(contents);
+        assert_eq!(crc, expected_crc.sum());
+        assert_eq!(le_u32(&zip, zip.len() - 22), 0x06054b50);
+
+        let error = write_zip(&path, "results.jsonl", b"other").unwrap_err();
+        assert!(error.contains("already exists"));
+        assert_eq!(fs::read(&path).unwrap(), zip);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::commands::{enumerate_ports, get_flasher_path, python_command};
+use crate::esptool::resolve_esptool;
+use crate::results::check_store_writable;
+use crate::versions::{esptool_version, flasher_version, python_version};
+
+/// One check of run_self_test
+#[derive(Debug, Serialize)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    /// What was found, or why the check failed
+    pub detail: String,
+    /// What to do about a failed check
+    pub remediation: Option<String>,
+}
+
+impl SelfTestCheck {
+    fn pass(name: &str, detail: String) -> Self {
+        SelfTestCheck {
+            name: name.to_string(),
+            passed: true,
+            detail,
+            remediation: None,
+        }
+    }
+
+    fn fail(name: &str, detail: String, remediation: &str) -> Self {
+        SelfTestCheck {
+            name: name.to_string(),
+            passed: false,
+            detail,
+            remediation: Some(remediation.to_string()),
+        }
+    }
+}
+
+/// Result of run_self_test
+#[derive(Debug, Serialize)]
+pub struct SelfTestReport {
+    /// True when every check passed
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+fn check_python() -> SelfTestCheck {
+    match python_version() {
+        Some(version) => SelfTestCheck::pass("Python", format!("{} runs (Python {})", python_command(), version)),
+        None => SelfTestCheck::fail(
+            "Python",
+            format!("{} was not found or did not run", python_command()),
+            "Install Python 3 and add it to PATH, or choose the interpreter in settings",
+        ),
+    }
+}
+
+fn check_flasher(app_handle: &AppHandle) -> SelfTestCheck {
+    match get_flasher_path(app_handle) {
+        Ok(path) => match flasher_version(app_handle) {
+            Some(version) => SelfTestCheck::pass("ncd_flasher.py", format!("{} (version {})", path.display(), version)),
+            None => SelfTestCheck::fail(
+                "ncd_flasher.py",
+                format!("{} did not report a version", path.display()),
+                "Check the script runs with Python and its dependencies are installed (pip install -r requirements.txt)",
+            ),
+        },
+        Err(e) => SelfTestCheck::fail(
+            "ncd_flasher.py",
+            e,
+            "Reinstall the app or point the flasher path setting at ncd_flasher.py",
+        ),
+    }
+}
+
+fn check_esptool(app_handle: &AppHandle) -> SelfTestCheck {
+    if let Err(e) = resolve_esptool(app_handle) {
+        return SelfTestCheck::fail("esptool", e, "Install esptool (pip install esptool) or set the esptool path");
+    }
+    match esptool_version(app_handle) {
+        Some(version) => SelfTestCheck::pass("esptool", format!("esptool {}", version)),
+        None => SelfTestCheck::fail(
+            "esptool",
+            "esptool was found but did not report a version".to_string(),
+            "Check esptool runs from a terminal; reinstall it with pip install esptool",
+        ),
+    }
+}
+
+fn check_results_store(app_handle: &AppHandle) -> SelfTestCheck {
+    match check_store_writable(app_handle) {
+        Ok(path) => SelfTestCheck::pass("Results store", format!("{} is writable", path.display())),
+        Err(e) => SelfTestCheck::fail(
+            "Results store",
+            e,
+            "Check the disk isn't full and the user can write to the app data directory",
+        ),
+    }
+}
+
+fn check_serial() -> SelfTestCheck {
+    match enumerate_ports() {
+        Ok(ports) => SelfTestCheck::pass("Serial ports", format!("{} port(s) found", ports.len())),
+        Err(e) => SelfTestCheck::fail(
+            "Serial ports",
+            e,
+            "Install the USB-serial driver (CP210x/CH34x) and, on Linux, add the user to the dialout group",
+        ),
+    }
+}
+
+/// Check a station's toolchain without a device: Python runs, ncd_flasher.py and esptool
+/// are found and report versions, the results store is writable and serial ports can be listed.
+/// Every check runs even after one fails.
+#[tauri::command]
+pub async fn run_self_test(app_handle: AppHandle) -> Result<SelfTestReport, String> {
+    tokio::task::spawn_blocking(move || {
+        let checks = vec![
+            check_python(),
+            check_flasher(&app_handle),
+            check_esptool(&app_handle),
+            check_results_store(&app_handle),
+            check_serial(),
+        ];
+        SelfTestReport {
+            passed: checks.iter().all(|c| c.passed),
+            checks,
+        }
+    }).await.map_err(|e| format!("Task join error: {}", e))
+}
@@ -0,0 +1,901 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::checklist::{
+    check_label, compile_events, event_names, line_has_event, ChecklistItem, EventChecklist, EventMatcher, REGEX_PREFIX,
+};
+use crate::commands::{extract_param, parse_build_time, parse_params, Bounds, EventTime, FirmwareCheck, TelemetryStats, TestOptions, TestPrompt, TestQuality, TestResult, TestStep};
+use crate::profiles::TestProfile;
+use crate::protocol::{json_to_event_line, Protocol, DEFAULT_JSON_EVENT_FIELD};
+use crate::resets::{parse_reset_line, ResetEvent};
+
+/// Event-matching and success-detection state for a single production test run.
+/// Lines are fed in as they arrive; anything worth showing the operator is
+/// passed back through the `emit` callback.
+pub struct TestSession {
+    checklist: EventChecklist,
+    /// Checklist entries observed since the caller last took them
+    observed: Vec<ChecklistItem>,
+    /// Events outside the profile already reported as unlisted
+    unlisted: HashSet<String>,
+    events: Vec<String>,
+    max_events: Option<usize>,
+    /// Most recent lines once `events` holds its first half of max_events
+    events_tail: VecDeque<String>,
+    /// Lines dropped since the last kept one
+    elided: usize,
+    /// True while lines are boot output rather than test output (only with capture_boot_log)
+    in_boot: bool,
+    boot_log: Vec<String>,
+    firmware_version: Option<String>,
+    mac_address: Option<String>,
+    sensor_bounds: HashMap<String, Bounds>,
+    sensor_readings: HashMap<String, f64>,
+    device_attributes: HashMap<String, String>,
+    warn_events: Vec<EventMatcher>,
+    warn_bounds: HashMap<String, Bounds>,
+    warnings: Vec<String>,
+    sensor_samples: Vec<f64>,
+    min_sensor_samples: Option<u32>,
+    max_sample_variance: Option<f64>,
+    reset_reason: Option<String>,
+    calibration: Option<HashMap<String, f64>>,
+    calibration_bounds: HashMap<String, Bounds>,
+    fail_on_power_fault: bool,
+    protocol: Protocol,
+    json_event_field: String,
+    max_line_repeats: Option<u32>,
+    fail_fast_events: Vec<EventMatcher>,
+    forbidden_events: Vec<EventMatcher>,
+    prompts: Vec<(Regex, String)>,
+    /// Prompt answers waiting to be written to the device
+    replies: Vec<String>,
+    telemetry_window: Option<Duration>,
+    /// End of the telemetry capture, once SENSOR_OK has started it
+    telemetry_until: Option<Instant>,
+    telemetry: HashMap<String, Vec<f64>>,
+    post_success_capture: Duration,
+    /// End of the post-success capture, once the run has passed
+    capture_until: Option<Instant>,
+    min_build: Option<DateTime<Utc>>,
+    build_time: Option<DateTime<Utc>>,
+    max_boot_temp_c: Option<f64>,
+    boot_temp_c: Option<f64>,
+    /// allowed_firmware entries with the regex each compiles to
+    allowed_firmware: Vec<(String, Regex)>,
+    firmware_check: Option<FirmwareCheck>,
+    line_counts: HashMap<String, u32>,
+    /// When the test command was sent; event times are measured from here
+    started: Instant,
+    event_times: Vec<EventTime>,
+    steps: Vec<TestStep>,
+    passed: bool,
+    failure: Option<String>,
+}
+
+impl TestSession {
+    /// Fails if any required, fail-fast or warn event pattern is not a valid regex
+    pub fn new(profile: &TestProfile, options: &TestOptions) -> Result<Self, String> {
+        Ok(TestSession {
+            checklist: EventChecklist::compile(&profile.required_events)?,
+            observed: Vec::new(),
+            unlisted: HashSet::new(),
+            events: Vec::new(),
+            max_events: options.max_events,
+            events_tail: VecDeque::new(),
+            elided: 0,
+            in_boot: options.capture_boot_log,
+            boot_log: Vec::new(),
+            firmware_version: None,
+            mac_address: None,
+            sensor_bounds: options.sensor_bounds.clone(),
+            sensor_readings: HashMap::new(),
+            device_attributes: HashMap::new(),
+            warn_events: compile_events(&options.warn_events)?,
+            warn_bounds: options.warn_bounds.clone(),
+            warnings: Vec::new(),
+            sensor_samples: Vec::new(),
+            min_sensor_samples: options.min_sensor_samples,
+            max_sample_variance: options.max_sample_variance,
+            reset_reason: None,
+            calibration: None,
+            calibration_bounds: options.calibration_bounds.clone(),
+            fail_on_power_fault: options.fail_on_power_fault,
+            protocol: options.protocol,
+            json_event_field: options
+                .json_event_field
+                .clone()
+                .unwrap_or_else(|| DEFAULT_JSON_EVENT_FIELD.to_string()),
+            max_line_repeats: options.max_line_repeats,
+            fail_fast_events: compile_events(&options.fail_fast_events)?,
+            forbidden_events: compile_events(&options.forbidden_events)?,
+            prompts: options.prompts.iter().map(compile_prompt).collect::<Result<_, _>>()?,
+            replies: Vec::new(),
+            telemetry_window: options.telemetry_secs.map(Duration::from_secs),
+            telemetry_until: None,
+            telemetry: HashMap::new(),
+            post_success_capture: Duration::from_millis(options.post_success_capture_ms.unwrap_or(0)),
+            capture_until: None,
+            // resolve() has already rejected an unparseable min_build
+            min_build: options.min_build().ok().flatten(),
+            build_time: None,
+            max_boot_temp_c: options.max_boot_temp_c,
+            boot_temp_c: None,
+            allowed_firmware: options
+                .allowed_firmware
+                .iter()
+                .map(String::as_str)
+                .map(compile_version_pattern)
+                .collect::<Result<_, _>>()?,
+            firmware_check: None,
+            line_counts: HashMap::new(),
+            started: Instant::now(),
+            event_times: Vec::new(),
+            steps: Vec::new(),
+            passed: false,
+            failure: None,
+        })
+    }
+
+    /// Process one raw line from the device.
+    /// `source` tags the line with the UART it came from when more than one is in use.
+    pub fn process_line(&mut self, line: &str, source: Option<&str>, emit: &mut dyn FnMut(&str)) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || self.is_finished() {
+            return;
+        }
+
+        let tagged = match source {
+            Some(source) => format!("[{}] {}", source, trimmed),
+            None => trimmed.to_string(),
+        };
+        // Emit raw line to frontend for real-time display
+        emit(&tagged);
+        // Boot output ends once the application starts reporting events
+        self.in_boot &= event_names(trimmed).is_empty()
+            && (self.protocol != Protocol::Json || json_to_event_line(trimmed, &self.json_event_field).is_none());
+        if self.in_boot {
+            self.boot_log.push(tagged);
+        } else {
+            self.record_line(tagged);
+        }
+        // Forbidden events fail the unit even after it has passed
+        if self.check_forbidden(trimmed, emit) {
+            return;
+        }
+        if let Some((_, response)) = self.prompts.iter().find(|(pattern, _)| pattern.is_match(trimmed)) {
+            self.replies.push(response.clone());
+        }
+        // After a pass, lines are only collected, except TELEMETRY during its capture window
+        if self.passed && !self.capturing_telemetry() {
+            return;
+        }
+
+        if self.check_repeats(trimmed, emit) {
+            return;
+        }
+
+        // ROM reset messages are plain text whatever protocol the firmware speaks
+        if let Some(reset) = parse_reset_line(trimmed) {
+            self.record_reset(&reset, emit);
+            if self.is_finished() {
+                return;
+            }
+        }
+
+        // JSON events are matched in their text form; other output in JSON mode is just logged
+        let normalized;
+        let trimmed = match self.protocol {
+            Protocol::Text => trimmed,
+            Protocol::Json => match json_to_event_line(trimmed, &self.json_event_field) {
+                Some(line) => {
+                    normalized = line;
+                    normalized.as_str()
+                }
+                None => return,
+            },
+        };
+        if self.protocol == Protocol::Json && self.check_forbidden(trimmed, emit) {
+            return;
+        }
+
+        // Note: We don't immediately trust [EVENT:TEST_PASS] or [EVENT:TEST_FAIL]
+        // from the device's internal test because it may run before MQTT connects.
+        // Instead, we wait for the actual connection events.
+
+        if let Some(event) = self.fail_fast_events.iter().find(|e| e.matches(trimmed)) {
+            self.fail(format!("Fail-fast event: {}", event.name()), emit);
+            return;
+        }
+
+        // Capture every key=value an event carries (HW_REV, BATCH, RSSI, ...)
+        let names = event_names(trimmed);
+        if !names.is_empty() {
+            self.device_attributes.extend(parse_params(trimmed));
+            self.record_event_times(names);
+        }
+
+        self.check_warnings(trimmed, emit);
+
+        if line_has_event(trimmed, "SENSOR_SAMPLE") {
+            if let Some(value) = extract_param(trimmed, "value").and_then(|v| v.trim_end_matches(']').parse().ok()) {
+                self.sensor_samples.push(value);
+            }
+        }
+
+        if line_has_event(trimmed, "CAL_DONE") {
+            if let Err(message) = self.check_calibration(trimmed) {
+                emit(&format!("[CHECK] ✗ {}", message));
+                self.failure = Some(message);
+                return;
+            }
+        }
+
+        // Gate sensor readings before SENSOR_OK can count towards a pass
+        if line_has_event(trimmed, "SENSOR_OK") {
+            let checked = self.check_sensor_readings(trimmed).and_then(|_| self.check_sensor_samples());
+            if let Err(message) = checked {
+                emit(&format!("[CHECK] ✗ {}", message));
+                self.failure = Some(message);
+                return;
+            }
+        }
+
+        if line_has_event(trimmed, "SENSOR_OK") && self.telemetry_until.is_none() {
+            if let Some(window) = self.telemetry_window {
+                emit(&format!("[INFO] Capturing telemetry for {}s", window.as_secs()));
+                self.telemetry_until = Some(Instant::now() + window);
+            }
+        }
+        if line_has_event(trimmed, "TELEMETRY") && self.capturing_telemetry() {
+            for (name, value) in parse_params(trimmed) {
+                if let Ok(value) = value.parse::<f64>() {
+                    self.telemetry.entry(name).or_default().push(value);
+                }
+            }
+        }
+
+        // Identify the unit before the BOOT_COMPLETE gates, so a unit they reject is still traceable
+        if line_has_event(trimmed, "BOOT_COMPLETE") {
+            if let Some(fw) = extract_param(trimmed, "FIRMWARE") {
+                emit(&format!("[INFO] Firmware: {}", fw));
+                self.firmware_version = Some(fw);
+            }
+            if let Some(mac) = extract_param(trimmed, "MAC") {
+                emit(&format!("[INFO] MAC: {}", mac));
+                self.mac_address = Some(mac);
+            }
+            let checked = self
+                .check_build(trimmed, emit)
+                .and_then(|_| self.check_boot_temp(trimmed, emit))
+                .and_then(|_| self.check_firmware(trimmed, emit));
+            if let Err(message) = checked {
+                emit(&format!("[CHECK] ✗ {}", message));
+                self.failure = Some(message);
+                return;
+            }
+        }
+
+        // Track production events for auto-detection and emit status
+        if let Some(item) = self.checklist.observe(trimmed) {
+            emit(&format!("[CHECK] ✓ {}", item.display_name));
+            let finished_ms = self.elapsed_ms();
+            self.steps.push(TestStep {
+                name: item.display_name.clone(),
+                passed: true,
+                started_ms: self.last_step_ms(),
+                finished_ms,
+                detail: Some(trimmed.to_string()),
+            });
+            self.observed.push(item);
+        }
+        for name in event_names(trimmed) {
+            if !self.checklist.requires(&name) && self.unlisted.insert(name.clone()) {
+                self.observed.push(ChecklistItem::unlisted(name));
+            }
+        }
+
+        // Auto-detect success when all required events have been seen
+        if !self.passed && self.checklist.is_complete() {
+            self.passed = true;
+            emit("[CHECK] ✓ All checks passed!");
+            self.capture_until = Some(Instant::now() + self.post_success_capture);
+        }
+    }
+
+    /// Keep a line read before the test command was sent, when capturing the boot log
+    pub fn record_boot_line(&mut self, line: &str) {
+        if self.in_boot {
+            self.boot_log.push(line.to_string());
+        }
+    }
+
+    /// Measure event times from now; call when the test command is sent (or resent)
+    pub fn restart_clock(&mut self) {
+        self.started = Instant::now();
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// When the latest step finished, where the next one starts
+    fn last_step_ms(&self) -> u64 {
+        self.steps.last().map_or(0, |step| step.finished_ms)
+    }
+
+    /// Steps for the required events never observed: the first carries the failure that
+    /// stopped the run, if any, and the rest were not reached
+    fn unfinished_steps(&self) -> Vec<TestStep> {
+        let started_ms = self.last_step_ms();
+        let finished_ms = self.elapsed_ms();
+        self.checklist
+            .missing_items()
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| TestStep {
+                name: item.display_name.clone(),
+                passed: false,
+                started_ms,
+                finished_ms,
+                detail: Some(match (index, &self.failure) {
+                    (0, Some(failure)) => failure.clone(),
+                    (0, None) => "Not observed".to_string(),
+                    _ => "Not reached".to_string(),
+                }),
+            })
+            .collect()
+    }
+
+    /// Note the first sighting of each event
+    fn record_event_times(&mut self, names: Vec<String>) {
+        let t_ms = self.elapsed_ms();
+        for event in names {
+            if !self.event_times.iter().any(|e| e.event == event) {
+                self.event_times.push(EventTime { event, t_ms });
+            }
+        }
+    }
+
+    /// Answers to prompts seen since the last call, for the caller to write to the device
+    pub fn take_replies(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// Checklist entries observed since the last call, for the "test-event" emission
+    pub fn take_observed(&mut self) -> Vec<ChecklistItem> {
+        std::mem::take(&mut self.observed)
+    }
+
+    /// Add a line to `events`. Past max_events, the first and last halves are kept and the
+    /// lines between them are replaced by "... N lines elided ..." markers, except lines
+    /// carrying an [EVENT:...], which are always kept.
+    fn record_line(&mut self, line: String) {
+        let Some(max) = self.max_events else {
+            self.events.push(line);
+            return;
+        };
+        let head = max / 2;
+        if self.events.len() < head && self.events_tail.is_empty() {
+            self.events.push(line);
+            return;
+        }
+        self.events_tail.push_back(line);
+        if self.events_tail.len() <= max - head {
+            return;
+        }
+        if let Some(oldest) = self.events_tail.pop_front() {
+            if event_names(&oldest).is_empty() {
+                self.elided += 1;
+            } else {
+                self.flush_elided();
+                self.events.push(oldest);
+            }
+        }
+    }
+
+    fn flush_elided(&mut self) {
+        if self.elided > 0 {
+            self.events.push(format!("... {} lines elided ...", self.elided));
+            self.elided = 0;
+        }
+    }
+
+    /// Fail the run if the line carries a forbidden event. Returns true if it did.
+    fn check_forbidden(&mut self, line: &str, emit: &mut dyn FnMut(&str)) -> bool {
+        let Some(event) = self.forbidden_events.iter().find(|e| e.matches(line)) else {
+            return false;
+        };
+        let message = format!("Forbidden event observed: {}", event.name());
+        self.fail(message, emit);
+        true
+    }
+
+    /// Count a raw line and fail the run once it has repeated more than max_line_repeats times.
+    /// Returns true if the run was failed.
+    fn check_repeats(&mut self, line: &str, emit: &mut dyn FnMut(&str)) -> bool {
+        let Some(limit) = self.max_line_repeats else {
+            return false;
+        };
+        let count = self.line_counts.entry(line.to_string()).or_default();
+        *count += 1;
+        if *count <= limit {
+            return false;
+        }
+        let message = format!("Device appears stuck in a loop: {}", line);
+        emit(&format!("[CHECK] ✗ {}", message));
+        self.failure = Some(message);
+        true
+    }
+
+    /// Check the SENSOR_SAMPLE values seen so far against the sample count and noise limits
+    fn check_sensor_samples(&self) -> Result<(), String> {
+        let count = self.sensor_samples.len();
+        if let Some(min) = self.min_sensor_samples {
+            if count < min as usize {
+                return Err(format!("Too few sensor samples: {} (need {})", count, min));
+            }
+        }
+        if let (Some(max), true) = (self.max_sample_variance, count >= 2) {
+            let mean = self.sensor_samples.iter().sum::<f64>() / count as f64;
+            let variance = self
+                .sensor_samples
+                .iter()
+                .map(|v| (v - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64;
+            if variance > max {
+                return Err(format!("Sensor readings too noisy: variance {:.3} (max {})", variance, max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record every numeric reading on a SENSOR_OK line and check it against its bounds
+    fn check_sensor_readings(&mut self, line: &str) -> Result<(), String> {
+        let readings: Vec<(String, f64)> = parse_params(line)
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value.parse::<f64>().ok()?)))
+            .collect();
+        self.sensor_readings.extend(readings.iter().cloned());
+
+        for (name, value) in readings {
+            let Some(bounds) = self.sensor_bounds.get(&name) else {
+                continue;
+            };
+            if !bounds.contains(value) {
+                return Err(format!(
+                    "Sensor reading out of range: {}={} (allowed {} to {})",
+                    name, value, bounds.min, bounds.max
+                ));
+            }
+        }
+
+        let mut unreported: Vec<&String> = self
+            .sensor_bounds
+            .keys()
+            .filter(|name| !self.sensor_readings.contains_key(*name))
+            .collect();
+        unreported.sort();
+        if let Some(name) = unreported.first() {
+            return Err(format!("Sensor reading missing: {}", name));
+        }
+
+        Ok(())
+    }
+
+    /// Record the coefficients on a CAL_DONE line and check each against its bounds
+    fn check_calibration(&mut self, line: &str) -> Result<(), String> {
+        let coefficients: HashMap<String, f64> = parse_params(line)
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value.parse::<f64>().ok()?)))
+            .collect();
+
+        let mut bounded: Vec<(&String, &Bounds)> = self.calibration_bounds.iter().collect();
+        bounded.sort_by_key(|(name, _)| *name);
+        let result = bounded.into_iter().try_for_each(|(name, bounds)| match coefficients.get(name) {
+            Some(value) if !bounds.contains(*value) => Err(format!("Calibration out of range: {}={}", name, value)),
+            Some(_) => Ok(()),
+            None => Err(format!("Calibration coefficient missing: {}", name)),
+        });
+        self.calibration = Some(coefficients);
+        result
+    }
+
+    /// Record the BUILD timestamp on a BOOT_COMPLETE line and check it against min_build
+    fn check_build(&mut self, line: &str, emit: &mut dyn FnMut(&str)) -> Result<(), String> {
+        let build = extract_param(line, "BUILD");
+        if let Some(build) = &build {
+            match parse_build_time(build) {
+                Some(time) => {
+                    emit(&format!("[INFO] Build: {}", format_build_time(&time)));
+                    self.build_time = Some(time);
+                }
+                None => emit(&format!("[WARN] Unrecognised build timestamp: {}", build)),
+            }
+        }
+        let Some(min) = self.min_build else {
+            return Ok(());
+        };
+        match self.build_time {
+            Some(time) if time < min => Err(format!(
+                "Firmware build {} is older than the minimum {}",
+                format_build_time(&time),
+                format_build_time(&min)
+            )),
+            Some(_) => Ok(()),
+            None => Err("Firmware did not report a valid build timestamp".to_string()),
+        }
+    }
+
+    /// Record the temp_c reading on a BOOT_COMPLETE line and check it against max_boot_temp_c
+    fn check_boot_temp(&mut self, line: &str, emit: &mut dyn FnMut(&str)) -> Result<(), String> {
+        let Some(value) = extract_param(line, "temp_c") else {
+            return Ok(());
+        };
+        let Ok(temp) = value.parse::<f64>() else {
+            emit(&format!("[WARN] Unrecognised boot temperature: {}", value));
+            return Ok(());
+        };
+        emit(&format!("[INFO] Boot temperature: {:.1}C", temp));
+        self.boot_temp_c = Some(temp);
+        match self.max_boot_temp_c {
+            Some(max) if temp > max => Err(format!("Boot temperature too high: {:.1}C", temp)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the FIRMWARE version on a BOOT_COMPLETE line against allowed_firmware
+    fn check_firmware(&mut self, line: &str, emit: &mut dyn FnMut(&str)) -> Result<(), String> {
+        if self.allowed_firmware.is_empty() {
+            return Ok(());
+        }
+        let version = extract_param(line, "FIRMWARE");
+        let matched = version.as_deref().and_then(|version| {
+            self.allowed_firmware
+                .iter()
+                .find(|(_, regex)| regex.is_match(version))
+                .map(|(pattern, _)| pattern.clone())
+        });
+        self.firmware_check = Some(FirmwareCheck {
+            version: version.clone(),
+            approved: matched.is_some(),
+            matched: matched.clone(),
+        });
+        match (version, matched) {
+            (Some(version), Some(pattern)) => {
+                emit(&format!("[INFO] Firmware {} approved ({})", version, pattern));
+                Ok(())
+            }
+            (Some(version), None) => Err(format!("Firmware {} not in approved list", version)),
+            (None, _) => Err("Firmware did not report a version to check against the approved list".to_string()),
+        }
+    }
+
+    /// Note a reset reported by the ROM. Brownouts fail the run when fail_on_power_fault
+    /// is set; other unexpected resets are warnings.
+    fn record_reset(&mut self, reset: &ResetEvent, emit: &mut dyn FnMut(&str)) {
+        if !reset.is_abnormal() {
+            return;
+        }
+        // Keep the most telling reason: a power fault isn't overwritten by the reset it causes
+        if !self.reset_reason.as_deref().is_some_and(|r| r == "BROWNOUT") {
+            self.reset_reason = Some(reset.reason.clone());
+        }
+
+        if reset.power_fault && self.fail_on_power_fault {
+            let message = "Power fault detected: brownout".to_string();
+            emit(&format!("[CHECK] ✗ {}", message));
+            self.failure = Some(message);
+            return;
+        }
+        let warning = if reset.power_fault {
+            format!("Power fault: {}", reset.reason)
+        } else {
+            format!("Unexpected reset: {}", reset.reason)
+        };
+        if !self.warnings.contains(&warning) {
+            emit(&format!("[WARN] {}", warning));
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Record soft-check findings on a line; these never affect the outcome
+    fn check_warnings(&mut self, line: &str, emit: &mut dyn FnMut(&str)) {
+        let mut found = Vec::new();
+        for event in &self.warn_events {
+            if event.matches(line) {
+                found.push(format!("{} reported", check_label(event.name())));
+            }
+        }
+        for (name, value) in parse_params(line) {
+            let (Some(bounds), Ok(value)) = (self.warn_bounds.get(&name), value.parse::<f64>()) else {
+                continue;
+            };
+            if !bounds.contains(value) {
+                found.push(format!(
+                    "{}={} outside soft limit ({} to {})",
+                    name, value, bounds.min, bounds.max
+                ));
+            }
+        }
+
+        for warning in found {
+            if !self.warnings.contains(&warning) {
+                emit(&format!("[WARN] {}", warning));
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    /// End the run with a failure detected outside the line stream (e.g. an idle timeout)
+    pub fn fail(&mut self, message: String, emit: &mut dyn FnMut(&str)) {
+        emit(&format!("[CHECK] ✗ {}", message));
+        self.failure = Some(message);
+    }
+
+    fn capturing_telemetry(&self) -> bool {
+        self.telemetry_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    /// True once any event has been recognised
+    pub fn has_events(&self) -> bool {
+        !self.event_times.is_empty()
+    }
+
+    /// True once the run has passed (and any telemetry or post-success capture has ended)
+    /// or hit a failure that ends it early
+    pub fn is_finished(&self) -> bool {
+        let capturing = self.capturing_telemetry() || self.capture_until.is_some_and(|until| Instant::now() < until);
+        (self.passed && !capturing) || self.failure.is_some()
+    }
+
+    fn telemetry_stats(&self) -> HashMap<String, TelemetryStats> {
+        self.telemetry
+            .iter()
+            .filter_map(|(name, values)| Some((name.clone(), TelemetryStats::from_values(values)?)))
+            .collect()
+    }
+
+    /// Build the final result once the run has passed or timed out
+    pub fn into_result(mut self) -> TestResult {
+        self.flush_elided();
+        self.events.extend(self.events_tail.drain(..));
+        let mut steps = std::mem::take(&mut self.steps);
+        steps.extend(self.unfinished_steps());
+        let (success, message) = match self.failure.take() {
+            Some(message) => (false, message),
+            None if self.passed => (true, "All tests passed".to_string()),
+            None => {
+                // Build a message showing which events were missing
+                let missing = self.checklist.missing();
+                if missing.is_empty() {
+                    (false, "Test timed out".to_string())
+                } else {
+                    (false, format!("Test timed out - missing events: {}", missing.join(", ")))
+                }
+            }
+        };
+
+        let mut result = TestResult {
+            success,
+            message,
+            telemetry: self.telemetry_stats(),
+            build_time: self.build_time.as_ref().map(format_build_time),
+            events: self.events,
+            firmware_version: self.firmware_version,
+            mac_source: self.mac_address.as_ref().map(|_| "boot_event".to_string()),
+            mac_address: self.mac_address,
+            sensor_readings: self.sensor_readings,
+            device_attributes: self.device_attributes,
+            warnings: self.warnings,
+            sensor_samples: self.sensor_samples,
+            reset_reason: self.reset_reason,
+            calibration: self.calibration,
+            post_pass_ack: None,
+            attempts: 1,
+            quality: TestQuality::Fail,
+            event_times: self.event_times,
+            boot_log: self.boot_log,
+            steps,
+            boot_temp_c: self.boot_temp_c,
+            firmware_check: self.firmware_check,
+        };
+        result.quality = TestQuality::assess(&result);
+        result
+    }
+}
+
+/// An allowed_firmware entry is matched against the whole version, "*" standing for any run of characters
+fn compile_version_pattern(pattern: &str) -> Result<(String, Regex), String> {
+    let regex = format!("^{}$", regex::escape(pattern.trim()).replace(r"\*", ".*"));
+    let regex = Regex::new(&regex).map_err(|e| format!("Invalid allowed_firmware entry '{}': {}", pattern, e))?;
+    Ok((pattern.to_string(), regex))
+}
+
+/// A prompt pattern is matched literally unless it is a "re:" regex
+fn compile_prompt(prompt: &TestPrompt) -> Result<(Regex, String), String> {
+    let regex = match prompt.pattern.strip_prefix(REGEX_PREFIX) {
+        Some(pattern) => Regex::new(pattern).map_err(|e| format!("Invalid prompt pattern '{}': {}", pattern, e))?,
+        None => Regex::new(&regex::escape(&prompt.pattern)).map_err(|e| e.to_string())?,
+    };
+    Ok((regex, prompt.response.clone()))
+}
+
+fn format_build_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::builtin_profiles;
+
+    fn local_sensor() -> TestProfile {
+        builtin_profiles().into_iter().find(|p| p.name == "local-sensor").unwrap()
+    }
+
+    fn run(session: &mut TestSession, lines: &[&str]) {
+        for line in lines {
+            session.process_line(line, None, &mut |_| {});
+        }
+    }
+
+    #[test]
+    fn json_boot_complete_keeps_last_param_intact() {
+        let options = TestOptions { protocol: Protocol::Json, ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(
+            &mut session,
+            &[
+                r#"{"event":"BOOT_COMPLETE","mac":"AA:BB:CC:DD:EE:FF","firmware":"1.2"}"#,
+                r#"{"event":"SENSOR_OK"}"#,
+            ],
+        );
+        let result = session.into_result();
+        assert!(result.success);
+        assert_eq!(result.mac_address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(result.firmware_version.as_deref(), Some("1.2"));
+    }
+
+    #[test]
+    fn failed_run_keeps_boot_complete_mac() {
+        let mut session = TestSession::new(&local_sensor(), &TestOptions::default()).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE FIRMWARE=1.2 MAC=AA:BB:CC:DD:EE:FF]"]);
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.mac_address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(result.firmware_version.as_deref(), Some("1.2"));
+        assert_eq!(result.mac_source.as_deref(), Some("boot_event"));
+    }
+
+    #[test]
+    fn old_build_still_identifies_the_unit() {
+        let options = TestOptions { min_build: Some("2024-06-01T00:00:00Z".to_string()), ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE BUILD=2024-01-01T00:00:00Z FIRMWARE=1.2 MAC=AA:BB:CC:DD:EE:FF]"]);
+        assert!(session.is_finished());
+        let result = session.into_result();
+        assert!(!result.success);
+        assert!(result.message.contains("older than the minimum"));
+        assert_eq!(result.mac_address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(result.firmware_version.as_deref(), Some("1.2"));
+        assert_eq!(result.build_time.as_deref(), Some("2024-01-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn unapproved_firmware_keeps_version_and_mac() {
+        let options = TestOptions { allowed_firmware: vec!["2.3.*".to_string()], ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE FIRMWARE=2.4.0 MAC=AA:BB:CC:DD:EE:FF]"]);
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.message, "Firmware 2.4.0 not in approved list");
+        assert_eq!(result.firmware_version.as_deref(), Some("2.4.0"));
+        assert_eq!(result.mac_address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        let check = result.firmware_check.unwrap();
+        assert_eq!(check.version.as_deref(), Some("2.4.0"));
+        assert!(!check.approved);
+    }
+
+    #[test]
+    fn approved_firmware_wildcard_matches() {
+        let options = TestOptions { allowed_firmware: vec!["2.3.*".to_string()], ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE FIRMWARE=2.3.7]", "[EVENT:SENSOR_OK]"]);
+        let result = session.into_result();
+        assert!(result.success);
+        assert_eq!(result.firmware_check.unwrap().matched.as_deref(), Some("2.3.*"));
+    }
+
+    #[test]
+    fn hot_boot_fails_but_keeps_identity() {
+        let options = TestOptions { max_boot_temp_c: Some(60.0), ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE FIRMWARE=1.2 MAC=AA:BB:CC:DD:EE:FF temp_c=71.5]"]);
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.message, "Boot temperature too high: 71.5C");
+        assert_eq!(result.boot_temp_c, Some(71.5));
+        assert_eq!(result.mac_address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(result.firmware_version.as_deref(), Some("1.2"));
+    }
+
+    #[test]
+    fn event_params_are_parsed() {
+        let line = "[EVENT:SENSOR_OK TEMP=21.5 HUM=40]";
+        assert_eq!(extract_param(line, "TEMP").as_deref(), Some("21.5"));
+        assert_eq!(extract_param(line, "HUM").as_deref(), Some("40"));
+        assert_eq!(extract_param(line, "PRESS"), None);
+        assert_eq!(
+            parse_params(line),
+            vec![("TEMP".to_string(), "21.5".to_string()), ("HUM".to_string(), "40".to_string())]
+        );
+    }
+
+    #[test]
+    fn passes_once_every_required_event_is_seen() {
+        let mut session = TestSession::new(&local_sensor(), &TestOptions::default()).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE MAC=AA:BB:CC:DD:EE:FF]"]);
+        assert!(!session.is_finished());
+        run(&mut session, &["[EVENT:SENSOR_OK]"]);
+        assert!(session.is_finished());
+        let result = session.into_result();
+        assert!(result.success);
+        assert_eq!(result.message, "All tests passed");
+        assert_eq!(result.steps.iter().filter(|s| s.passed).count(), 2);
+    }
+
+    #[test]
+    fn timeout_lists_missing_events() {
+        let mut session = TestSession::new(&local_sensor(), &TestOptions::default()).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE]"]);
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.message, "Test timed out - missing events: SENSOR_OK");
+        let missing = result.steps.iter().find(|s| !s.passed).unwrap();
+        assert_eq!(missing.detail.as_deref(), Some("Not observed"));
+    }
+
+    #[test]
+    fn fail_fast_event_ends_the_run() {
+        let options = TestOptions { fail_fast_events: vec!["SENSOR_FAIL".to_string()], ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE]", "[EVENT:SENSOR_FAIL code=3]", "[EVENT:SENSOR_OK]"]);
+        assert!(session.is_finished());
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.message, "Fail-fast event: SENSOR_FAIL");
+    }
+
+    #[test]
+    fn forbidden_event_fails_a_passed_run() {
+        let options = TestOptions {
+            forbidden_events: vec!["WATCHDOG".to_string()],
+            post_success_capture_ms: Some(60_000),
+            ..TestOptions::default()
+        };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["[EVENT:BOOT_COMPLETE]", "[EVENT:SENSOR_OK]", "[EVENT:WATCHDOG]"]);
+        let result = session.into_result();
+        assert!(!result.success);
+        assert_eq!(result.message, "Forbidden event observed: WATCHDOG");
+    }
+
+    #[test]
+    fn repeated_line_is_a_stuck_loop() {
+        let options = TestOptions { max_line_repeats: Some(2), ..TestOptions::default() };
+        let mut session = TestSession::new(&local_sensor(), &options).unwrap();
+        run(&mut session, &["Retrying sensor", "Retrying sensor", "Retrying sensor"]);
+        assert!(session.is_finished());
+        assert_eq!(session.into_result().message, "Device appears stuck in a loop: Retrying sensor");
+    }
+
+    #[test]
+    fn invalid_event_pattern_is_rejected() {
+        let options = TestOptions { fail_fast_events: vec!["re:([".to_string()], ..TestOptions::default() };
+        assert!(TestSession::new(&local_sensor(), &options).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::config;
+use crate::profiles;
+use crate::results::{self, now_millis};
+use crate::state::AppState;
+
+/// Save once this many results have been counted since the last save
+const SAVE_EVERY_RESULTS: u32 = 10;
+/// Otherwise unsaved results are written at least this often
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Passed and failed counts for one kind of result
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PassFail {
+    pub passed: u32,
+    pub failed: u32,
+}
+
+/// The operator's counters for the current shift. Saved to disk periodically and restored
+/// on startup if the app was restarted on the same day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// Local calendar day the session started, YYYY-MM-DD
+    pub date: String,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+    /// Counts per result kind ("test", "flash", "provision", ...)
+    pub counts: BTreeMap<String, PassFail>,
+}
+
+impl SessionStats {
+    fn new() -> Self {
+        SessionStats {
+            date: today(),
+            started_at: now_millis(),
+            counts: BTreeMap::new(),
+        }
+    }
+}
+
+/// What session.json holds
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedSession {
+    stats: SessionStats,
+    seen_macs: Vec<String>,
+}
+
+fn today() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn session_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Could not resolve data directory: {}", e))?;
+    Ok(dir.join("session.json"))
+}
+
+/// Reload the saved session if it's from today, otherwise start a new one. Called at startup.
+pub fn restore(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let saved = session_path(app_handle)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|contents| serde_json::from_str::<SavedSession>(&contents).map_err(|e| e.to_string()));
+
+    let stats = match saved {
+        Ok(saved) if saved.stats.date == today() => {
+            log::info!("Restored session from {} with {} known MACs", saved.stats.date, saved.seen_macs.len());
+            if let Ok(mut seen) = state.seen_macs.lock() {
+                seen.extend(saved.seen_macs);
+            }
+            saved.stats
+        }
+        _ => SessionStats::new(),
+    };
+    if let Ok(mut current) = state.session_stats.lock() {
+        *current = stats;
+    };
+}
+
+/// Write the session stats and the duplicate-MAC set to session.json
+pub fn save(app_handle: &AppHandle) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let saved = SavedSession {
+        stats: state.session_stats.lock().map_err(|e| e.to_string())?.clone(),
+        seen_macs: state.seen_macs.lock().map_err(|e| e.to_string())?.iter().cloned().collect(),
+    };
+    state.unsaved_results.store(0, Ordering::SeqCst);
+
+    let path = session_path(app_handle)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let contents = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, contents).map_err(|e| format!("Failed to write session: {}", e))?;
+    fs::rename(&temp_path, &path).map_err(|e| format!("Failed to replace session: {}", e))
+}
+
+fn save_logged(app_handle: &AppHandle) {
+    if let Err(e) = save(app_handle) {
+        log::error!("Failed to save session: {}", e);
+    }
+}
+
+/// Count a finished operation towards the session, saving every SAVE_EVERY_RESULTS results
+pub fn count(app_handle: &AppHandle, kind: &str, success: bool) {
+    let state = app_handle.state::<AppState>();
+    if let Ok(mut stats) = state.session_stats.lock() {
+        let counts = stats.counts.entry(kind.to_string()).or_default();
+        if success {
+            counts.passed += 1;
+        } else {
+            counts.failed += 1;
+        }
+    }
+    if state.unsaved_results.fetch_add(1, Ordering::SeqCst) + 1 >= SAVE_EVERY_RESULTS {
+        save_logged(app_handle);
+    }
+}
+
+/// Save any unsaved results every SAVE_INTERVAL for the life of the app
+pub fn start_autosave(app_handle: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(SAVE_INTERVAL);
+        if app_handle.state::<AppState>().unsaved_results.load(Ordering::SeqCst) > 0 {
+            save_logged(&app_handle);
+        }
+    });
+}
+
+/// Counters for the current session
+#[tauri::command]
+pub fn get_session_stats(app_handle: AppHandle) -> Result<SessionStats, String> {
+    let state = app_handle.state::<AppState>();
+    let stats = state.session_stats.lock().map_err(|e| e.to_string())?.clone();
+    Ok(stats)
+}
+
+/// Start a fresh session for a new shift: zero the counters and forget the MACs seen so far.
+/// Emits "session-started" with the new stats.
+#[tauri::command]
+pub fn start_new_session(app_handle: AppHandle) -> Result<SessionStats, String> {
+    let state = app_handle.state::<AppState>();
+    let stats = SessionStats::new();
+    *state.session_stats.lock().map_err(|e| e.to_string())? = stats.clone();
+    state.seen_macs.lock().map_err(|e| e.to_string())?.clear();
+    save(&app_handle)?;
+    let _ = app_handle.emit("session-started", &stats);
+    Ok(stats)
+}
+
+/// Payload of the "profile-switched" event, also returned by switch_profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSwitch {
+    pub from: String,
+    pub to: String,
+    /// True when the session was archived and restarted with the switch
+    pub session_reset: bool,
+}
+
+/// Make `name` the profile tests use when they don't name one, e.g. when the line changes
+/// product mid-shift. With `reset_session`, the current session stats are archived to the
+/// results store as a "session" record and a fresh session is started, forgetting the MACs
+/// seen so far. Emits "profile-switched" with the old and new names.
+#[tauri::command]
+pub fn switch_profile(app_handle: AppHandle, name: String, reset_session: bool) -> Result<ProfileSwitch, String> {
+    let profile = profiles::resolve_profile(&app_handle, Some(&name))?;
+    let from = profiles::active_profile(&app_handle);
+    config::update(&app_handle, |c| c.active_profile = Some(profile.name.clone()))?;
+
+    if reset_session {
+        let stats = get_session_stats(app_handle.clone())?;
+        let message = format!("Session closed on switch from profile '{}' to '{}'", from, profile.name);
+        results::store(&app_handle, "session", "", None, true, &message, &stats);
+        start_new_session(app_handle.clone())?;
+    }
+
+    let switch = ProfileSwitch {
+        from,
+        to: profile.name,
+        session_reset: reset_session,
+    };
+    log::info!(
+        "Switched profile from '{}' to '{}'{}",
+        switch.from,
+        switch.to,
+        if reset_session { " and started a new session" } else { "" }
+    );
+    let _ = app_handle.emit("profile-switched", &switch);
+    Ok(switch)
+}
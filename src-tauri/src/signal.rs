@@ -0,0 +1,101 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_shell::ShellExt;
+
+use crate::config::{self, HookCommand};
+
+/// Payload of the "result-signal" event
+#[derive(Debug, Clone, Serialize)]
+struct ResultSignal<'a> {
+    port: &'a str,
+    pass: bool,
+}
+
+/// Announce a finished test so the UI can play a sound or flash the screen,
+/// and run the configured signal command if there is one
+pub fn signal_result(app_handle: &AppHandle, port: &str, pass: bool) {
+    let _ = app_handle.emit("result-signal", ResultSignal { port, pass });
+
+    let Some(hook) = config::current(app_handle).result_signal_command else {
+        return;
+    };
+    let outcome = if pass { "pass" } else { "fail" };
+    // Fire and forget: a slow or broken stack light must never hold up the result
+    let spawned = app_handle
+        .shell()
+        .command(&hook.program)
+        .args(&hook.args)
+        .arg(outcome)
+        .spawn();
+    if let Err(e) = spawned {
+        log::warn!("Result signal command '{}' failed to start: {}", hook.program, e);
+    }
+}
+
+/// Set (or clear, with None) the command run after each test with "pass" or "fail" appended
+#[tauri::command]
+pub fn set_result_signal_command(app_handle: AppHandle, command: Option<HookCommand>) -> Result<(), String> {
+    config::update(&app_handle, |c| c.result_signal_command = command)?;
+    Ok(())
+}
+
+/// Post a desktop notification when a flash or test finishes, if notify_on_complete is set.
+/// Uses the platform's own notifier (notify-send, osascript, PowerShell); if that is missing
+/// or notifications aren't permitted, nothing is shown and only a debug line is logged.
+pub fn notify_complete<T>(
+    app_handle: &AppHandle,
+    operation: &str,
+    port: &str,
+    result: &Result<T, String>,
+    summary: impl Fn(&T) -> (bool, &str),
+) {
+    if !config::current(app_handle).notify_on_complete {
+        return;
+    }
+    let (title, body) = match result {
+        Ok(value) => {
+            let (success, message) = summary(value);
+            let outcome = if success { "passed" } else { "failed" };
+            (format!("{} {} on {}", operation, outcome, port), message.to_string())
+        }
+        Err(e) => (format!("{} error on {}", operation, port), e.clone()),
+    };
+
+    let (program, args) = notification_command(&title, &body);
+    if let Err(e) = app_handle.shell().command(program).args(args).spawn() {
+        log::debug!("Desktop notification unavailable: {}", e);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn notification_command(title: &str, body: &str) -> (&'static str, Vec<String>) {
+    ("notify-send", vec![title.to_string(), body.to_string()])
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(title: &str, body: &str) -> (&'static str, Vec<String>) {
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let script = format!("display notification {} with title {}", quote(body), quote(title));
+    ("osascript", vec!["-e".to_string(), script])
+}
+
+#[cfg(windows)]
+fn notification_command(title: &str, body: &str) -> (&'static str, Vec<String>) {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; \
+         $n = New-Object System.Windows.Forms.NotifyIcon; \
+         $n.Icon = [System.Drawing.SystemIcons]::Information; $n.Visible = $true; \
+         $n.ShowBalloonTip(5000, {}, {}, 'Info'); Start-Sleep -Seconds 6; $n.Dispose()",
+        quote(title),
+        quote(body)
+    );
+    ("powershell", vec!["-NoProfile".to_string(), "-WindowStyle".to_string(), "Hidden".to_string(), "-Command".to_string(), script])
+}
+
+/// Turn desktop notifications at the end of flash_firmware and run_device_test on or off
+#[tauri::command]
+pub fn set_notify_on_complete(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    config::update(&app_handle, |c| c.notify_on_complete = enabled)?;
+    Ok(())
+}
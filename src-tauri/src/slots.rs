@@ -0,0 +1,42 @@
+use tauri::AppHandle;
+
+use crate::commands::{enumerate_ports, SerialPortInfo};
+use crate::config;
+
+/// Assign the USB adapter with `usb_serial_number` to fixture slot `slot_index`, replacing the
+/// slot's previous device. A serial number can only belong to one slot.
+#[tauri::command]
+pub fn register_slot(app_handle: AppHandle, slot_index: u32, usb_serial_number: String) -> Result<(), String> {
+    let serial_number = usb_serial_number.trim().to_string();
+    if serial_number.is_empty() {
+        return Err("USB serial number is empty".to_string());
+    }
+    let taken = config::current(&app_handle)
+        .slots
+        .into_iter()
+        .find(|(slot, serial)| *slot != slot_index && *serial == serial_number);
+    if let Some((slot, _)) = taken {
+        return Err(format!("{} is already registered to slot {}", serial_number, slot));
+    }
+    config::update(&app_handle, |c| {
+        c.slots.insert(slot_index, serial_number.clone());
+    })?;
+    log::info!("Slot {} registered to USB serial {}", slot_index, serial_number);
+    Ok(())
+}
+
+/// Find the port currently belonging to fixture slot `slot_index`, by the USB serial number
+/// registered with register_slot. Errors when the slot is unregistered or its device isn't plugged in.
+#[tauri::command]
+pub async fn resolve_slot_port(app_handle: AppHandle, slot_index: u32) -> Result<SerialPortInfo, String> {
+    let serial_number = config::current(&app_handle)
+        .slots
+        .remove(&slot_index)
+        .ok_or_else(|| format!("Slot {} is not registered", slot_index))?;
+    tokio::task::spawn_blocking(move || {
+        enumerate_ports()?
+            .into_iter()
+            .find(|p| p.serial_number.as_deref() == Some(serial_number.as_str()))
+            .ok_or_else(|| format!("Device for slot {} (USB serial {}) is not connected", slot_index, serial_number))
+    }).await.map_err(|e| format!("Task join error: {}", e))?
+}
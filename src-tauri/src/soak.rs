@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::{run_device_test, TestOptions};
+use crate::operations;
+use crate::results::now_millis;
+
+/// Pause after a run that couldn't start (e.g. the port vanished), so the loop doesn't spin
+const ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A soak run that failed, with everything needed to tell a fixture fault from a unit fault
+#[derive(Debug, Serialize)]
+pub struct SoakFailure {
+    /// 1-based run number
+    pub run: u32,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    pub message: String,
+    /// Raw device output of the run, boot log first; empty when the run couldn't start
+    pub log: Vec<String>,
+}
+
+/// Outcome of run_soak
+#[derive(Debug, Serialize)]
+pub struct SoakSummary {
+    pub runs: u32,
+    pub passed: u32,
+    pub failed: u32,
+    /// failed / runs, 0 when nothing ran
+    pub failure_rate: f64,
+    pub elapsed_secs: u64,
+    pub failures: Vec<SoakFailure>,
+    /// True when the soak was cancelled before `duration_secs` elapsed
+    pub cancelled: bool,
+}
+
+/// Payload of the "soak-progress" event, sent after each run
+#[derive(Debug, Clone, Serialize)]
+struct SoakProgress<'a> {
+    port: &'a str,
+    /// 1-based run just finished
+    run: u32,
+    passed: u32,
+    failed: u32,
+    success: bool,
+    elapsed_secs: u64,
+    duration_secs: u64,
+}
+
+/// Soak-test the station: re-test a known-good unit on `port` back to back until `duration_secs`
+/// have elapsed, counting passes and failures. Every failure is expected to come from the
+/// fixture, so runs that couldn't even start are counted too and the soak carries on.
+/// Each run hardware-resets the unit first and is stored as a "soak" result, outside yield and
+/// the session counters. Emits "soak-progress" after every run.
+#[tauri::command]
+pub async fn run_soak(
+    app_handle: AppHandle,
+    port: String,
+    duration_secs: u64,
+    options: Option<TestOptions>,
+) -> Result<SoakSummary, String> {
+    if duration_secs == 0 {
+        return Err("Soak duration must be at least one second".to_string());
+    }
+    let options = TestOptions {
+        reset_before_test: true,
+        // The golden unit passes every run by design
+        allow_duplicates: true,
+        record_kind: Some("soak"),
+        ..options.unwrap_or_default()
+    };
+
+    let mut operation = operations::begin(&app_handle, "soak", &port);
+    let control = operation.control();
+    let duration = Duration::from_secs(duration_secs);
+    let start = Instant::now();
+    let mut summary = SoakSummary {
+        runs: 0,
+        passed: 0,
+        failed: 0,
+        failure_rate: 0.0,
+        elapsed_secs: 0,
+        failures: Vec::new(),
+        cancelled: false,
+    };
+
+    while start.elapsed() < duration {
+        if control.is_cancelled() {
+            summary.cancelled = true;
+            break;
+        }
+        summary.runs += 1;
+        let success = match run_device_test(app_handle.clone(), port.clone(), Some(options.clone())).await {
+            Ok(result) if result.success => true,
+            Ok(result) => {
+                summary.failures.push(SoakFailure {
+                    run: summary.runs,
+                    timestamp: now_millis(),
+                    message: result.message,
+                    log: result.boot_log.into_iter().chain(result.events).collect(),
+                });
+                false
+            }
+            Err(e) => {
+                summary.failures.push(SoakFailure {
+                    run: summary.runs,
+                    timestamp: now_millis(),
+                    message: e,
+                    log: Vec::new(),
+                });
+                tokio::time::sleep(ERROR_BACKOFF).await;
+                false
+            }
+        };
+        if success {
+            summary.passed += 1;
+        } else {
+            summary.failed += 1;
+        }
+        let _ = app_handle.emit(
+            "soak-progress",
+            SoakProgress {
+                port: &port,
+                run: summary.runs,
+                passed: summary.passed,
+                failed: summary.failed,
+                success,
+                elapsed_secs: start.elapsed().as_secs(),
+                duration_secs,
+            },
+        );
+    }
+
+    summary.elapsed_secs = start.elapsed().as_secs();
+    if summary.runs > 0 {
+        summary.failure_rate = summary.failed as f64 / summary.runs as f64;
+    }
+    log::info!(
+        "Soak on {}: {} runs, {} failed over {}s",
+        port,
+        summary.runs,
+        summary.failed,
+        summary.elapsed_secs
+    );
+    let result = Ok(summary);
+    operation.finish(&result, |s| s.failed == 0 && !s.cancelled);
+    result
+}
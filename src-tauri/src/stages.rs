@@ -0,0 +1,118 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+/// Stages of a firmware flash, in the order they happen
+pub const FLASH_STAGES: [&str; 5] = ["Download", "Connect", "Erase", "Write", "Verify"];
+
+/// Payload of the "flash-stage" event
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashStage {
+    pub port: String,
+    pub stage: String,
+    /// 1-based position of the stage within `total`
+    pub index: u8,
+    pub total: u8,
+}
+
+/// Payload of the "flash-progress" event
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashProgress {
+    pub port: String,
+    pub percent: u8,
+}
+
+/// Structured line from the flasher script. The contract (see README):
+/// `PROGRESS:<0-100>` reports overall percent complete, and `STAGE:<name>` announces
+/// one of FLASH_STAGES (case-insensitive). Anything else is plain output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlasherMessage {
+    Progress(u8),
+    Stage(usize),
+}
+
+pub fn parse_flasher_message(line: &str) -> Option<FlasherMessage> {
+    let line = line.trim();
+    if let Some(percent) = line.strip_prefix("PROGRESS:") {
+        let percent: u8 = percent.trim().trim_end_matches('%').parse().ok()?;
+        (percent <= 100).then_some(FlasherMessage::Progress(percent))
+    } else if let Some(name) = line.strip_prefix("STAGE:") {
+        let name = name.trim();
+        FLASH_STAGES
+            .iter()
+            .position(|stage| stage.eq_ignore_ascii_case(name))
+            .map(FlasherMessage::Stage)
+    } else {
+        None
+    }
+}
+
+/// Map a line of flasher output to the stage it signals, if any.
+/// ncd_flasher's own [PROGRESS] banners are checked first, then esptool's sub-output.
+fn stage_for_line(line: &str) -> Option<usize> {
+    let line = line.trim();
+    if line.starts_with("[PROGRESS] Downloading") || line.starts_with("[PROGRESS] Building SPIFFS") {
+        Some(0)
+    } else if line.starts_with("[PROGRESS] Starting upload") || line.starts_with("Connecting...") {
+        Some(1)
+    } else if line.starts_with("Flash will be erased") || line.starts_with("Erasing flash") {
+        Some(2)
+    } else if line.starts_with("Compressed ") || line.starts_with("Writing at ") {
+        Some(3)
+    } else if line.starts_with("Hash of data verified") {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+/// Follows flasher output and emits "flash-stage" whenever a later stage begins.
+/// Stages only move forward, so esptool repeating erase/write per region doesn't flap the UI.
+#[derive(Debug, Default)]
+pub struct FlashStageTracker {
+    current: Option<usize>,
+}
+
+impl FlashStageTracker {
+    /// Follow a line of flasher output. Structured PROGRESS:/STAGE: lines are turned into
+    /// events and return true; other lines fall back to matching esptool's text and return false.
+    pub fn observe(&mut self, handle: &AppHandle, port: &str, line: &str) -> bool {
+        match parse_flasher_message(line) {
+            Some(FlasherMessage::Progress(percent)) => {
+                let _ = handle.emit(
+                    "flash-progress",
+                    FlashProgress {
+                        port: port.to_string(),
+                        percent,
+                    },
+                );
+                true
+            }
+            Some(FlasherMessage::Stage(stage)) => {
+                self.enter(handle, port, stage);
+                true
+            }
+            None => {
+                if let Some(stage) = stage_for_line(line) {
+                    self.enter(handle, port, stage);
+                }
+                false
+            }
+        }
+    }
+
+    fn enter(&mut self, handle: &AppHandle, port: &str, stage: usize) {
+        if self.current.is_some_and(|current| stage <= current) {
+            return;
+        }
+        self.current = Some(stage);
+        let _ = handle.emit(
+            "flash-stage",
+            FlashStage {
+                port: port.to_string(),
+                stage: FLASH_STAGES[stage].to_string(),
+                index: stage as u8 + 1,
+                total: FLASH_STAGES.len() as u8,
+            },
+        );
+    }
+}
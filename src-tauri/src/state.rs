@@ -0,0 +1,48 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::commands::SerialPortInfo;
+use crate::config::AppConfig;
+use crate::mirror::EventMirror;
+use crate::operations::{ActiveOperation, OperationLog, ProcessSlots};
+use crate::session_stats::SessionStats;
+use crate::versions::Versions;
+
+/// Process-wide state shared between commands, registered with `app.manage()`
+#[derive(Default)]
+pub struct AppState {
+    /// Persisted station settings, loaded at startup
+    pub config: Mutex<AppConfig>,
+    /// Where "*-output" events are mirrored for external tooling, if enabled
+    pub event_mirror: Mutex<Option<EventMirror>>,
+    /// Also forward every "*-output" line to the combined "console-stream" event
+    pub console_stream: AtomicBool,
+    /// MACs of units that passed this session, used to catch a unit being tested twice
+    pub seen_macs: Mutex<HashSet<String>>,
+    /// Counters for the current shift; saved with seen_macs to session.json
+    pub session_stats: Mutex<SessionStats>,
+    /// Results counted since session.json was last written
+    pub unsaved_results: AtomicU32,
+    /// Last successful port enumeration, served when the USB stack hiccups
+    pub last_ports: Mutex<Option<Vec<SerialPortInfo>>>,
+    /// Device operations currently in flight
+    pub operations: Mutex<Vec<ActiveOperation>>,
+    pub next_operation_id: AtomicU64,
+    /// Recent output per operation, kept until acknowledged after it finishes
+    pub operation_logs: Mutex<Vec<OperationLog>>,
+    /// When flash_firmware last started or finished per port, for the flash_debounce_ms window
+    pub last_flash: Mutex<HashMap<String, Instant>>,
+    /// Flasher/esptool processes running, capped by the max_processes setting
+    pub process_slots: ProcessSlots,
+    /// Chip family detected per port, so "auto" doesn't re-probe on every operation
+    pub detected_chips: Mutex<HashMap<String, String>>,
+    /// Serialises access to the results store file
+    pub results_lock: Mutex<()>,
+    pub next_result_id: AtomicU64,
+    /// Tool versions from the first get_versions call
+    pub versions: Mutex<Option<Versions>>,
+    /// Serial number from the last barcode scan, used by provision_device when none is given
+    pub pending_serial: Mutex<Option<String>>,
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter};
+
+use crate::mirror::emit_output;
+
+/// Flash size assumed for erase throughput when esptool doesn't report one;
+/// matches the `--flash_size 4MB` our flasher writes with
+pub const DEFAULT_FLASH_SIZE_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Throughput {
+    pub bytes: u64,
+    pub elapsed_ms: u64,
+    pub bytes_per_sec: f64,
+}
+
+/// Payload of the "throughput" event
+#[derive(Debug, Clone, Serialize)]
+struct ThroughputEvent<'a> {
+    operation: &'a str,
+    port: &'a str,
+    #[serde(flatten)]
+    throughput: &'a Throughput,
+}
+
+/// Times esptool's write/erase phases from its output, so the measurement
+/// excludes connection setup and image download
+#[derive(Debug, Default)]
+pub struct ThroughputMeter {
+    start: Option<Instant>,
+    end: Option<Instant>,
+    bytes: u64,
+}
+
+impl ThroughputMeter {
+    /// Feed a line of write_flash output ("Compressed ...", "Writing at ...", "Wrote N bytes ...")
+    pub fn observe_write(&mut self, line: &str) {
+        let line = line.trim_start();
+        if line.starts_with("Compressed ") || line.starts_with("Writing at ") {
+            self.start.get_or_insert_with(Instant::now);
+        } else if let Some(rest) = line.strip_prefix("Wrote ") {
+            if let Some(Ok(bytes)) = rest.split(' ').next().map(str::parse::<u64>) {
+                self.bytes += bytes;
+                self.end = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Feed a line of erase_flash output; a completed erase counts the whole flash
+    pub fn observe_erase(&mut self, line: &str, flash_size: u64) {
+        let line = line.trim_start();
+        if line.starts_with("Erasing flash") {
+            self.start.get_or_insert_with(Instant::now);
+        } else if line.starts_with("Chip erase completed") {
+            self.bytes = flash_size;
+            self.end = Some(Instant::now());
+        }
+    }
+
+    /// The measured throughput, if a complete write/erase phase was observed
+    pub fn finish(&self) -> Option<Throughput> {
+        let elapsed = self.end?.checked_duration_since(self.start?)?;
+        if self.bytes == 0 || elapsed.is_zero() {
+            return None;
+        }
+        Some(Throughput {
+            bytes: self.bytes,
+            elapsed_ms: elapsed.as_millis() as u64,
+            bytes_per_sec: self.bytes as f64 / elapsed.as_secs_f64(),
+        })
+    }
+}
+
+/// Parse a size reported by esptool such as "4MB" or "512KB"
+pub fn parse_flash_size(line: &str) -> Option<u64> {
+    let size = line.rsplit(' ').next()?.trim();
+    if let Some(mb) = size.strip_suffix("MB") {
+        mb.parse::<u64>().ok().map(|n| n * 1024 * 1024)
+    } else if let Some(kb) = size.strip_suffix("KB") {
+        kb.parse::<u64>().ok().map(|n| n * 1024)
+    } else {
+        None
+    }
+}
+
+/// Emit the structured "throughput" event plus a readable line on the operation's output channel
+pub fn report_throughput(handle: &AppHandle, channel: &str, port: &str, throughput: &Throughput) {
+    let _ = handle.emit(
+        "throughput",
+        ThroughputEvent {
+            operation: channel.strip_suffix("-output").unwrap_or(channel),
+            port,
+            throughput,
+        },
+    );
+    emit_output(
+        handle,
+        channel,
+        port,
+        &format!(
+            "[INFO] Throughput: {:.1} KB/s ({} bytes in {:.1}s)",
+            throughput.bytes_per_sec / 1024.0,
+            throughput.bytes,
+            throughput.elapsed_ms as f64 / 1000.0
+        ),
+    );
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+use std::fs;
+use std::process::Command;
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{get_flasher_path, python_command};
+use crate::esptool::resolve_esptool;
+use crate::state::AppState;
+
+/// Tool versions a station is running; None where a tool is missing or didn't report one
+#[derive(Debug, Clone, Serialize)]
+pub struct Versions {
+    pub app_version: String,
+    pub flasher_version: Option<String>,
+    pub esptool_version: Option<String>,
+    pub python_version: Option<String>,
+}
+
+/// Last non-empty line a command printed on stdout, or on stderr (older Pythons) if stdout was empty
+fn command_output(command: &mut Command) -> Option<String> {
+    let output = command.output().ok().filter(|o| o.status.success())?;
+    let last_line = |stream: &[u8]| {
+        String::from_utf8_lossy(stream)
+            .lines()
+            .map(str::trim)
+            .rfind(|l| !l.is_empty())
+            .map(str::to_string)
+    };
+    last_line(&output.stdout).or_else(|| last_line(&output.stderr))
+}
+
+/// A VERSION file beside ncd_flasher.py wins; otherwise ask the script
+pub(crate) fn flasher_version(app_handle: &AppHandle) -> Option<String> {
+    let script_path = get_flasher_path(app_handle).ok()?;
+    if let Ok(contents) = fs::read_to_string(script_path.with_file_name("VERSION")) {
+        let version = contents.trim();
+        if !version.is_empty() {
+            return Some(version.to_string());
+        }
+    }
+    let mut command = Command::new(python_command());
+    command.arg(&script_path).arg("--version");
+    if let Some(dir) = script_path.parent() {
+        command.current_dir(dir);
+    }
+    command_output(&mut command)
+}
+
+/// esptool's "version" command prints "esptool.py v4.5.1" followed by "4.5.1"
+pub(crate) fn esptool_version(app_handle: &AppHandle) -> Option<String> {
+    let esptool = resolve_esptool(app_handle).ok()?;
+    let line = command_output(esptool.command().arg("version"))?;
+    Some(line.trim_start_matches("esptool.py v").to_string())
+}
+
+pub(crate) fn python_version() -> Option<String> {
+    let line = command_output(Command::new(python_command()).arg("--version"))?;
+    Some(line.trim_start_matches("Python ").to_string())
+}
+
+/// Report the app, flasher, esptool and Python versions. Probed once and cached for the session.
+#[tauri::command]
+pub async fn get_versions(app_handle: AppHandle) -> Result<Versions, String> {
+    let state = app_handle.state::<AppState>();
+    if let Some(cached) = state.versions.lock().map_err(|e| e.to_string())?.clone() {
+        return Ok(cached);
+    }
+
+    let handle = app_handle.clone();
+    let versions = tokio::task::spawn_blocking(move || Versions {
+        app_version: handle.package_info().version.to_string(),
+        flasher_version: flasher_version(&handle),
+        esptool_version: esptool_version(&handle),
+        python_version: python_version(),
+    })
+    .await
+    .map_err(|e| format!("Task join error: {}", e))?;
+
+    *state.versions.lock().map_err(|e| e.to_string())? = Some(versions.clone());
+    Ok(versions)
+}
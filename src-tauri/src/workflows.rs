@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+use crate::commands::{flash_firmware, run_device_test, FlashResult, TestOptions, TestResult};
+use crate::esptool::{read_flash, write_flash_image, APP_PARTITION_OFFSET};
+use crate::mirror::emit_output;
+use crate::results::now_millis;
+
+/// Flash region backed up before flashing: the app partition (APP_PARTITION_OFFSET onwards)
+const APP_PARTITION_LENGTH: u32 = 0x100000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlashAndTestResult {
+    pub success: bool,
+    pub message: String,
+    pub flash: FlashResult,
+    /// None when the flash failed and the test never ran
+    pub test: Option<TestResult>,
+    /// True when the backed-up firmware was written back after a failure
+    pub restored: bool,
+}
+
+/// Temporary file for a port's app-partition backup
+fn backup_path(port: &str) -> PathBuf {
+    let port: String = port
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    std::env::temp_dir().join(format!("app-backup-{}-{}.bin", port, now_millis()))
+}
+
+/// Write the backup back and describe the outcome for the result message
+async fn restore_backup(app_handle: &AppHandle, port: &str, backup: &Path, failure: &str) -> (bool, String) {
+    emit_output(app_handle, "flash-output", port, "Restoring previous firmware...");
+    match write_flash_image(app_handle, port, APP_PARTITION_OFFSET, backup, "flash-output").await {
+        Ok(()) => (true, format!("{} — restored previous firmware", failure)),
+        Err(e) => (false, format!("{} — restoring previous firmware failed: {}", failure, e)),
+    }
+}
+
+/// Flash firmware, then run the production test. When the test reports no MAC, the eFuse MAC
+/// read during the flash identifies the unit (TestResult.mac_source is then "efuse").
+/// With `backup`, the app partition is read off the unit first and written back if the
+/// flash or the test fails, so a field-return unit is never left on broken firmware.
+#[tauri::command]
+pub async fn run_flash_and_test(
+    app_handle: AppHandle,
+    port: String,
+    firmware_id: u32,
+    options: Option<TestOptions>,
+    backup: Option<bool>,
+) -> Result<FlashAndTestResult, String> {
+    let backup = if backup.unwrap_or(false) {
+        let path = backup_path(&port);
+        emit_output(&app_handle, "flash-output", &port, "Backing up current firmware...");
+        read_flash(
+            app_handle.clone(),
+            port.clone(),
+            Some("auto".to_string()),
+            APP_PARTITION_OFFSET,
+            APP_PARTITION_LENGTH,
+            path.to_string_lossy().into_owned(),
+        )
+        .await
+        .map_err(|e| format!("Backup failed, not flashing: {}", e))?;
+        Some(path)
+    } else {
+        None
+    };
+
+    let flash = match flash_firmware(app_handle.clone(), port.clone(), firmware_id, None, None).await {
+        Ok(flash) => flash,
+        Err(e) => {
+            // Errors (missing flasher, cancellation) end the workflow without touching the unit again
+            if let Some(path) = &backup {
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(e);
+        }
+    };
+    let test = if flash.success {
+        // A unit that fails before BOOT_COMPLETE can still be traced by the eFuse MAC
+        let options = TestOptions {
+            fallback_mac: flash.detected_mac.clone(),
+            ..options.unwrap_or_default()
+        };
+        Some(run_device_test(app_handle.clone(), port.clone(), Some(options)).await)
+    } else {
+        None
+    };
+
+    let failure = match &test {
+        None => Some("Flash failed".to_string()),
+        Some(Ok(result)) if result.success => None,
+        Some(Ok(_)) => Some("Test failed".to_string()),
+        Some(Err(e)) => Some(format!("Test could not run ({})", e)),
+    };
+    let test = test.and_then(Result::ok);
+
+    let (success, message, restored) = match (failure, &backup) {
+        (None, _) => (true, "Flashed and tested successfully".to_string(), false),
+        (Some(failure), None) => (false, failure, false),
+        (Some(failure), Some(path)) => {
+            let (restored, message) = restore_backup(&app_handle, &port, path, &failure).await;
+            (false, message, restored)
+        }
+    };
+    if let Some(path) = &backup {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(FlashAndTestResult {
+        success,
+        message,
+        flash,
+        test,
+        restored,
+    })
+}
@@ -4,12 +4,78 @@ import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 export interface SerialPortInfo {
   name: string;
   port_type: string;
+  serial_number: string | null;
+  vid: number | null;
+  pid: number | null;
+}
+
+export interface Throughput {
+  bytes: number;
+  elapsed_ms: number;
+  bytes_per_sec: number;
+}
+
+export interface ThroughputEvent extends Throughput {
+  operation: string;
+  port: string;
+}
+
+export interface PortList {
+  ports: SerialPortInfo[];
+  stale: boolean;
+}
+
+export interface OperationInfo {
+  operation_id: string;
+  kind: "flash" | "test" | "reset" | "erase";
+  port: string;
+  started_at: number;
+}
+
+export interface FlashStage {
+  port: string;
+  stage: string;
+  index: number;
+  total: number;
+}
+
+export interface ReadFlashProgress {
+  port: string;
+  percent: number;
+}
+
+export interface FlashProgress {
+  port: string;
+  percent: number;
+}
+
+export interface ValidationResult {
+  valid: boolean;
+  size: number;
+  sha256: string | null;
+  reason: string | null;
 }
 
 export interface FlashResult {
   success: boolean;
   message: string;
   output: string;
+  throughput: Throughput | null;
+  detected_chip: string | null;
+  detected_mac: string | null;
+  /** "disconnected" when the USB link dropped mid-flash */
+  error_category: string | null;
+  /** Phase a failed flash stopped in */
+  failed_phase: "erase" | "flash" | "verify" | null;
+  /** flashIfNeeded only: "verified" when nothing needed writing, "flashed" otherwise */
+  path_taken: "verified" | "flashed" | null;
+}
+
+export interface TelemetryStats {
+  count: number;
+  min: number;
+  max: number;
+  mean: number;
 }
 
 export interface TestResult {
@@ -18,11 +84,342 @@ export interface TestResult {
   events: string[];
   firmware_version: string | null;
   mac_address: string | null;
+  sensor_readings: Record<string, number>;
+  device_attributes: Record<string, string>;
+  warnings: string[];
+  sensor_samples: number[];
+  reset_reason: string | null;
+  /** Coefficients reported on CAL_DONE */
+  calibration: Record<string, number> | null;
+  /** Line acknowledging post_pass_command, when one was sent */
+  post_pass_ack: string | null;
+  /** Summary per field of TELEMETRY events captured after SENSOR_OK */
+  telemetry: Record<string, TelemetryStats>;
+  /** Firmware build time from BOOT_COMPLETE's BUILD parameter (RFC 3339 UTC) */
+  build_time: string | null;
+  /** Times the test command was sent; more than 1 when the run was retried */
+  attempts: number;
+  quality: TestQuality;
+  /** When each event was first seen, in order */
+  event_times: EventTime[];
+  /** Boot output kept apart from events when capture_boot_log is set */
+  boot_log: string[];
+  /** Each required event as a timed step, in the order they passed, then those that didn't */
+  steps: TestStep[];
+  /** Internal temperature from BOOT_COMPLETE's temp_c */
+  boot_temp_c: number | null;
+  /** Where mac_address came from: "boot_event", or "efuse" when read while flashing */
+  mac_source: "boot_event" | "efuse" | null;
+  /** Outcome of the allowed_firmware check, when one was made */
+  firmware_check: FirmwareCheck | null;
+}
+
+export interface FirmwareCheck {
+  /** Version BOOT_COMPLETE reported; null when it reported none */
+  version: string | null;
+  approved: boolean;
+  /** The allowed_firmware entry it matched */
+  matched: string | null;
+}
+
+export interface TestStep {
+  /** Display name of the required event */
+  name: string;
+  passed: boolean;
+  /** Milliseconds since the test command was sent: when the previous step finished */
+  started_ms: number;
+  /** When the event arrived, or when the run ended for a step that didn't pass */
+  finished_ms: number;
+  /** The line that satisfied the step, or why it didn't pass */
+  detail: string | null;
+}
+
+export interface EventTime {
+  event: string;
+  /** Milliseconds since the test command was sent */
+  t_ms: number;
 }
 
+export type TestQuality = "clean_pass" | "pass_with_retry" | "pass_with_warnings" | "fail";
+
 export interface ResetResult {
   success: boolean;
   message: string;
+  throughput: Throughput | null;
+  detected_chip: string | null;
+}
+
+export type LineEnding = "crlf" | "lf" | "cr" | "none";
+
+/** A required event with the name and position the operator's checklist shows it under */
+export interface RequiredEventEntry {
+  event: string;
+  display_name?: string;
+  order?: number;
+}
+
+export interface TestProfile {
+  name: string;
+  description: string;
+  /** Event names, or "re:<regex>" to match the raw line (also for fail_fast_events, forbidden_events and warn_events) */
+  required_events: (string | RequiredEventEntry)[];
+  timeout_secs: number | null;
+  idle_timeout_secs: number | null;
+  baud: number | null;
+  command: string | null;
+  line_ending: LineEnding | null;
+  fail_fast_events: string[];
+  forbidden_events: string[];
+  warn_events: string[];
+}
+
+export interface Bounds {
+  min: number;
+  max: number;
+}
+
+export interface TestPrompt {
+  /** Text to look for in a line, or "re:<regex>" */
+  pattern: string;
+  /** Sent followed by the line ending; empty just presses Enter */
+  response?: string;
+}
+
+export interface TestOptions {
+  profile?: string;
+  log_port?: string;
+  sensor_bounds?: Record<string, Bounds>;
+  calibration_bounds?: Record<string, Bounds>;
+  min_sensor_samples?: number;
+  max_sample_variance?: number;
+  warn_events?: string[];
+  warn_bounds?: Record<string, Bounds>;
+  fail_on_power_fault?: boolean;
+  timeout_secs?: number;
+  reset_before_test?: boolean;
+  quick?: boolean;
+  allow_duplicates?: boolean;
+  wait_for_ready?: string;
+  batch_output?: boolean;
+  protocol?: "text" | "json";
+  json_event_field?: string;
+  /** Fail early once any single line repeats more than this many times */
+  max_line_repeats?: number;
+  /** Command sent only after a full pass (e.g. "LOCK"); the unit fails if it isn't acknowledged */
+  post_pass_command?: string;
+  /** Ack marker for post_pass_command (defaults to "[EVENT:<command>_OK") */
+  post_pass_ack?: string;
+  idle_timeout_secs?: number;
+  baud?: number;
+  command?: string;
+  line_ending?: LineEnding;
+  fail_fast_events?: string[];
+  /** Fail the unit whenever one appears, even after every required event (e.g. "re:^\\[ERROR\\]") */
+  forbidden_events?: string[];
+  /** After SENSOR_OK, keep reading TELEMETRY events this long and summarise them */
+  telemetry_secs?: number;
+  /** Oldest acceptable firmware build (RFC 3339); older or missing BUILD fails the unit */
+  min_build?: string;
+  /** After a pass, keep collecting output into events this long (default 0) */
+  post_success_capture_ms?: number;
+  /** Prompts answered whenever the firmware prints them, e.g. { pattern: "Press any key", response: "" } */
+  prompts?: TestPrompt[];
+  /** Cap on lines kept in events; the middle is elided, [EVENT:...] lines are always kept */
+  max_events?: number;
+  /** Keep output from before the test command (and until the first event) in boot_log, not events */
+  capture_boot_log?: boolean;
+  /** Resend the test command every few seconds, up to this many times, until the first event arrives */
+  command_retries?: number;
+  /** Operator running the test, written to the outbox file */
+  operator?: string;
+  /** Fail units whose BOOT_COMPLETE reports a temp_c above this */
+  max_boot_temp_c?: number;
+  /** eFuse MAC read while flashing, used when the test reports none (runFlashAndTest sets it) */
+  fallback_mac?: string;
+  /** Approved firmware versions ("*" is a wildcard, e.g. "2.3.*"); empty disables the check */
+  allowed_firmware?: string[];
+}
+
+export interface HookCommand {
+  program: string;
+  args: string[];
+}
+
+export interface UsbId {
+  vid: number;
+  pid: number | null;
+}
+
+export interface AppConfig {
+  result_signal_command: HookCommand | null;
+  flasher_path: string | null;
+  esptool_path: string | null;
+  python_path: string | null;
+  release_logging: boolean;
+  log_level: string | null;
+  port_allowlist: UsbId[] | null;
+  flash_reset: ResetConfig;
+  serial_pattern: string | null;
+  /** Post a desktop notification when a flash or test finishes */
+  notify_on_complete: boolean;
+  /** Flasher/esptool processes allowed at once (null means 4) */
+  max_processes: number | null;
+  /** Only warn when the flasher or esptool fail the integrity check, instead of refusing to run */
+  integrity_warn_only: boolean;
+  /** USB serial number registered to each fixture slot, keyed by slot index */
+  slots: Record<string, string>;
+  /** Drop folder receiving one JSON result file per finished test */
+  outbox_dir: string | null;
+  /** Station name in outbox files (null uses the host name) */
+  station_name: string | null;
+  /** Repeat flashes of a port within this many ms are rejected (null means 2000, 0 disables) */
+  flash_debounce_ms: number | null;
+  /** Profile used when a test doesn't name one (null means "full") */
+  active_profile: string | null;
+}
+
+export type ResetStrategy = "classic" | "unix1200bps" | "usb_reset" | "no_reset";
+
+export interface ResetConfig {
+  strategy: ResetStrategy;
+  /** Boot pin hold after the reset pulse; null keeps esptool's 50 ms */
+  reset_delay_ms: number | null;
+  /** How long esptool tries to sync with the bootloader; null keeps esptool's 7 attempts */
+  connect_timeout_secs: number | null;
+}
+
+export interface ResultSignal {
+  port: string;
+  pass: boolean;
+}
+
+export type MirrorTarget = { file: string } | { tcp: number };
+
+export interface BatchUnitResult {
+  port: string;
+  result: TestResult | null;
+  error: string | null;
+}
+
+export interface BatchResult {
+  results: BatchUnitResult[];
+  halted: boolean;
+}
+
+export interface FlashAndTestResult {
+  success: boolean;
+  message: string;
+  flash: FlashResult;
+  test: TestResult | null;
+  restored: boolean;
+}
+
+export interface BurninResult {
+  iterations: number;
+  passed: number;
+  failed: number;
+  results: TestResult[];
+  error: string | null;
+}
+
+export interface BurninProgress {
+  port: string;
+  iteration: number;
+  iterations: number;
+  passed: number;
+  failed: number;
+  success: boolean;
+}
+
+export interface SoakFailure {
+  /** 1-based run number */
+  run: number;
+  /** Unix time in milliseconds */
+  timestamp: number;
+  message: string;
+  /** Raw device output of the run; empty when it couldn't start */
+  log: string[];
+}
+
+export interface SoakSummary {
+  runs: number;
+  passed: number;
+  failed: number;
+  /** failed / runs */
+  failure_rate: number;
+  elapsed_secs: number;
+  failures: SoakFailure[];
+  cancelled: boolean;
+}
+
+export interface SoakProgress {
+  port: string;
+  run: number;
+  passed: number;
+  failed: number;
+  success: boolean;
+  elapsed_secs: number;
+  duration_secs: number;
+}
+
+export interface BatchHalted {
+  port: string;
+  message: string;
+  remaining: number;
+}
+
+export interface LatencyStats {
+  iterations: number;
+  lost: number;
+  min_ms: number;
+  avg_ms: number;
+  max_ms: number;
+  p95_ms: number;
+}
+
+export interface ResultRecord {
+  id: string;
+  /** Unix time in milliseconds */
+  timestamp: number;
+  kind: string;
+  port: string;
+  mac: string | null;
+  success: boolean;
+  message: string;
+  data: unknown;
+  /** Fields set later with updateUnitMetadata */
+  metadata?: Record<string, string>;
+  audit?: AuditEntry[];
+}
+
+export interface AuditEntry {
+  /** Unix time in milliseconds */
+  timestamp: number;
+  operator: string | null;
+  fields: Record<string, string>;
+}
+
+export interface FieldChange {
+  field: string;
+  a: unknown;
+  b: unknown;
+}
+
+export interface CheckFlip {
+  event: string;
+  label: string;
+  passed_a: boolean;
+  passed_b: boolean;
+}
+
+export interface TestDiff {
+  a: ResultRecord;
+  b: ResultRecord;
+  changed_fields: FieldChange[];
+  flipped_checks: CheckFlip[];
+  sensor_changes: Record<string, [number | null, number | null]>;
+  warnings_only_in_a: string[];
+  warnings_only_in_b: string[];
 }
 
 export interface FirmwareOption {
@@ -38,77 +435,1039 @@ export const FIRMWARE_OPTIONS: FirmwareOption[] = [
 
 /**
  * List all available serial ports
+ * `stale` is set when enumeration failed and the last known list was returned
+ */
+export async function listSerialPorts(): Promise<PortList> {
+  return invoke<PortList>("list_serial_ports");
+}
+
+/**
+ * Pick the port automatically when exactly one USB device matches the allowlist;
+ * rejects with the candidate count otherwise
+ */
+export async function autoSelectPort(): Promise<SerialPortInfo> {
+  return invoke<SerialPortInfo>("auto_select_port");
+}
+
+/**
+ * Set the USB ids autoSelectPort accepts (null restores the built-in list)
  */
-export async function listSerialPorts(): Promise<SerialPortInfo[]> {
-  return invoke<SerialPortInfo[]>("list_serial_ports");
+export async function setPortAllowlist(allowlist: UsbId[] | null): Promise<void> {
+  return invoke<void>("set_port_allowlist", { allowlist });
+}
+
+/**
+ * Register the USB adapter with `usbSerialNumber` (SerialPortInfo.serial_number) to a fixture slot
+ */
+export async function registerSlot(slotIndex: number, usbSerialNumber: string): Promise<void> {
+  return invoke<void>("register_slot", { slotIndex, usbSerialNumber });
+}
+
+/**
+ * Find the port of the device registered to a fixture slot; rejects when it isn't connected
+ */
+export async function resolveSlotPort(slotIndex: number): Promise<SerialPortInfo> {
+  return invoke<SerialPortInfo>("resolve_slot_port", { slotIndex });
+}
+
+export interface PortReleaseResult {
+  /** True once the port could be opened */
+  released: boolean;
+  message: string;
+  /** What was tried, in order */
+  steps: string[];
+}
+
+/**
+ * Try to recover a port left locked ("Access is denied"): cancels this app's operations on it,
+ * restarts the device on Windows where permitted, then retries the open with backoff.
+ * When `released` is false the device needs a physical replug. Emits "port-output" lines
+ */
+export async function forceReleasePort(port: string): Promise<PortReleaseResult> {
+  return invoke<PortReleaseResult>("force_release_port", { port });
 }
 
 /**
  * Flash firmware to the device
+ * With `eraseFirst`, the whole flash is erased first; a failed erase stops before flashing.
+ * `connectTimeoutSecs` overrides the configured bootloader sync time
  */
 export async function flashFirmware(
   port: string,
-  firmwareId: number
+  firmwareId: number,
+  eraseFirst?: boolean,
+  connectTimeoutSecs?: number
 ): Promise<FlashResult> {
   return invoke<FlashResult>("flash_firmware", {
     port,
     firmwareId,
+    eraseFirst,
+    connectTimeoutSecs,
   });
 }
 
+/**
+ * Set how long after a flash starts or finishes a repeat flash of the same port is rejected
+ * (null restores 2 seconds, 0 turns the check off)
+ */
+export async function setFlashDebounce(windowMs: number | null): Promise<void> {
+  return invoke<void>("set_flash_debounce", { windowMs });
+}
+
+/**
+ * Verify `firmware` (a .bin path) against the device and only flash it if it differs,
+ * verifying again afterwards. `offset` defaults to 0x10000, the app partition
+ */
+export async function flashIfNeeded(port: string, firmware: string, offset?: number): Promise<FlashResult> {
+  return invoke<FlashResult>("flash_if_needed", { port, firmware, offset });
+}
+
+export interface CustomFlashResult {
+  success: boolean;
+  message: string;
+  bytes_sent: number;
+  chunks: number;
+  /** Chunks resent after a NAK or missing ACK */
+  retries: number;
+}
+
+/**
+ * Flash an image through the custom serial bootloader (UPDATE/READY, CHUNK/ACK, DONE)
+ * instead of esptool. `chunkSize` defaults to 1024 bytes, `baud` to 115200
+ */
+export async function flashCustomProtocol(
+  port: string,
+  binPath: string,
+  chunkSize?: number,
+  baud?: number
+): Promise<CustomFlashResult> {
+  return invoke<CustomFlashResult>("flash_custom_protocol", { port, binPath, chunkSize, baud });
+}
+
+/**
+ * List the available production test profiles: built-in ones plus test_profiles.json
+ * Rejects with every field error when test_profiles.json is invalid
+ */
+export async function listTestProfiles(): Promise<TestProfile[]> {
+  return invoke<TestProfile[]>("list_test_profiles");
+}
+
+export interface ChecklistItem {
+  event: string;
+  display_name: string;
+  order: number | null;
+  required: boolean;
+}
+
+export interface ProfileDescription {
+  name: string;
+  description: string;
+  /** Required events in checklist order */
+  checklist: ChecklistItem[];
+  fail_fast_events: string[];
+  forbidden_events: string[];
+  warn_events: string[];
+  timeout_secs: number;
+  idle_timeout_secs: number | null;
+  baud: number;
+  /** Command that starts the test, without line ending */
+  command: string;
+  line_ending: LineEnding;
+}
+
+/**
+ * Preview what a profile (default: the active one) will check and with which settings, without a device
+ */
+export async function describeProfile(name?: string): Promise<ProfileDescription> {
+  return invoke<ProfileDescription>("describe_profile", { name });
+}
+
 /**
  * Run production test on the device
  */
-export async function runDeviceTest(port: string): Promise<TestResult> {
-  return invoke<TestResult>("run_device_test", { port });
+export async function runDeviceTest(port: string, options?: TestOptions): Promise<TestResult> {
+  return invoke<TestResult>("run_device_test", { port, options });
+}
+
+/**
+ * Run the production test on each port in turn, optionally halting at the first failure
+ */
+export async function runBatchTest(
+  ports: string[],
+  options?: TestOptions,
+  stopOnFailure?: boolean
+): Promise<BatchResult> {
+  return invoke<BatchResult>("run_batch_test", { ports, options, stopOnFailure });
+}
+
+/**
+ * Flash firmware then run the production test
+ * With `backup`, the current app image is saved first and restored if the flash or test fails
+ */
+export async function runFlashAndTest(
+  port: string,
+  firmwareId: number,
+  options?: TestOptions,
+  backup?: boolean
+): Promise<FlashAndTestResult> {
+  return invoke<FlashAndTestResult>("run_flash_and_test", { port, firmwareId, options, backup });
+}
+
+/**
+ * Reset and re-test one unit `iterations` times; stops at the first failure unless stopOnFailure is false
+ */
+export async function runBurninTest(
+  port: string,
+  iterations: number,
+  timeoutSecs?: number,
+  options?: TestOptions,
+  stopOnFailure?: boolean
+): Promise<BurninResult> {
+  return invoke<BurninResult>("run_burnin_test", { port, iterations, timeoutSecs, options, stopOnFailure });
+}
+
+/**
+ * Soak-test the station by re-testing a golden unit until `durationSecs` elapse;
+ * resolves to pass/fail counts and every failure's log
+ */
+export async function runSoak(port: string, durationSecs: number, options?: TestOptions): Promise<SoakSummary> {
+  return invoke<SoakSummary>("run_soak", { port, durationSecs, options });
+}
+
+/**
+ * Clear the session's duplicate-MAC set; resolves to the number of MACs removed
+ */
+export async function clearSessionMacs(): Promise<number> {
+  return invoke<number>("clear_session_macs");
+}
+
+export interface PassFail {
+  passed: number;
+  failed: number;
+}
+
+export interface SessionStats {
+  /** Local day the session started, YYYY-MM-DD */
+  date: string;
+  /** Unix ms */
+  started_at: number;
+  /** Counts per result kind ("test", "flash", ...) */
+  counts: Record<string, PassFail>;
+}
+
+/**
+ * Counters for the current session; restored after a restart on the same day
+ */
+export async function getSessionStats(): Promise<SessionStats> {
+  return invoke<SessionStats>("get_session_stats");
+}
+
+/**
+ * Start a fresh session for a new shift: zero the counters and clear the duplicate-MAC set
+ */
+export async function startNewSession(): Promise<SessionStats> {
+  return invoke<SessionStats>("start_new_session");
+}
+
+export interface ProfileSwitch {
+  from: string;
+  to: string;
+  /** True when the session stats were archived and a new session started */
+  session_reset: boolean;
+}
+
+/**
+ * Make `name` the profile tests use by default; with `resetSession`, archive the current
+ * session stats to the results store and start a new session
+ */
+export async function switchProfile(name: string, resetSession: boolean): Promise<ProfileSwitch> {
+  return invoke<ProfileSwitch>("switch_profile", { name, resetSession });
+}
+
+/**
+ * Replay a captured serial log through the test parser (emits "test-output" events)
+ */
+export async function replayTestLog(
+  path: string,
+  options?: TestOptions,
+  lineDelayMs?: number
+): Promise<TestResult> {
+  return invoke<TestResult>("replay_test_log", { path, options, lineDelayMs });
 }
 
 /**
  * Factory reset the device
+ * `waitForReady` is a prompt to wait for before sending the command;
+ * `waitForReboot` also requires BOOT_COMPLETE after the reset for success;
+ * `verifyCleared` resets the device again and checks GET_INFO reports `expectedDefaults`
+ * (default SERIAL and SSID empty), failing with whatever persisted
  */
-export async function factoryReset(port: string): Promise<ResetResult> {
-  return invoke<ResetResult>("factory_reset", { port });
+export async function factoryReset(
+  port: string,
+  waitForReady?: string,
+  waitForReboot?: boolean,
+  verifyCleared?: boolean,
+  expectedDefaults?: Record<string, string>
+): Promise<ResetResult> {
+  return invoke<ResetResult>("factory_reset", {
+    port,
+    waitForReady,
+    waitForReboot,
+    verifyCleared,
+    expectedDefaults,
+  });
 }
 
 /**
  * Erase device flash
+ * `chip` is an esptool --chip value (default "esp32"), or "auto" to detect it;
+ * `connectTimeoutSecs` overrides the configured bootloader sync time
  */
-export async function eraseDevice(port: string): Promise<ResetResult> {
-  return invoke<ResetResult>("erase_device", { port });
+export async function eraseDevice(port: string, chip?: string, connectTimeoutSecs?: number): Promise<ResetResult> {
+  return invoke<ResetResult>("erase_device", { port, chip, connectTimeoutSecs });
 }
 
 /**
- * Listen for factory reset output events (real-time progress)
+ * Use a specific esptool (esptool.py or standalone binary); null restores the default search
  */
-export async function onResetOutput(callback: (line: string) => void): Promise<UnlistenFn> {
-  return listen<string>("reset-output", (event) => {
-    callback(event.payload);
-  });
+export async function setEsptoolPath(path: string | null): Promise<void> {
+  return invoke<void>("set_esptool_path", { path });
 }
 
 /**
- * Listen for erase output events (real-time progress)
+ * Set the reset strategy and timing used when flashing and erasing
  */
-export async function onEraseOutput(callback: (line: string) => void): Promise<UnlistenFn> {
-  return listen<string>("erase-output", (event) => {
-    callback(event.payload);
-  });
+export async function setFlashReset(reset: ResetConfig): Promise<void> {
+  return invoke<void>("set_flash_reset", { reset });
 }
 
 /**
- * Listen for flash output events (real-time progress)
+ * Dump `length` bytes of flash from `offset` to `outPath`; returns the bytes written.
+ * The dump only replaces `outPath` once complete, so an existing file survives a failed read.
+ * `chip` works as in eraseDevice
  */
-export async function onFlashOutput(callback: (line: string) => void): Promise<UnlistenFn> {
-  return listen<string>("flash-output", (event) => {
-    callback(event.payload);
-  });
+export async function readFlash(
+  port: string,
+  chip: string | undefined,
+  offset: number,
+  length: number,
+  outPath: string
+): Promise<number> {
+  return invoke<number>("read_flash", { port, chip, offset, length, outPath });
 }
 
 /**
- * Listen for test output events (real-time progress)
+ * Read the config stored on the device (GET_CONFIG), parsed from JSON or key=value lines
  */
-export async function onTestOutput(callback: (line: string) => void): Promise<UnlistenFn> {
-  return listen<string>("test-output", (event) => {
+export async function readDeviceConfig(port: string): Promise<Record<string, unknown>> {
+  return invoke<Record<string, unknown>>("read_device_config", { port });
+}
+
+export interface ProvisionResult {
+  success: boolean;
+  message: string;
+  serial: string;
+}
+
+/**
+ * Set the regex scanned serial numbers must match; null restores the default
+ */
+export async function setSerialPattern(pattern: string | null): Promise<void> {
+  return invoke<void>("set_serial_pattern", { pattern });
+}
+
+/**
+ * Validate a scanned serial number and hold it for the next provisionDevice
+ */
+export async function acceptScannedSerial(serial: string): Promise<string> {
+  return invoke<string>("accept_scanned_serial", { serial });
+}
+
+/**
+ * Write a serial number to the device; without `serial` the last scanned one is used
+ */
+export async function provisionDevice(port: string, serial?: string): Promise<ProvisionResult> {
+  return invoke<ProvisionResult>("provision_device", { port, serial });
+}
+
+export interface TimeSetResult {
+  success: boolean;
+  message: string;
+  epoch_secs: number;
+  /** Time the device reported back, when verified */
+  device_epoch_secs: number | null;
+}
+
+/**
+ * Set the device clock (defaults to the host's current time); `verify` reads it back with GET_TIME
+ */
+export async function setDeviceTime(port: string, epochSecs?: number, verify?: boolean): Promise<TimeSetResult> {
+  return invoke<TimeSetResult>("set_device_time", { port, epochSecs, verify });
+}
+
+/**
+ * Confirm the device on `port` still has `expectedMac` before an erase or flash, since a
+ * recycled COM number can point at a different unit after a replug. Resolves with the MAC
+ * read from the device; rejects with "Port COMx now points to a different device (MAC mismatch)..."
+ */
+export async function verifyDeviceIdentity(port: string, expectedMac: string): Promise<string> {
+  return invoke<string>("verify_device_identity", { port, expectedMac });
+}
+
+/**
+ * Check a local firmware file (exists, non-empty, optional SHA-256, ESP32 image magic) before flashing
+ * `appImage` defaults to true for .bin files
+ */
+export async function validateFirmware(
+  path: string,
+  expectedSha256?: string,
+  appImage?: boolean
+): Promise<ValidationResult> {
+  return invoke<ValidationResult>("validate_firmware", { path, expectedSha256, appImage });
+}
+
+/**
+ * Time PING/PONG round trips on a port (default 20 iterations)
+ */
+export async function measureLatency(port: string, iterations?: number): Promise<LatencyStats> {
+  return invoke<LatencyStats>("measure_latency", { port, iterations });
+}
+
+export interface LoopbackResult {
+  success: boolean;
+  message: string;
+  baud: number;
+  round_trip_ms: number | null;
+}
+
+/**
+ * Fixture self-test: with TX shorted to RX, write a known pattern and expect it back (default 115200 baud)
+ */
+export async function testLoopback(port: string, baud?: number): Promise<LoopbackResult> {
+  return invoke<LoopbackResult>("test_loopback", { port, baud });
+}
+
+export interface MqttCheck {
+  reachable: boolean;
+  latency_ms: number | null;
+  /** CONNACK return code when mqttConnect was set (0 = accepted) */
+  connack_code: number | null;
+  error: string | null;
+}
+
+/**
+ * Check the MQTT broker is reachable from this station (default port 1883)
+ * With `mqttConnect`, also sends an MQTT CONNECT and reports the CONNACK code
+ */
+export async function checkMqtt(host: string, port?: number, mqttConnect?: boolean): Promise<MqttCheck> {
+  return invoke<MqttCheck>("check_mqtt", { host, port, mqttConnect });
+}
+
+/**
+ * Use a specific ncd_flasher.py instead of the bundled one (null restores the default)
+ */
+export async function setFlasherPath(path: string | null): Promise<void> {
+  return invoke<void>("set_flasher_path", { path });
+}
+
+/**
+ * Use a specific Python interpreter for flashing and esptool.py (null restores python3/python on PATH).
+ * Fails if the interpreter doesn't run; otherwise returns the version it reports
+ */
+export async function setPythonPath(path: string | null): Promise<string | null> {
+  return invoke<string | null>("set_python_path", { path });
+}
+
+/**
+ * List the device operations currently running (for re-syncing UI state after a reload)
+ */
+export async function getActiveOperations(): Promise<OperationInfo[]> {
+  return invoke<OperationInfo[]>("get_active_operations");
+}
+
+/**
+ * Emergency stop: cancel every running operation on every port; returns how many were cancelled
+ */
+export async function cancelAllOperations(): Promise<number> {
+  return invoke<number>("cancel_all_operations");
+}
+
+/**
+ * Cap how many flasher/esptool processes run at once; further flashes and erases queue
+ * with "Queued — waiting for a flash slot". Pass null to restore the default of 4
+ */
+export async function setMaxProcesses(limit: number | null): Promise<void> {
+  return invoke<void>("set_max_processes", { limit });
+}
+
+export interface OperationLog {
+  info: OperationInfo;
+  /** Most recent output lines (up to 500), oldest first */
+  lines: string[];
+  /** Older lines that no longer fit */
+  dropped: number;
+  finished: boolean;
+  outcome: "pass" | "fail" | "error" | null;
+}
+
+/**
+ * Recent output of an operation, e.g. to re-sync the UI after a reload
+ */
+export async function getOperationLog(operationId: string): Promise<OperationLog> {
+  return invoke<OperationLog>("get_operation_log", { operationId });
+}
+
+/**
+ * Discard a finished operation's log once its outcome has been shown; false if it is still running
+ */
+export async function acknowledgeOperationLog(operationId: string): Promise<boolean> {
+  return invoke<boolean>("acknowledge_operation_log", { operationId });
+}
+
+/**
+ * Get the station config
+ */
+export async function getConfig(): Promise<AppConfig> {
+  return invoke<AppConfig>("get_config");
+}
+
+/**
+ * Write the station config and custom test profiles to a JSON file for importSettings on another station
+ */
+export async function exportSettings(path: string): Promise<void> {
+  return invoke("export_settings", { path });
+}
+
+export interface ImportReport {
+  /** Config fields applied, plus "profiles" when the test profiles were replaced */
+  applied: string[];
+  /** Everything left as it was, each with the reason */
+  rejected: string[];
+}
+
+/**
+ * Apply a settings file from exportSettings. Fields that aren't valid on this station
+ * (e.g. a missing esptool path) are skipped and listed in `rejected`
+ */
+export async function importSettings(path: string): Promise<ImportReport> {
+  return invoke<ImportReport>("import_settings", { path });
+}
+
+export interface Versions {
+  app_version: string;
+  flasher_version: string | null;
+  esptool_version: string | null;
+  python_version: string | null;
+}
+
+/**
+ * Get the app, flasher, esptool and Python versions (cached after the first call)
+ */
+export async function getVersions(): Promise<Versions> {
+  return invoke<Versions>("get_versions");
+}
+
+export interface Capabilities {
+  python: boolean;
+  /** flashFirmware (needs Python and ncd_flasher.py) */
+  catalog_flash: boolean;
+  /** eraseDevice, readFlash and backup restore */
+  esptool: boolean;
+  /** Serial test, reset and config commands */
+  serial: boolean;
+  /** Why each unavailable capability is missing */
+  reasons: string[];
+}
+
+/**
+ * Report which features this station can run, so unavailable ones can be grayed out
+ */
+export async function getCapabilities(): Promise<Capabilities> {
+  return invoke<Capabilities>("get_capabilities");
+}
+
+export interface SelfTestCheck {
+  name: string;
+  passed: boolean;
+  /** What was found, or why the check failed */
+  detail: string;
+  /** What to do about a failed check */
+  remediation: string | null;
+}
+
+export interface SelfTestReport {
+  passed: boolean;
+  checks: SelfTestCheck[];
+}
+
+/**
+ * Check the station's toolchain without a device (Python, ncd_flasher.py, esptool,
+ * results store, serial enumeration), for bringing up a new station
+ */
+export async function runSelfTest(): Promise<SelfTestReport> {
+  return invoke<SelfTestReport>("run_self_test");
+}
+
+export interface IntegrityCheck {
+  path: string;
+  /** SHA-256 of the copy bundled with the app */
+  expected: string;
+  /** SHA-256 of the file on disk; null when it couldn't be found or read */
+  actual: string | null;
+  ok: boolean;
+}
+
+/**
+ * Compare ncd_flasher.py and esptool against the copies bundled with the app.
+ * Flashing and erasing refuse to run when a check fails, unless warn-only is set.
+ */
+export async function verifyToolingIntegrity(): Promise<IntegrityCheck[]> {
+  return invoke<IntegrityCheck[]>("verify_tooling_integrity");
+}
+
+/**
+ * Only log a warning when the tooling fails the integrity check (for development)
+ */
+export async function setIntegrityWarnOnly(enabled: boolean): Promise<void> {
+  return invoke<void>("set_integrity_warn_only", { enabled });
+}
+
+/**
+ * Change the app log level ("off", "error", "warn", "info", "debug" or "trace"); persisted
+ */
+export async function setLogLevel(level: string): Promise<void> {
+  return invoke<void>("set_log_level", { level });
+}
+
+/**
+ * Get the current app log level
+ */
+export async function getLogLevel(): Promise<string> {
+  return invoke<string>("get_log_level");
+}
+
+/**
+ * Set (or clear) the command run after each test with "pass" or "fail" appended
+ */
+export async function setResultSignalCommand(command: HookCommand | null): Promise<void> {
+  return invoke<void>("set_result_signal_command", { command });
+}
+
+/**
+ * Turn desktop notifications at the end of flashes and tests on or off (off by default)
+ */
+export async function setNotifyOnComplete(enabled: boolean): Promise<void> {
+  return invoke<void>("set_notify_on_complete", { enabled });
+}
+
+/**
+ * Write a JSON file per finished test (named by MAC and time) into `path` for an external
+ * uploader; null stops writing them. The directory is created if missing
+ */
+export async function setOutboxDir(path: string | null): Promise<void> {
+  return invoke<void>("set_outbox_dir", { path });
+}
+
+/**
+ * Name this station in outbox files (null uses the host name)
+ */
+export async function setStationName(name: string | null): Promise<void> {
+  return invoke<void>("set_station_name", { name });
+}
+
+/**
+ * Mirror all "*-output" events to a newline-delimited JSON file or local TCP socket
+ */
+export async function enableEventMirror(target: MirrorTarget): Promise<void> {
+  return invoke<void>("enable_event_mirror", { target });
+}
+
+/**
+ * Stop mirroring events
+ */
+export async function disableEventMirror(): Promise<void> {
+  return invoke<void>("disable_event_mirror");
+}
+
+/**
+ * Get the active event mirror target, if any
+ */
+export async function getEventMirror(): Promise<MirrorTarget | null> {
+  return invoke<MirrorTarget | null>("get_event_mirror");
+}
+
+/**
+ * Turn the combined "console-stream" event (every output line from every port) on or off
+ */
+export async function setConsoleStream(enabled: boolean): Promise<void> {
+  return invoke<void>("set_console_stream", { enabled });
+}
+
+/**
+ * Write every stored result for a unit (by MAC or port) to a JSON file; returns the path written
+ */
+export async function exportUnitReport(macOrPort: string, path: string): Promise<string> {
+  return invoke<string>("export_unit_report", { macOrPort, path });
+}
+
+/**
+ * Compare two stored test runs by result id
+ */
+export async function diffTestResults(idA: string, idB: string): Promise<TestDiff> {
+  return invoke<TestDiff>("diff_test_results", { idA, idB });
+}
+
+export interface TimelineEntry {
+  event_name: string;
+  /** Milliseconds since the test command was sent */
+  t_ms: number;
+  delta_from_prev_ms: number;
+}
+
+/**
+ * Each event of a stored test run with its time since the test command and since the
+ * previous event, for a waterfall chart. Rejects for results recorded without event times
+ */
+export async function getEventTimeline(resultId: string): Promise<TimelineEntry[]> {
+  return invoke<TimelineEntry[]>("get_event_timeline", { resultId });
+}
+
+export interface UnitCertificate {
+  mac: string;
+  passed: boolean;
+  record_id: string;
+  text: string;
+  path: string | null;
+}
+
+/**
+ * Render the unit's latest test result as a text certificate, optionally written to `path`
+ */
+export async function finalizeUnit(mac: string, operator?: string, path?: string): Promise<UnitCertificate> {
+  return invoke<UnitCertificate>("finalize_unit", { mac, operator, path });
+}
+
+/**
+ * Merge metadata (new serial, batch, rework note, ...) into the unit's latest stored result
+ * and append an audit entry; returns the updated record
+ */
+export async function updateUnitMetadata(
+  mac: string,
+  fields: Record<string, string>,
+  operator?: string
+): Promise<ResultRecord> {
+  return invoke<ResultRecord>("update_unit_metadata", { mac, fields, operator });
+}
+
+export interface ResultCounts {
+  total: number;
+  passed: number;
+  failed: number;
+  /** Unix ms of the oldest and newest records */
+  oldest: number | null;
+  newest: number | null;
+}
+
+/**
+ * Count the records in the results store
+ */
+export async function countResults(): Promise<ResultCounts> {
+  return invoke<ResultCounts>("count_results");
+}
+
+/**
+ * Delete stored results older than `before` (Unix ms); returns how many were deleted
+ */
+export async function pruneResults(before: number): Promise<number> {
+  return invoke<number>("prune_results", { before });
+}
+
+/**
+ * Move stored results older than `before` (Unix ms) into a new zip at `path` (refused if it exists); returns how many were archived
+ */
+export async function archiveResults(path: string, before: number): Promise<number> {
+  return invoke<number>("archive_results", { path, before });
+}
+
+export interface YieldStats {
+  tests: number;
+  passed_tests: number;
+  /** Distinct MAC addresses tested */
+  units: number;
+  first_pass_units: number;
+  passed_units: number;
+  /** Tests that never reported a MAC */
+  unidentified_tests: number;
+  /** 0-1 */
+  first_pass_yield: number;
+  /** 0-1, retests included */
+  final_yield: number;
+}
+
+export interface DailyYield extends YieldStats {
+  /** YYYY-MM-DD (UTC) */
+  date: string;
+}
+
+export interface YieldReport extends YieldStats {
+  since: number | null;
+  until: number | null;
+  days: DailyYield[];
+}
+
+/**
+ * Compute first-pass and final yield from stored test results between `since` and `until` (Unix ms)
+ */
+export async function getYield(since?: number, until?: number): Promise<YieldReport> {
+  return invoke<YieldReport>("get_yield", { since, until });
+}
+
+/**
+ * Write the per-day yield breakdown to a CSV file; returns the path written
+ */
+export async function exportYieldCsv(path: string, since?: number, until?: number): Promise<string> {
+  return invoke<string>("export_yield_csv", { path, since, until });
+}
+
+/**
+ * Listen for factory reset output events (real-time progress)
+ */
+export async function onResetOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("reset-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for erase output events (real-time progress)
+ */
+export async function onEraseOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("erase-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for flash output events (real-time progress)
+ */
+export async function onFlashOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("flash-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/** A line a tool wrote to stderr; it also arrives on the combined output channel */
+export interface StderrLine {
+  port: string;
+  line: string;
+  stream: "stderr";
+}
+
+/**
+ * Listen for stderr lines from esptool or the flasher script, e.g. to show them in red
+ */
+export async function onStderrOutput(
+  channel: "flash-output" | "erase-output" | "read-flash-output",
+  callback: (line: StderrLine) => void
+): Promise<UnlistenFn> {
+  return listen<StderrLine>(`${channel}-err`, (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for test output events (real-time progress)
+ */
+export async function onTestOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("test-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for batched raw test output (sent instead of per-line events when batch_output is set)
+ */
+export async function onTestOutputBatch(callback: (lines: string[]) => void): Promise<UnlistenFn> {
+  return listen<string[]>("test-output-batch", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for flash/erase throughput measurements
+ */
+export async function onThroughput(callback: (event: ThroughputEvent) => void): Promise<UnlistenFn> {
+  return listen<ThroughputEvent>("throughput", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for the pass/fail signal at the end of each device test
+ */
+export async function onResultSignal(callback: (signal: ResultSignal) => void): Promise<UnlistenFn> {
+  return listen<ResultSignal>("result-signal", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for flash stage transitions ("Step 2 of 5: Connect")
+ */
+export async function onFlashStage(callback: (stage: FlashStage) => void): Promise<UnlistenFn> {
+  return listen<FlashStage>("flash-stage", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for a batch halting early on a failed unit
+ */
+export async function onBatchHalted(callback: (halted: BatchHalted) => void): Promise<UnlistenFn> {
+  return listen<BatchHalted>("batch-halted", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for read_flash output lines
+ */
+export async function onReadFlashOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("read-flash-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for read_flash progress percentages
+ */
+export async function onReadFlashProgress(callback: (progress: ReadFlashProgress) => void): Promise<UnlistenFn> {
+  return listen<ReadFlashProgress>("read-flash-progress", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for cancel_all_operations; the payload is the number of operations cancelled
+ */
+export async function onAllCancelled(callback: (count: number) => void): Promise<UnlistenFn> {
+  return listen<number>("all-cancelled", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for burn-in progress after each iteration
+ */
+export async function onBurninProgress(callback: (progress: BurninProgress) => void): Promise<UnlistenFn> {
+  return listen<BurninProgress>("burnin-progress", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for soak-test progress after each run
+ */
+export async function onSoakProgress(callback: (progress: SoakProgress) => void): Promise<UnlistenFn> {
+  return listen<SoakProgress>("soak-progress", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for read_device_config output (secrets masked)
+ */
+export async function onConfigOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("config-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for overall flash progress reported by the flasher's PROGRESS: lines
+ */
+export async function onFlashProgress(callback: (progress: FlashProgress) => void): Promise<UnlistenFn> {
+  return listen<FlashProgress>("flash-progress", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for barcode scans accepted by acceptScannedSerial
+ */
+export async function onSerialScanned(callback: (serial: string) => void): Promise<UnlistenFn> {
+  return listen<{ serial: string }>("serial-scanned", (event) => {
+    callback(event.payload.serial);
+  });
+}
+
+/**
+ * Listen for provision_device output (real-time progress)
+ */
+export async function onProvisionOutput(callback: (line: string) => void): Promise<UnlistenFn> {
+  return listen<string>("provision-output", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for finalized units, e.g. to capture the result panel alongside the text certificate
+ */
+export async function onUnitFinalized(callback: (certificate: UnitCertificate) => void): Promise<UnlistenFn> {
+  return listen<UnitCertificate>("unit-finalized", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for a new session being started
+ */
+export async function onSessionStarted(callback: (stats: SessionStats) => void): Promise<UnlistenFn> {
+  return listen<SessionStats>("session-started", (event) => {
+    callback(event.payload);
+  });
+}
+
+/**
+ * Listen for the default test profile being switched
+ */
+export async function onProfileSwitched(callback: (change: ProfileSwitch) => void): Promise<UnlistenFn> {
+  return listen<ProfileSwitch>("profile-switched", (event) => {
+    callback(event.payload);
+  });
+}
+
+export interface TestEvent {
+  port: string;
+  event: string;
+  display_name: string;
+  /** Checklist position; null for events the profile doesn't require, shown last */
+  order: number | null;
+  required: boolean;
+}
+
+/**
+ * Listen for production events as they are first seen during a test, for the checklist
+ */
+export async function onTestEvent(callback: (event: TestEvent) => void): Promise<UnlistenFn> {
+  return listen<TestEvent>("test-event", (event) => {
+    callback(event.payload);
+  });
+}
+
+export interface ConsoleLine {
+  port: string;
+  /** "flash", "test", "erase", ... */
+  operation: string;
+  line: string;
+  timestamp_ms: number;
+}
+
+/**
+ * Listen for output from every port and operation interleaved, while setConsoleStream is on
+ */
+export async function onConsoleStream(callback: (line: ConsoleLine) => void): Promise<UnlistenFn> {
+  return listen<ConsoleLine>("console-stream", (event) => {
     callback(event.payload);
   });
 }